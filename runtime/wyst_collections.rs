@@ -0,0 +1,35 @@
+// Runtime for the methods on vec and map, copied into the build directory when a program uses them
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+pub fn vec_len<T>(v: &mut Vec<T>) -> i32 {
+    v.len() as i32
+}
+
+pub fn vec_push<T>(v: &mut Vec<T>, item: T) {
+    v.push(item);
+}
+
+pub fn vec_contains<T: PartialEq>(v: &mut Vec<T>, item: T) -> bool {
+    v.contains(&item)
+}
+
+pub fn map_len<K, V>(m: &mut HashMap<K, V>) -> i32 {
+    m.len() as i32
+}
+
+// Replaces the value when the key is already there
+pub fn map_insert<K: Hash + Eq, V>(m: &mut HashMap<K, V>, key: K, value: V) {
+    m.insert(key, value);
+}
+
+pub fn map_has<K: Hash + Eq, V>(m: &mut HashMap<K, V>, key: K) -> bool {
+    m.contains_key(&key)
+}
+
+// Removing a key that isn't there does nothing
+pub fn map_remove<K: Hash + Eq, V>(m: &mut HashMap<K, V>, key: K) {
+    m.remove(&key);
+}
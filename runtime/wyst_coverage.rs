@@ -0,0 +1,35 @@
+// Runtime for --coverage, copied into the build directory of instrumented programs
+#![allow(dead_code)]
+
+use std::{collections::BTreeMap, fs::OpenOptions, io::Write, sync::Mutex};
+
+pub const REPORT: &str = "coverage.lcov";
+
+static HITS: Mutex<Vec<u64>> = Mutex::new(Vec::new());
+
+pub fn hit(probe: usize) {
+    let mut hits = HITS.lock().unwrap();
+    if hits.len() <= probe {
+        hits.resize(probe + 1, 0);
+    }
+    hits[probe] += 1;
+}
+
+/*Appends an lcov record for the file, lines maps every probe to the wyst line it was placed on*/
+pub fn write_report(file: &str, lines: &[usize]) {
+    let hits = HITS.lock().unwrap();
+    let mut counts: BTreeMap<usize, u64> = BTreeMap::new();
+    for (probe, line) in lines.iter().enumerate() {
+        *counts.entry(*line).or_insert(0) += hits.get(probe).cloned().unwrap_or(0);
+    }
+    let mut out = format!("TN:\nSF:{}\n", file);
+    for (line, count) in &counts {
+        out += format!("DA:{},{}\n", line, count).as_str();
+    }
+    let hit = counts.values().filter(|count| **count > 0).count();
+    out += format!("LF:{}\nLH:{}\nend_of_record\n", counts.len(), hit).as_str();
+    // Runs are appended so lcov merges them, e.g. one per test process
+    if let Ok(mut report) = OpenOptions::new().create(true).append(true).open(REPORT) {
+        let _ = report.write_all(out.as_bytes());
+    }
+}
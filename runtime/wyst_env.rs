@@ -0,0 +1,47 @@
+// Runtime for env::, copied into the build directory of programs that use it
+#![allow(dead_code)]
+
+use std::sync::OnceLock;
+
+static ARGS: OnceLock<Vec<&'static str>> = OnceLock::new();
+
+// The arguments the program was started with, without its own name
+pub fn args() -> Vec<&'static str> {
+    ARGS.get_or_init(|| {
+        std::env::args()
+            .skip(1)
+            .map(|arg| &*Box::leak(arg.into_boxed_str()))
+            .collect()
+    })
+    .clone()
+}
+
+// Empty when the variable isn't set
+pub fn var(name: &str) -> &'static str {
+    Box::leak(std::env::var(name).unwrap_or_default().into_boxed_str())
+}
+
+pub fn has(name: &str) -> bool {
+    std::env::var_os(name).is_some()
+}
+
+// Whether --name was given
+pub fn flag(name: &str) -> bool {
+    let long = format!("--{}", name);
+    args().iter().any(|arg| *arg == long)
+}
+
+// The value of --name value or --name=value, default when it wasn't given
+pub fn option(name: &str, default: &'static str) -> &'static str {
+    let long = format!("--{}", name);
+    let args = args();
+    for (i, arg) in args.iter().enumerate() {
+        if *arg == long {
+            return args.get(i + 1).copied().unwrap_or(default);
+        }
+        if let Some(value) = arg.strip_prefix(&long).and_then(|rest| rest.strip_prefix('=')) {
+            return value;
+        }
+    }
+    default
+}
@@ -0,0 +1,24 @@
+// Runtime for fs::, copied into the build directory of programs that use it
+#![allow(dead_code)]
+
+use std::fs;
+
+/*The contents of a file, empty when it can't be read. The text is kept for the rest of the run,
+so it can be used like a string literal*/
+pub fn read_to_string(path: &str) -> &'static str {
+    Box::leak(fs::read_to_string(path).unwrap_or_default().into_boxed_str())
+}
+
+// Replaces the file, false when it can't be written
+pub fn write(path: &str, text: &str) -> bool {
+    fs::write(path, text).is_ok()
+}
+
+pub fn exists(path: &str) -> bool {
+    fs::metadata(path).is_ok()
+}
+
+// The lines of a file without their line endings, none when it can't be read
+pub fn lines(path: &str) -> Vec<&'static str> {
+    read_to_string(path).lines().collect()
+}
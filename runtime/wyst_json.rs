@@ -0,0 +1,292 @@
+// Runtime for @derive(json), copied into the build directory when a program uses it
+#![allow(dead_code)]
+
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+pub trait ToJson {
+    fn to_json(&self) -> String;
+}
+
+pub trait FromJson: Sized {
+    fn from_json_value(value: &Json) -> Option<Self>;
+}
+
+pub fn from_str<T: FromJson>(input: &str) -> Option<T> {
+    let mut parser = Parser {
+        chars: input.chars().collect(),
+        pos: 0,
+    };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos != parser.chars.len() {
+        return None;
+    }
+    T::from_json_value(&value)
+}
+
+pub fn quote(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            '\r' => out += "\\r",
+            '\t' => out += "\\t",
+            c if (c as u32) < 0x20 => out += format!("\\u{:04x}", c as u32).as_str(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+macro_rules! json_number {
+    ($($t:ty),*) => {$(
+        impl ToJson for $t {
+            fn to_json(&self) -> String {
+                self.to_string()
+            }
+        }
+    )*};
+}
+
+json_number!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64);
+
+// A number with a fraction or out of the type's range isn't read, rather than cut to fit
+macro_rules! json_integer {
+    ($($t:ty),*) => {$(
+        impl FromJson for $t {
+            fn from_json_value(value: &Json) -> Option<$t> {
+                match value {
+                    // MAX + 1 is a power of two, so unlike MAX it's exact as an f64
+                    Json::Number(n)
+                        if n.fract() == 0.0
+                            && *n >= <$t>::MIN as f64
+                            && *n < <$t>::MAX as f64 + 1.0 =>
+                    {
+                        Some(*n as $t)
+                    }
+                    _ => None,
+                }
+            }
+        }
+    )*};
+}
+
+json_integer!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+// Floats round to the nearest value, one too big for the type isn't read
+macro_rules! json_float {
+    ($($t:ty),*) => {$(
+        impl FromJson for $t {
+            fn from_json_value(value: &Json) -> Option<$t> {
+                match value {
+                    Json::Number(n) if n.is_finite() && (*n as $t).is_finite() => Some(*n as $t),
+                    _ => None,
+                }
+            }
+        }
+    )*};
+}
+
+json_float!(f32, f64);
+
+impl ToJson for bool {
+    fn to_json(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl FromJson for bool {
+    fn from_json_value(value: &Json) -> Option<bool> {
+        match value {
+            Json::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl ToJson for String {
+    fn to_json(&self) -> String {
+        quote(self)
+    }
+}
+
+impl FromJson for String {
+    fn from_json_value(value: &Json) -> Option<String> {
+        match value {
+            Json::String(s) => Some(s.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl<T: ToJson> ToJson for Vec<T> {
+    fn to_json(&self) -> String {
+        let items: Vec<String> = self.iter().map(|item| item.to_json()).collect();
+        format!("[{}]", items.join(","))
+    }
+}
+
+impl<T: FromJson> FromJson for Vec<T> {
+    fn from_json_value(value: &Json) -> Option<Vec<T>> {
+        match value {
+            Json::Array(items) => items.iter().map(T::from_json_value).collect(),
+            _ => None,
+        }
+    }
+}
+
+impl<T: ToJson, const N: usize> ToJson for [T; N] {
+    fn to_json(&self) -> String {
+        let items: Vec<String> = self.iter().map(|item| item.to_json()).collect();
+        format!("[{}]", items.join(","))
+    }
+}
+
+impl<T: FromJson, const N: usize> FromJson for [T; N] {
+    fn from_json_value(value: &Json) -> Option<[T; N]> {
+        let items: Vec<T> = FromJson::from_json_value(value)?;
+        std::convert::TryInto::try_into(items).ok()
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.chars.get(self.pos) == Some(&c) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Option<Json> {
+        for c in word.chars() {
+            if self.chars.get(self.pos) != Some(&c) {
+                return None;
+            }
+            self.pos += 1;
+        }
+        Some(value)
+    }
+
+    fn value(&mut self) -> Option<Json> {
+        self.skip_whitespace();
+        match self.chars.get(self.pos)? {
+            'n' => self.literal("null", Json::Null),
+            't' => self.literal("true", Json::Bool(true)),
+            'f' => self.literal("false", Json::Bool(false)),
+            '"' => Some(Json::String(self.string()?)),
+            '[' => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                if self.eat(']') {
+                    return Some(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    if self.eat(']') {
+                        return Some(Json::Array(items));
+                    }
+                    if !self.eat(',') {
+                        return None;
+                    }
+                }
+            }
+            '{' => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                if self.eat('}') {
+                    return Some(Json::Object(fields));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    if !self.eat(':') {
+                        return None;
+                    }
+                    fields.push((key, self.value()?));
+                    if self.eat('}') {
+                        return Some(Json::Object(fields));
+                    }
+                    if !self.eat(',') {
+                        return None;
+                    }
+                }
+            }
+            _ => {
+                let start = self.pos;
+                while self.pos < self.chars.len()
+                    && (self.chars[self.pos].is_ascii_digit()
+                        || "+-.eE".contains(self.chars[self.pos]))
+                {
+                    self.pos += 1;
+                }
+                let number: String = self.chars[start..self.pos].iter().collect();
+                number.parse().ok().map(Json::Number)
+            }
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        if self.chars.get(self.pos) != Some(&'"') {
+            return None;
+        }
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let c = *self.chars.get(self.pos)?;
+            self.pos += 1;
+            match c {
+                '"' => return Some(out),
+                '\\' => {
+                    let escaped = *self.chars.get(self.pos)?;
+                    self.pos += 1;
+                    match escaped {
+                        'n' => out.push('\n'),
+                        'r' => out.push('\r'),
+                        't' => out.push('\t'),
+                        'b' => out.push('\u{8}'),
+                        'f' => out.push('\u{c}'),
+                        'u' => {
+                            let hex: String = self.chars.get(self.pos..self.pos + 4)?.iter().collect();
+                            self.pos += 4;
+                            out.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+                        }
+                        c => out.push(c),
+                    }
+                }
+                c => out.push(c),
+            }
+        }
+    }
+}
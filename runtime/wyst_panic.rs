@@ -0,0 +1,68 @@
+// Runtime for runtime failures, copied into the build directory of every program
+#![allow(dead_code)]
+
+use std::sync::{Mutex, OnceLock};
+
+// Called with the message and the wyst location in place of the default report
+static HOOK: Mutex<Option<fn(&str, &str)>> = Mutex::new(None);
+
+// The rust name of every function with its wyst name and where it's declared
+static SYMBOLS: OnceLock<&'static [(&'static str, &'static str)]> = OnceLock::new();
+
+/*Replaces how a failure is reported, the program still stops with 101 after the hook returns.
+Called from a cb block, e.g. to log failures somewhere else*/
+pub fn set_hook(hook: fn(&str, &str)) {
+    *HOOK.lock().unwrap() = Some(hook);
+}
+
+/*Done first thing in main. Panics of the generated rust, like an index out of bounds, go
+through fail too, with the rust location since there's no wyst one for them*/
+pub fn install(symbols: &'static [(&'static str, &'static str)]) {
+    let _ = SYMBOLS.set(symbols);
+    std::panic::set_hook(Box::new(|info| {
+        let message = match info.payload().downcast_ref::<&str>() {
+            Some(message) => message.to_string(),
+            None => match info.payload().downcast_ref::<String>() {
+                Some(message) => message.clone(),
+                None => "panicked".to_string(),
+            },
+        };
+        let location = info
+            .location()
+            .map(|at| format!("{}:{}:{} (generated)", at.file(), at.line(), at.column()))
+            .unwrap_or_default();
+        report(&message, &location);
+    }));
+}
+
+// WYST_BACKTRACE=1 adds the wyst functions that were running to the report
+fn backtrace() {
+    if std::env::var("WYST_BACKTRACE").is_ok_and(|value| value != "0") {
+        let symbols = SYMBOLS.get().copied().unwrap_or_default();
+        let trace = std::backtrace::Backtrace::force_capture().to_string();
+        eprintln!("backtrace:");
+        let mut depth = 0;
+        for line in trace.lines() {
+            if let Some((_, function)) = symbols.iter().find(|(rname, _)| line.contains(rname)) {
+                eprintln!("  {}: {}", depth, function);
+                depth += 1;
+            }
+        }
+    }
+}
+
+fn report(message: &str, location: &str) {
+    let hook = *HOOK.lock().unwrap();
+    match hook {
+        Some(hook) => hook(message, location),
+        None => eprintln!("{}\n  at {}", message, location),
+    }
+    backtrace();
+}
+
+/*Stops the program with exit status 101 after a failed assert, an overflow in checked_
+arithmetic or panic(). Nothing is cleaned up, like abort but with a report first*/
+pub fn fail(message: &str, location: &str) -> ! {
+    report(message, location);
+    std::process::exit(101)
+}
@@ -0,0 +1,28 @@
+// Runtime for exit and abort, copied into the build directory of programs that use them
+#![allow(dead_code)]
+
+use std::{io::Write, sync::Mutex};
+
+static CLEANUPS: Mutex<Vec<fn()>> = Mutex::new(Vec::new());
+
+// Runs when the program calls exit, like the coverage report that main writes when it returns
+pub fn at_exit(cleanup: fn()) {
+    CLEANUPS.lock().unwrap().push(cleanup);
+}
+
+/*Ends the program the way returning from main does: the cleanups run, newest first, and
+whatever is buffered on stdout and stderr is written out before the process exits with code*/
+pub fn exit(code: i32) -> ! {
+    let cleanups = std::mem::take(&mut *CLEANUPS.lock().unwrap());
+    for cleanup in cleanups.into_iter().rev() {
+        cleanup();
+    }
+    let _ = std::io::stdout().flush();
+    let _ = std::io::stderr().flush();
+    std::process::exit(code)
+}
+
+// Ends the program right away, no cleanups and no flushing, the exit status is that of SIGABRT
+pub fn abort() -> ! {
+    std::process::abort()
+}
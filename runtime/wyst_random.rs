@@ -0,0 +1,46 @@
+// Runtime for random::int and random::seed, copied into the build directory of programs that use them
+#![allow(dead_code)]
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// The seed of a --deterministic build, and what random::seed mixes its value with
+const SEED: u64 = 0x2545_f491_4f6c_dd1d;
+
+// 0 until the generator is seeded, xorshift never reaches it after that
+static STATE: AtomicU64 = AtomicU64::new(0);
+
+pub fn seed(value: i32) {
+    STATE.store(SEED ^ value as u32 as u64, Ordering::Relaxed);
+}
+
+fn next(deterministic: bool) -> u64 {
+    let mut x = STATE.load(Ordering::Relaxed);
+    if x == 0 {
+        x = if deterministic {
+            SEED
+        } else {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_nanos() as u64)
+                .unwrap_or(0);
+            SEED ^ nanos
+        };
+    }
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    STATE.store(x, Ordering::Relaxed);
+    x
+}
+
+// A number from low up to but not including high, low when the range is empty
+pub fn int(deterministic: bool, low: i32, high: i32) -> i32 {
+    if high <= low {
+        return low;
+    }
+    let span = (high as i64 - low as i64) as u64;
+    (low as i64 + (next(deterministic) % span) as i64) as i32
+}
@@ -0,0 +1,28 @@
+// Runtime for the methods on string, copied into the build directory when a program uses them
+#![allow(dead_code)]
+
+// Lengths and positions are in bytes, like the rust strings underneath
+pub fn len(s: &'static str) -> i32 {
+    s.len() as i32
+}
+
+// Clamped to the string, so out of range arguments give a shorter (or empty) string
+pub fn substr(s: &'static str, start: i32, len: i32) -> &'static str {
+    let start = (start.max(0) as usize).min(s.len());
+    let end = start.saturating_add(len.max(0) as usize).min(s.len());
+    s.get(start..end).unwrap_or("")
+}
+
+// -1 when the string doesn't contain needle
+pub fn find(s: &'static str, needle: &str) -> i32 {
+    s.find(needle).map_or(-1, |pos| pos as i32)
+}
+
+pub fn split(s: &'static str, separator: &str) -> Vec<&'static str> {
+    s.split(separator).collect()
+}
+
+// 0 when the string isn't a number
+pub fn to_int(s: &'static str) -> i32 {
+    s.trim().parse().unwrap_or(0)
+}
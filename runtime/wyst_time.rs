@@ -0,0 +1,38 @@
+// Runtime for time::now and time::millis, copied into the build directory of programs that use them
+#![allow(dead_code)]
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        OnceLock,
+    },
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+static START: OnceLock<Instant> = OnceLock::new();
+
+// The clock of a --deterministic build, it moves on one millisecond every time it's read
+static FAKE: AtomicU64 = AtomicU64::new(0);
+
+fn fake() -> u64 {
+    FAKE.fetch_add(1, Ordering::Relaxed)
+}
+
+// Seconds since the unix epoch
+pub fn now(deterministic: bool) -> i32 {
+    if deterministic {
+        return (fake() / 1000) as i32;
+    }
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs() as i32)
+        .unwrap_or(0)
+}
+
+// Milliseconds since the program first read the clock
+pub fn millis(deterministic: bool) -> i32 {
+    if deterministic {
+        return fake() as i32;
+    }
+    START.get_or_init(Instant::now).elapsed().as_millis() as i32
+}
@@ -0,0 +1,194 @@
+use crate::{
+    cfg::Cfg,
+    lexer::{lex, map_type, type_args, LexerState, Token, TokenType, RUST_SUFFIXES},
+    lspcom::{Problem, ProblemType},
+    messages::message,
+    parser::{Ast, AstType},
+    variable::Variables,
+};
+use std::collections::HashMap;
+
+// Reports non-void functions that can reach the end of their body without returning
+pub fn check_returns(name: &Token, body: &Token) -> Option<Problem> {
+    let cfg = Cfg::build(body);
+    let missing = cfg.missing_returns();
+    let id = *missing.first()?;
+    let state = if id == cfg.entry {
+        LexerState {
+            line: name.line,
+            column: name.column,
+        }
+    } else {
+        cfg.blocks[id].state
+    };
+    Some(Problem {
+        problem_type: ProblemType::MissingReturn,
+        problem_msg: message("missing_return", &[]),
+        state,
+        file: String::new(),
+    })
+}
+
+// Warns about the first statement of a function body that comes after an exit, abort or return
+pub fn check_unreachable(body: &Token) -> Option<Problem> {
+    let cfg = Cfg::build(body);
+    let id = cfg.unreachable()?;
+    Some(Problem {
+        problem_type: ProblemType::Unreachable,
+        problem_msg: message("unreachable", &[]),
+        state: cfg.blocks[id].state,
+        file: String::new(),
+    })
+}
+
+fn const_number(token: &Token) -> Option<i64> {
+    if token.token_type != TokenType::Number {
+        return None;
+    }
+    token.value.parse().ok()
+}
+
+// Folds an integer expression made only of literals, None when anything in it isn't constant
+pub fn const_eval(tokens: &[Token]) -> Option<i64> {
+    let mut sum: i64 = 0;
+    let mut i = 0;
+    loop {
+        let mut sign = 1;
+        while i < tokens.len() && (tokens[i].value == "-" || tokens[i].value == "+") {
+            if tokens[i].value == "-" {
+                sign = -sign;
+            }
+            i += 1;
+        }
+        let mut product = const_number(tokens.get(i)?)?;
+        i += 1;
+        while i < tokens.len() && (tokens[i].value == "*" || tokens[i].value == "/") {
+            let rhs = const_number(tokens.get(i + 1)?)?;
+            product = if tokens[i].value == "*" {
+                product.checked_mul(rhs)?
+            } else {
+                product.checked_div(rhs)?
+            };
+            i += 2;
+        }
+        sum = sum.checked_add(sign * product)?;
+        if i == tokens.len() {
+            return Some(sum);
+        }
+        if tokens[i].value != "-" && tokens[i].value != "+" {
+            return None;
+        }
+    }
+}
+
+// The digits of a literal like 4kb and its suffix, which is empty for a plain number
+pub fn split_literal(literal: &str) -> (&str, &str) {
+    let digits = literal.len()
+        - literal
+            .trim_start_matches(|c: char| c.is_ascii_digit())
+            .len();
+    literal.split_at(digits)
+}
+
+/*Replaces the literals whose suffix is in the table, like 4kb, by their value so a const can be
+folded from them. Rust's own suffixes, unknown ones and values that don't fit an int are left for
+the transpiler to report*/
+pub fn expand_suffixes(tokens: &[Token], suffixes: &HashMap<String, i64>) -> Vec<Token> {
+    tokens
+        .iter()
+        .map(|token| {
+            let (number, suffix) = split_literal(&token.value);
+            let value = (token.token_type == TokenType::Number && !RUST_SUFFIXES.contains(&suffix))
+                .then(|| suffixes.get(suffix))
+                .flatten()
+                .and_then(|factor| number.parse::<i64>().ok()?.checked_mul(*factor))
+                .filter(|value| *value <= i32::MAX as i64);
+            match value {
+                Some(value) => Token {
+                    value: value.to_string(),
+                    ..token.clone()
+                },
+                None => token.clone(),
+            }
+        })
+        .collect()
+}
+
+// Folds like const_eval, the names of consts in tokens stand for their values
+pub fn const_value(tokens: &[Token], variables: &mut Variables) -> Option<i64> {
+    let tokens: Vec<Token> = tokens
+        .iter()
+        .map(|token| {
+            let value = match token.token_type {
+                TokenType::Identifier => variables
+                    .get_mut(token.value.clone())
+                    .and_then(|var| var.value),
+                _ => None,
+            };
+            match value {
+                Some(value) => Token {
+                    token_type: TokenType::Number,
+                    value: value.to_string(),
+                    ..token.clone()
+                },
+                None => token.clone(),
+            }
+        })
+        .collect();
+    const_eval(&tokens)
+}
+
+/*The rust type and the length of `buffer<int, 4 * 16>`, an array on the stack whose length is
+worked out by const_eval. None when the length isn't a constant*/
+pub fn buffer_type(args: &str) -> Option<(String, usize)> {
+    let args = type_args(&format!("buffer<{}>", args));
+    if args.len() != 2 {
+        return None;
+    }
+    let tokens = lex(&args[1], false, LexerState { line: 1, column: 0 }).ok()?;
+    let size = usize::try_from(const_eval(&tokens)?).ok()?;
+    Some((format!("[{}; {}]", map_type(&args[0]), size), size))
+}
+
+/*Looks for indexes into fixed-size arrays that are known to be out of bounds at compile time*/
+pub fn check_indexes(asts: &[Ast], variables: &mut Variables) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    for pair in asts.windows(2) {
+        let (target, index) = (&pair[0], &pair[1]);
+        if target.ast_type != AstType::Other
+            || target.tokens.len() != 1
+            || target.tokens[0].token_type != TokenType::Identifier
+            || index.ast_type != AstType::Index
+        {
+            continue;
+        }
+        let size = match variables.get_mut(target.tokens[0].value.clone()) {
+            Some(var) => match var.size {
+                Some(size) => size,
+                None => continue,
+            },
+            None => continue,
+        };
+        let square = &index.tokens[0];
+        let state = LexerState {
+            line: square.line,
+            column: square.column,
+        };
+        if let Ok(tokens) = lex(square.value.as_str(), false, state) {
+            if let Some(value) = const_value(&tokens, variables) {
+                if value < 0 || value as usize >= size {
+                    problems.push(Problem {
+                        problem_type: ProblemType::IndexOutOfBounds,
+                        problem_msg: message(
+                            "index_out_of_bounds",
+                            &[&value, &target.tokens[0].value, &size],
+                        ),
+                        state,
+                        file: String::new(),
+                    });
+                }
+            }
+        }
+    }
+    problems
+}
@@ -0,0 +1,125 @@
+use crate::{
+    ast_text::{AST_TYPES, TOKEN_TYPES},
+    lexer::{lex, LexerState, Token},
+    parser::{Ast, Parser},
+    variable::Variables,
+};
+use serde_json::{json, Value};
+
+/*The json form of a parsed file, for tools outside the compiler. It holds the same nodes and
+tokens as the text form:
+
+    {"version": 1, "nodes": [{"type": "VariableDeceleration", "tokens": [
+        {"type": "Identifier", "value": "i32", "line": 1, "column": 0}, ...], "children": []}]}
+
+schema/ast-v1.json describes version 1. Until VERSION changes fields, node types and token
+types are only ever added, so a tool should skip what it doesn't know, read does. Renaming or
+removing one, or changing what it means, bumps VERSION and ships a new schema file next to the
+old one*/
+pub const VERSION: u64 = 1;
+pub const SCHEMA: &str = include_str!("../schema/ast-v1.json");
+
+fn token(token: &Token) -> Value {
+    json!({
+        "type": format!("{:?}", token.token_type),
+        "value": token.value,
+        "line": token.line,
+        "column": token.column,
+    })
+}
+
+fn node(ast: &Ast) -> Value {
+    json!({
+        "type": format!("{:?}", ast.ast_type),
+        "tokens": ast.tokens.iter().map(token).collect::<Vec<Value>>(),
+        "children": ast.children.iter().map(node).collect::<Vec<Value>>(),
+    })
+}
+
+pub fn dump(asts: &[Ast]) -> String {
+    let nodes: Vec<Value> = asts.iter().map(node).collect();
+    let out = json!({ "version": VERSION, "nodes": nodes });
+    serde_json::to_string_pretty(&out).expect("ast json") + "\n"
+}
+
+pub fn dump_file(code: &str, path: &str) -> Result<String, String> {
+    match lex(code, false, LexerState { line: 1, column: 0 }) {
+        Ok(tokens) => Ok(dump(&Parser::new(tokens, Variables::new()).parse_tree())),
+        Err((state, _tokens)) => Err(format!(
+            "Invalid syntax at {}:{}:{}",
+            path, state.line, state.column
+        )),
+    }
+}
+
+/*A token, None for one of a type this version doesn't know. Err when it isn't a token at all*/
+fn read_token(value: &Value) -> Result<Option<Token>, ()> {
+    let token_type = value["type"].as_str().ok_or(())?;
+    let value_text = value["value"].as_str().ok_or(())?;
+    let line = value["line"].as_u64().ok_or(())? as usize;
+    let column = value["column"].as_u64().ok_or(())? as usize;
+    Ok(TOKEN_TYPES
+        .iter()
+        .find(|known| format!("{:?}", known) == token_type)
+        .map(|known| Token {
+            token_type: *known,
+            value: value_text.to_string(),
+            line,
+            column,
+        }))
+}
+
+/*The nodes of an array, `at` is where it is for the errors. Nodes and tokens of a type added
+after this version are left out*/
+fn read_nodes(nodes: &[Value], at: &str) -> Result<Vec<Ast>, String> {
+    let mut asts = Vec::new();
+    for (i, node) in nodes.iter().enumerate() {
+        let at = format!("{}{}", at, i);
+        let name = node["type"]
+            .as_str()
+            .ok_or_else(|| format!("node {}: expected a type", at))?;
+        let Some(ast_type) = AST_TYPES
+            .iter()
+            .find(|known| format!("{:?}", known) == name)
+        else {
+            continue;
+        };
+        let mut tokens = Vec::new();
+        for value in node["tokens"]
+            .as_array()
+            .ok_or_else(|| format!("node {}: expected a tokens array", at))?
+        {
+            let token = read_token(value).map_err(|_| format!("node {}: invalid token", at))?;
+            tokens.extend(token);
+        }
+        let children = node["children"]
+            .as_array()
+            .ok_or_else(|| format!("node {}: expected a children array", at))?;
+        asts.push(Ast {
+            tokens,
+            ast_type: ast_type.clone(),
+            children: read_nodes(children, &format!("{}.", at))?,
+        });
+    }
+    Ok(asts)
+}
+
+/*Reads the output of dump back into nodes. Another version is refused rather than read half
+right, fields this version doesn't know are ignored*/
+pub fn read(text: &str) -> Result<Vec<Ast>, String> {
+    let root: Value = serde_json::from_str(text).map_err(|err| err.to_string())?;
+    match root["version"].as_u64() {
+        Some(VERSION) => {}
+        Some(version) => {
+            return Err(format!(
+                "ast version {} isn't one this wyst reads ({})",
+                version, VERSION
+            ))
+        }
+        None => return Err("expected a version field".to_string()),
+    }
+    let nodes = root["nodes"]
+        .as_array()
+        .ok_or_else(|| "expected a nodes array".to_string())?;
+    read_nodes(nodes, "")
+}
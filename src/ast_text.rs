@@ -0,0 +1,683 @@
+use crate::{
+    lexer::{lex, LexerState, Token, TokenType},
+    parser::{Ast, AstType, Parser},
+    variable::Variables,
+};
+
+/*The canonical text form of a parsed file. A header line, then every node as its type on a
+line of its own followed by one indented line per token, and then its children indented one
+step further the same way:
+
+    wyst-ast 1
+    Return
+      Keyword 2:2 "return"
+      Identifier 2:9 "x"
+      Other
+        Identifier 2:9 "x"
+
+Token values are quoted and escaped like rust strings, so any text survives a round trip*/
+pub const HEADER: &str = "wyst-ast 1";
+
+pub const AST_TYPES: [AstType; 33] = [
+    AstType::Ref,
+    AstType::FunctionDeceleration,
+    AstType::StructDeceleration,
+    AstType::StructCall,
+    AstType::StructVar,
+    AstType::VoidFunctionDeceleration,
+    AstType::Namespace,
+    AstType::VariableDeceleration,
+    AstType::PointerDeceleration,
+    AstType::MutVariableDeceleration,
+    AstType::ConstDeceleration,
+    AstType::ArrayDeceleration,
+    AstType::ArrayLiteral,
+    AstType::Index,
+    AstType::For,
+    AstType::While,
+    AstType::If,
+    AstType::ElseIf,
+    AstType::Else,
+    AstType::Include,
+    AstType::IncludeLocal,
+    AstType::Pragma,
+    AstType::CodeBlock,
+    AstType::Json,
+    AstType::Impl,
+    AstType::StaticExecution,
+    AstType::Attribute,
+    AstType::Intrinsic,
+    AstType::MethodCall,
+    AstType::TypeAlias,
+    AstType::Return,
+    AstType::Error,
+    AstType::Other,
+];
+
+pub const TOKEN_TYPES: [TokenType; 34] = [
+    TokenType::Keyword,
+    TokenType::Keyword1,
+    TokenType::Keyword2,
+    TokenType::Newline,
+    TokenType::Whitespace,
+    TokenType::Number,
+    TokenType::Identifier,
+    TokenType::Ptr,
+    TokenType::Operator,
+    TokenType::SecondOperator,
+    TokenType::Equal,
+    TokenType::NotEqual,
+    TokenType::LessEqual,
+    TokenType::GreaterEqual,
+    TokenType::And,
+    TokenType::Or,
+    TokenType::ShiftLeft,
+    TokenType::ShiftRight,
+    TokenType::PathSep,
+    TokenType::Assign,
+    TokenType::Ampersand,
+    TokenType::Star,
+    TokenType::Colon,
+    TokenType::Semicolon,
+    TokenType::Round,
+    TokenType::Curly,
+    TokenType::Square,
+    TokenType::Angle,
+    TokenType::Include,
+    TokenType::Pragma,
+    TokenType::String,
+    TokenType::Comment,
+    TokenType::StaticExecution,
+    TokenType::Attribute,
+];
+
+fn dump_nodes(asts: &[Ast], indent: &str, out: &mut String) {
+    for ast in asts {
+        *out += format!("{}{:?}\n", indent, ast.ast_type).as_str();
+        for token in &ast.tokens {
+            *out += format!(
+                "{}  {:?} {}:{} {:?}\n",
+                indent, token.token_type, token.line, token.column, token.value
+            )
+            .as_str();
+        }
+        dump_nodes(&ast.children, &format!("{}  ", indent), out);
+    }
+}
+
+pub fn dump(asts: &[Ast]) -> String {
+    let mut out = String::from(HEADER);
+    out += "\n";
+    dump_nodes(asts, "", &mut out);
+    out
+}
+
+pub fn dump_file(code: &str, path: &str) -> Result<String, String> {
+    match lex(code, false, LexerState { line: 1, column: 0 }) {
+        Ok(tokens) => Ok(dump(&Parser::new(tokens, Variables::new()).parse_tree())),
+        Err((state, _tokens)) => Err(format!(
+            "Invalid syntax at {}:{}:{}",
+            path, state.line, state.column
+        )),
+    }
+}
+
+// Reverses the escapes {:?} puts in a string, the quotes included
+fn unquote(text: &str) -> Option<String> {
+    let inner = text.strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => out.push('\n'),
+            't' => out.push('\t'),
+            'r' => out.push('\r'),
+            '0' => out.push('\0'),
+            '\\' => out.push('\\'),
+            '"' => out.push('"'),
+            '\'' => out.push('\''),
+            'u' => {
+                let code: String = chars.by_ref().skip(1).take_while(|c| *c != '}').collect();
+                out.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+            }
+            _ => return None,
+        }
+    }
+    Some(out)
+}
+
+fn token(line: &str) -> Option<Token> {
+    let (token_type, rest) = line.split_once(' ')?;
+    let (position, value) = rest.split_once(' ')?;
+    let (row, column) = position.split_once(':')?;
+    Some(Token {
+        token_type: *TOKEN_TYPES
+            .iter()
+            .find(|known| format!("{:?}", known) == token_type)?,
+        value: unquote(value)?,
+        line: row.parse().ok()?,
+        column: column.parse().ok()?,
+    })
+}
+
+// Closes the innermost open node, it becomes the last child of the one around it
+fn close(open: &mut Vec<Ast>, asts: &mut Vec<Ast>) {
+    if let Some(ast) = open.pop() {
+        match open.last_mut() {
+            Some(parent) => parent.children.push(ast),
+            None => asts.push(ast),
+        }
+    }
+}
+
+/*Reads the output of dump back into nodes. The indentation of a line says which of the nodes
+it's in belongs to, the ones that are still open are kept from the outermost in*/
+pub fn read(text: &str) -> Result<Vec<Ast>, String> {
+    let mut lines = text.lines().enumerate();
+    match lines.next() {
+        Some((_, HEADER)) => {}
+        _ => return Err(format!("expected '{}' on the first line", HEADER)),
+    }
+    let mut asts: Vec<Ast> = Vec::new();
+    let mut open: Vec<Ast> = Vec::new();
+    for (i, line) in lines {
+        if line.is_empty() {
+            continue;
+        }
+        let content = line.trim_start_matches(' ');
+        let indent = line.len() - content.len();
+        if indent % 2 != 0 {
+            return Err(format!("{}: odd indentation", i + 1));
+        }
+        let depth = indent / 2;
+        if content.contains(' ') {
+            if depth == 0 || depth > open.len() {
+                return Err(format!("{}: token outside of a node", i + 1));
+            }
+            let token = token(content).ok_or_else(|| format!("{}: invalid token", i + 1))?;
+            while open.len() > depth {
+                close(&mut open, &mut asts);
+            }
+            open.last_mut().expect("open node").tokens.push(token);
+        } else {
+            if depth > open.len() {
+                return Err(format!("{}: node nested too deep", i + 1));
+            }
+            let ast_type = AST_TYPES
+                .iter()
+                .find(|known| format!("{:?}", known) == content)
+                .ok_or_else(|| format!("{}: unknown node type '{}'", i + 1, content))?;
+            while open.len() > depth {
+                close(&mut open, &mut asts);
+            }
+            open.push(Ast {
+                tokens: vec![],
+                ast_type: ast_type.clone(),
+                children: vec![],
+            });
+        }
+    }
+    while !open.is_empty() {
+        close(&mut open, &mut asts);
+    }
+    Ok(asts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast_json;
+    use proptest::{
+        collection::vec,
+        prelude::*,
+        sample::select,
+        strategy::{LazyJust, ValueTree},
+        test_runner::TestRunner,
+    };
+
+    const CASES: u32 = 256;
+    // Fewer for the tests that lex and parse, which builds the lexer's regexes on every call
+    const PROGRAMS: u32 = 32;
+
+    // Characters a token can hold that the escaping has to get right
+    const CHARS: [char; 16] = [
+        'a', 'Z', '0', ' ', '"', '\'', '\\', '\n', '\t', '\r', '\0', '{', 'é', '\u{7f}', '\u{301}',
+        '😀',
+    ];
+
+    fn any_token() -> impl Strategy<Value = Token> {
+        (
+            select(&TOKEN_TYPES[..]),
+            vec(select(&CHARS[..]), 0..8),
+            1..10_000usize,
+            0..200usize,
+        )
+            .prop_map(|(token_type, chars, line, column)| Token {
+                token_type,
+                value: chars.into_iter().collect(),
+                line,
+                column,
+            })
+    }
+
+    // Any node type with a few tokens of any type, and children down to `depth` more levels
+    fn any_asts(depth: usize) -> BoxedStrategy<Vec<Ast>> {
+        let children = match depth {
+            0 => LazyJust::new(Vec::new).boxed(),
+            _ => prop_oneof![LazyJust::new(Vec::new), any_asts(depth - 1)].boxed(),
+        };
+        let ast = (select(&AST_TYPES[..]), vec(any_token(), 0..5), children).prop_map(
+            |(ast_type, tokens, children)| Ast {
+                tokens,
+                ast_type,
+                children,
+            },
+        );
+        vec(ast, 0..6).boxed()
+    }
+
+    fn has_children(asts: &[Ast]) -> bool {
+        asts.iter().any(|ast| !ast.children.is_empty())
+    }
+
+    // Some of the trees have to be nested for the round trips to mean anything
+    fn check_nesting<S: Strategy>(strategy: S, cases: u32, nested: impl Fn(&S::Value) -> bool) {
+        let mut runner = TestRunner::deterministic();
+        let count = (0..cases)
+            .filter(|_| nested(&strategy.new_tree(&mut runner).unwrap().current()))
+            .count() as u32;
+        assert!(count > cases / 4, "only {} nested of {}", count, cases);
+    }
+
+    #[test]
+    fn trees_are_nested() {
+        check_nesting(any_asts(3), CASES, |asts| has_children(asts));
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(CASES))]
+
+        #[test]
+        fn text_round_trip(asts in any_asts(3)) {
+            let back = read(&dump(&asts)).map_err(TestCaseError::fail)?;
+            prop_assert_eq!(back, asts);
+        }
+
+        #[test]
+        fn json_round_trip(asts in any_asts(3)) {
+            let back = ast_json::read(&ast_json::dump(&asts)).map_err(TestCaseError::fail)?;
+            prop_assert_eq!(back, asts);
+        }
+
+        // The canonical form is a fixed point, dumping what was read gives the same text
+        #[test]
+        fn dump_is_canonical(asts in any_asts(3)) {
+            let text = dump(&asts);
+            let again = dump(&read(&text).map_err(TestCaseError::fail)?);
+            prop_assert_eq!(again, text);
+        }
+    }
+
+    // A newer wyst can add node and token types within a version, an older one leaves them out
+    #[test]
+    fn json_skips_unknown_types() {
+        let text = r#"{"version": 1, "nodes": [
+            {"type": "Return", "tokens": [
+                {"type": "Keyword", "value": "return", "line": 1, "column": 0},
+                {"type": "Lifetime", "value": "'a", "line": 1, "column": 7}
+            ], "children": [
+                {"type": "Closure", "tokens": [], "children": []}
+            ]},
+            {"type": "Closure", "tokens": [], "children": []}
+        ]}"#;
+        let asts = ast_json::read(text).expect("read");
+        assert_eq!(asts.len(), 1);
+        assert_eq!(asts[0].ast_type, AstType::Return);
+        assert_eq!(asts[0].tokens.len(), 1);
+        assert!(asts[0].children.is_empty());
+    }
+
+    const NAMES: [&str; 4] = ["a", "b", "total", "x2"];
+
+    fn token(token_type: TokenType, value: &str) -> Token {
+        Token {
+            token_type,
+            value: value.to_string(),
+            line: 0,
+            column: 0,
+        }
+    }
+
+    fn other(token_type: TokenType, value: &str) -> Ast {
+        Ast {
+            tokens: vec![token(token_type, value)],
+            ast_type: AstType::Other,
+            children: vec![],
+        }
+    }
+
+    fn any_name() -> impl Strategy<Value = &'static str> {
+        select(&NAMES[..])
+    }
+
+    fn any_operand() -> impl Strategy<Value = Token> {
+        prop_oneof![
+            any_name().prop_map(|name| token(TokenType::Identifier, name)),
+            (0..100u32).prop_map(|number| token(TokenType::Number, &number.to_string())),
+        ]
+    }
+
+    const OPS: [(TokenType, &str); 3] = [
+        (TokenType::Operator, "+"),
+        (TokenType::Operator, "-"),
+        (TokenType::Star, "*"),
+    ];
+
+    /*Operands with + - * between them, the parser gives every one of them its own node. A *
+    only comes after a number, `name * name` is read as a pointer declaration, so after a name
+    it's a + instead*/
+    fn any_expr() -> impl Strategy<Value = Vec<Token>> {
+        let rest = vec((select(&OPS[..]), any_operand()), 0..3);
+        (any_operand(), rest).prop_map(|(first, rest)| {
+            let mut tokens = vec![first];
+            for ((kind, op), operand) in rest {
+                let after_number = tokens.last().unwrap().token_type == TokenType::Number;
+                match kind == TokenType::Star && !after_number {
+                    true => tokens.push(token(TokenType::Operator, "+")),
+                    false => tokens.push(token(kind, op)),
+                }
+                tokens.push(operand);
+            }
+            tokens
+        })
+    }
+
+    fn any_cond() -> impl Strategy<Value = Token> {
+        (any_name(), select(&["<", ">", "=="][..]), 0..10u32).prop_map(|(name, cmp, number)| {
+            token(TokenType::Round, &format!("{} {} {}", name, cmp, number))
+        })
+    }
+
+    /*A node with a bracketed body, the body token is left empty like strip leaves it, its
+    contents are the children*/
+    fn block(ast_type: AstType, mut tokens: Vec<Token>, children: Vec<Ast>) -> Ast {
+        tokens.push(token(TokenType::Curly, ""));
+        Ast {
+            tokens,
+            ast_type,
+            children,
+        }
+    }
+
+    fn alone(token: Token) -> Ast {
+        Ast {
+            tokens: vec![token],
+            ast_type: AstType::Other,
+            children: vec![],
+        }
+    }
+
+    // `name = expr;`, the name declared on the spot or not
+    fn any_assignment() -> impl Strategy<Value = Vec<Ast>> {
+        (any_name(), any::<bool>(), any_expr()).prop_map(|(name, declared, expr)| {
+            let mut asts = vec![match declared {
+                true => Ast {
+                    tokens: vec![
+                        token(TokenType::Identifier, "i32"),
+                        token(TokenType::Identifier, name),
+                    ],
+                    ast_type: AstType::VariableDeceleration,
+                    children: vec![],
+                },
+                false => other(TokenType::Identifier, name),
+            }];
+            asts.push(other(TokenType::Assign, "="));
+            asts.extend(expr.into_iter().map(alone));
+            asts.push(other(TokenType::Semicolon, ";"));
+            asts
+        })
+    }
+
+    fn any_call() -> impl Strategy<Value = Vec<Ast>> {
+        let arg = prop_oneof![Just(token(TokenType::String, "\"text\"")), any_operand()];
+        (any_name(), vec(arg, 0..4)).prop_map(|(name, args)| {
+            let mut children = Vec::new();
+            for (i, arg) in args.into_iter().enumerate() {
+                if i > 0 {
+                    children.push(other(TokenType::SecondOperator, ","));
+                }
+                children.push(alone(arg));
+            }
+            vec![
+                other(TokenType::Identifier, name),
+                Ast {
+                    tokens: vec![token(TokenType::Round, "")],
+                    ast_type: AstType::Other,
+                    children,
+                },
+                other(TokenType::Semicolon, ";"),
+            ]
+        })
+    }
+
+    fn any_return() -> impl Strategy<Value = Vec<Ast>> {
+        any_expr().prop_map(|expr| {
+            let mut tokens = vec![token(TokenType::Keyword, "return")];
+            tokens.extend(expr.iter().cloned());
+            vec![
+                Ast {
+                    tokens,
+                    ast_type: AstType::Return,
+                    children: expr.into_iter().map(alone).collect(),
+                },
+                other(TokenType::Semicolon, ";"),
+            ]
+        })
+    }
+
+    /*Statements as parse_tree gives them: declarations, assignments, calls and returns, and if,
+    else and while with bodies down to `depth` more levels*/
+    fn any_statements(depth: usize) -> BoxedStrategy<Vec<Ast>> {
+        let statement = match depth {
+            0 => prop_oneof![2 => any_assignment(), 1 => any_call(), 1 => any_return()].boxed(),
+            _ => {
+                let body = || any_statements(depth - 1);
+                let branch = (any_cond(), body(), proptest::option::of(body())).prop_map(
+                    |(cond, body, otherwise)| {
+                        let mut asts = vec![block(
+                            AstType::If,
+                            vec![token(TokenType::Keyword1, "if"), cond],
+                            body,
+                        )];
+                        if let Some(body) = otherwise {
+                            let tokens = vec![token(TokenType::Keyword2, "else")];
+                            asts.push(block(AstType::Else, tokens, body));
+                        }
+                        asts
+                    },
+                );
+                let repeat = (any_cond(), body()).prop_map(|(cond, body)| {
+                    let tokens = vec![token(TokenType::Keyword1, "while"), cond];
+                    vec![block(AstType::While, tokens, body)]
+                });
+                prop_oneof![
+                    2 => any_assignment(),
+                    1 => any_call(),
+                    1 => any_return(),
+                    1 => branch,
+                    1 => repeat,
+                ]
+                .boxed()
+            }
+        };
+        vec(statement, 0..4)
+            .prop_map(|statements| statements.into_iter().flatten().collect())
+            .boxed()
+    }
+
+    // A few functions, with or without a value, and their bodies
+    fn any_program() -> impl Strategy<Value = Vec<Ast>> {
+        let function = (
+            any::<bool>(),
+            select(&["f", "step", "run"][..]),
+            select(&["", "int a", "int a, int b"][..]),
+            any_statements(2),
+        )
+            .prop_map(|(value, name, params, body)| {
+                let (ast_type, returns) = match value {
+                    true => (AstType::FunctionDeceleration, "i32"),
+                    false => (AstType::VoidFunctionDeceleration, "void"),
+                };
+                let tokens = vec![
+                    token(TokenType::Identifier, returns),
+                    token(TokenType::Identifier, name),
+                    token(TokenType::Round, params),
+                ];
+                block(ast_type, tokens, body)
+            });
+        vec(function, 1..4)
+    }
+
+    // The token parse_tree parses into the children of a node
+    fn body(ast: &Ast) -> Option<usize> {
+        if ast.ast_type == AstType::Return {
+            return None;
+        }
+        let at = |token_type| {
+            ast.tokens
+                .iter()
+                .position(|token: &Token| token.token_type == token_type)
+        };
+        at(TokenType::Curly).or_else(|| at(TokenType::Round))
+    }
+
+    // Whether the formatter puts a space between two tokens on a line
+    fn spaced(prev: &Token, next: &Token) -> bool {
+        !(next.token_type == TokenType::Semicolon
+            || next.token_type == TokenType::SecondOperator
+            || (next.token_type == TokenType::Round && prev.token_type == TokenType::Identifier))
+    }
+
+    /*Formats nodes as source, a statement to a line with bodies indented two spaces a level. A
+    body is written from the children, not the text of its token. `inline` keeps everything on
+    one line, for the arguments of a call*/
+    fn format(asts: &[Ast], depth: usize, inline: bool) -> String {
+        let indent = "  ".repeat(depth);
+        let mut out = String::new();
+        let mut prev: Option<&Token> = None;
+        for ast in asts {
+            for (i, token) in ast.tokens.iter().enumerate() {
+                match prev {
+                    None if !inline => out += indent.as_str(),
+                    Some(prev) if spaced(prev, token) => out.push(' '),
+                    _ => {}
+                }
+                let text = match (token.token_type, Some(i) == body(ast)) {
+                    (TokenType::Curly, true) => format!(
+                        "{{\n{}{}}}",
+                        format(&ast.children, depth + 1, false),
+                        indent
+                    ),
+                    (TokenType::Round, true) => {
+                        format!("({})", format(&ast.children, depth, true))
+                    }
+                    (TokenType::Curly, false) => format!("{{{}}}", token.value),
+                    (TokenType::Round, false) => format!("({})", token.value),
+                    _ => token.value.clone(),
+                };
+                out += text.as_str();
+                prev = Some(token);
+            }
+            let ends = ast.tokens.last().map(|token| token.token_type);
+            if !inline && matches!(ends, Some(TokenType::Semicolon | TokenType::Curly)) {
+                out.push('\n');
+                prev = None;
+            }
+        }
+        out
+    }
+
+    fn parse(code: &str) -> Result<Vec<Ast>, String> {
+        let tokens = lex(code, false, LexerState { line: 1, column: 0 })
+            .map_err(|(state, _)| format!("doesn't lex at {}:{}", state.line, state.column))?;
+        let mut parser = Parser::new(tokens, Variables::new());
+        let asts = parser.parse_tree();
+        match parser.errors.first() {
+            Some(err) => Err(format!(
+                "{}:{}: {}",
+                err.state.line, err.state.column, err.message
+            )),
+            None => Ok(asts),
+        }
+    }
+
+    // Without the positions and the text of the body tokens, what random_program makes
+    fn strip(asts: Vec<Ast>) -> Vec<Ast> {
+        asts.into_iter()
+            .map(|mut ast| {
+                let body = body(&ast);
+                for (i, token) in ast.tokens.iter_mut().enumerate() {
+                    token.line = 0;
+                    token.column = 0;
+                    if Some(i) == body {
+                        token.value.clear();
+                    }
+                }
+                ast.children = strip(ast.children);
+                ast
+            })
+            .collect()
+    }
+
+    /*Formats a program and parses it back, it has to be the program again once strip takes out
+    what formatting decides. Formatting what was parsed has to give source that parses into
+    exactly that, positions included, and the text form has to read it back*/
+    fn format_and_parse(program: &[Ast]) -> Result<Vec<Ast>, String> {
+        let parsed = parse(&format(program, 0, false))?;
+        let code = format(&parsed, 0, false);
+        let again = parse(&code)?;
+        if again != parsed {
+            return Err(format!(
+                "formatted again as\n{}parses as\n{}",
+                code,
+                dump(&again)
+            ));
+        }
+        let back = read(&dump(&parsed))?;
+        if back != parsed {
+            return Err(format!("read back as\n{}", dump(&back)));
+        }
+        let stripped = strip(back);
+        if stripped != program {
+            return Err(format!(
+                "parses as\n{}expected\n{}",
+                dump(&stripped),
+                dump(program)
+            ));
+        }
+        Ok(parsed)
+    }
+
+    #[test]
+    fn programs_are_nested() {
+        check_nesting(any_program(), PROGRAMS, |program| {
+            program.iter().any(|ast| has_children(&ast.children))
+        });
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(PROGRAMS))]
+
+        #[test]
+        fn format_round_trip(program in any_program()) {
+            if let Err(err) = format_and_parse(&program) {
+                let code = format(&program, 0, false);
+                return Err(TestCaseError::fail(format!("{}\n{}", err, code)));
+            }
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use crate::{
+    lexer::{LexerState, TokenType},
+    lspcom::{did_you_mean, Problem, ProblemType},
+    messages::message,
+    parser::{Ast, AstType},
+    variable::Variables,
+};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+pub const ATTRIBUTES: [&str; 5] = ["derive", "deprecated", "test", "packed", "align"];
+pub const DERIVES: [&str; 4] = ["to_string", "equals", "clone", "json"];
+
+pub fn name(attr: &Ast) -> &str {
+    attr.tokens[0].value.trim_start_matches('@')
+}
+
+fn state(attr: &Ast) -> LexerState {
+    LexerState {
+        line: attr.tokens[0].line,
+        column: attr.tokens[0].column,
+    }
+}
+
+// Splits the argument list of an attribute, `@derive(clone, equals)` gives ["clone", "equals"]
+pub fn args(attr: &Ast) -> Vec<String> {
+    if attr.tokens.len() < 2 {
+        return vec![];
+    }
+    attr.tokens[1]
+        .value
+        .split(',')
+        .map(|arg| arg.trim().to_string())
+        .filter(|arg| !arg.is_empty())
+        .collect()
+}
+
+// None if the attribute doesn't exist, otherwise whether it can be put on the declaration
+fn allowed(name: &str, ast_type: &AstType) -> Option<bool> {
+    match name {
+        "derive" | "packed" | "align" => Some(*ast_type == AstType::StructDeceleration),
+        "test" => Some(
+            *ast_type == AstType::FunctionDeceleration
+                || *ast_type == AstType::VoidFunctionDeceleration,
+        ),
+        "deprecated" => Some(
+            *ast_type == AstType::FunctionDeceleration
+                || *ast_type == AstType::VoidFunctionDeceleration
+                || *ast_type == AstType::StructDeceleration,
+        ),
+        _ => None,
+    }
+}
+
+/*Checks the attributes written in front of a declaration*/
+pub fn check(attributes: &[Ast], ast: &Ast) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    for attr in attributes {
+        match allowed(name(attr), &ast.ast_type) {
+            Some(true) => {}
+            Some(false) => problems.push(Problem {
+                problem_type: ProblemType::MisplacedAttribute,
+                problem_msg: message(
+                    "misplaced_attribute",
+                    &[&name(attr), &format!("{:?}", ast.ast_type)],
+                ),
+                state: state(attr),
+                file: String::new(),
+            }),
+            None => problems.push(Problem {
+                problem_type: ProblemType::UnknownAttribute,
+                problem_msg: message(
+                    "unknown_attribute",
+                    &[
+                        &name(attr),
+                        &did_you_mean(name(attr), ATTRIBUTES.iter().copied()),
+                    ],
+                ),
+                state: state(attr),
+                file: String::new(),
+            }),
+        }
+    }
+    problems
+}
+
+pub fn has(attributes: &[Ast], attr_name: &str) -> bool {
+    attributes.iter().any(|attr| name(attr) == attr_name)
+}
+
+/*The since/message part of the warning for `@deprecated("use area", since="1.2")`, both
+arguments can be left out*/
+fn deprecation(attr: &Ast) -> String {
+    static MESSAGE: Lazy<Regex> = Lazy::new(|| Regex::new(r#"^\s*"((?:[^"\\]|\\.)*)""#).unwrap());
+    static SINCE: Lazy<Regex> = Lazy::new(|| Regex::new(r#"since\s*=\s*"([^"]*)""#).unwrap());
+    let args = attr.tokens.get(1).map_or("", |token| token.value.as_str());
+    let mut note = String::new();
+    if let Some(caps) = SINCE.captures(args) {
+        note += message("deprecated_since", &[&&caps[1]]).as_str();
+    }
+    if let Some(caps) = MESSAGE.captures(args) {
+        note += ": ";
+        note += &caps[1];
+    }
+    note
+}
+
+/*Marks the functions and structs that have @deprecated in front of them, before anything is
+transpiled so uses above the declaration are caught too*/
+pub fn mark_deprecated(asts: &[Ast], variables: &mut Variables) {
+    let mut attributes: Vec<&Ast> = Vec::new();
+    for ast in asts {
+        if ast.ast_type == AstType::Attribute {
+            attributes.push(ast);
+            continue;
+        }
+        if ast
+            .tokens
+            .first()
+            .is_none_or(|token| token.token_type == TokenType::Comment)
+        {
+            continue;
+        }
+        let decl = match ast.ast_type {
+            AstType::FunctionDeceleration | AstType::VoidFunctionDeceleration => &ast.tokens[1],
+            AstType::StructDeceleration => &ast.tokens[0],
+            _ => {
+                attributes.clear();
+                continue;
+            }
+        };
+        if let Some(attr) = attributes.iter().find(|attr| name(attr) == "deprecated") {
+            if let Some(var) = variables.get_mut(decl.value.clone()) {
+                var.deprecated = Some(deprecation(attr));
+            }
+        }
+        attributes.clear();
+    }
+}
+
+pub fn derives(attributes: &[Ast], problems: &mut Vec<Problem>) -> Vec<String> {
+    let mut derives: Vec<String> = Vec::new();
+    for attr in attributes {
+        if name(attr) != "derive" {
+            continue;
+        }
+        for arg in args(attr) {
+            if !DERIVES.contains(&arg.as_str()) {
+                problems.push(Problem {
+                    problem_type: ProblemType::UnknownAttribute,
+                    problem_msg: message("unknown_derive", &[&arg, &DERIVES.join(", ")]),
+                    state: state(attr),
+                    file: String::new(),
+                });
+            } else if !derives.contains(&arg) {
+                derives.push(arg);
+            }
+        }
+    }
+    derives
+}
+
+/*The repr for @packed or @align(n) on a struct, the fields stay in declaration order like in C.
+Rust can't do both on one struct, and an alignment has to be a power of two*/
+pub fn layout(attributes: &[Ast], problems: &mut Vec<Problem>) -> Option<String> {
+    let packed = attributes.iter().find(|attr| name(attr) == "packed");
+    let align = attributes.iter().find(|attr| name(attr) == "align");
+    if let (Some(_), Some(align)) = (packed, align) {
+        problems.push(Problem {
+            problem_type: ProblemType::StructLayout,
+            problem_msg: message("packed_and_aligned", &[]),
+            state: state(align),
+            file: String::new(),
+        });
+        return None;
+    }
+    if packed.is_some() {
+        return Some("#[repr(C, packed)]\n".to_string());
+    }
+    let align = align?;
+    let arg = args(align).join(", ");
+    match arg.parse::<u32>() {
+        Ok(bytes) if bytes.is_power_of_two() && bytes <= 1 << 29 => {
+            Some(format!("#[repr(C, align({}))]\n", bytes))
+        }
+        _ => {
+            problems.push(Problem {
+                problem_type: ProblemType::StructLayout,
+                problem_msg: message("align_value", &[&arg]),
+                state: state(align),
+                file: String::new(),
+            });
+            None
+        }
+    }
+}
+
+// The methods a derive adds to the struct
+pub fn derived_methods(derive: &str) -> Vec<&'static str> {
+    match derive {
+        "to_string" => vec!["to_string"],
+        "equals" => vec!["equals"],
+        "clone" => vec!["clone"],
+        "json" => vec!["to_json", "from_json"],
+        _ => vec![],
+    }
+}
+
+/*A packed struct's fields can't be borrowed where they may be misaligned, to_json copies them out
+with `{ self.x }` instead*/
+fn expand_json(name: &str, fields: &[(String, String)], packed: bool) -> String {
+    let mut to_json = String::new();
+    let mut from_json = String::new();
+    for (i, (field, rname)) in fields.iter().enumerate() {
+        if i > 0 {
+            to_json += "    out += \",\";\n";
+        }
+        let value = if packed {
+            format!("{{ self.{} }}", rname)
+        } else {
+            format!("self.{}", rname)
+        };
+        to_json += format!(
+            "    out += \"\\\"{}\\\":\";\n    out += &crate::wyst_json::ToJson::to_json(&{});\n",
+            field, value
+        )
+        .as_str();
+        from_json += format!(
+            "      {}: crate::wyst_json::FromJson::from_json_value(value.get(\"{}\")?)?,\n",
+            rname, field
+        )
+        .as_str();
+    }
+    let mut out = format!(
+        "impl crate::wyst_json::ToJson for {} {}\n  fn to_json(&self) -> String {}\n    let mut out = String::from(\"{}\");\n{}    out += \"{}\";\n    out\n  {}\n{}\n",
+        name, "{", "{", "{", to_json, "}", "}", "}"
+    );
+    out += format!(
+        "impl crate::wyst_json::FromJson for {} {}\n  fn from_json_value(value: &crate::wyst_json::Json) -> Option<{}> {}\n    Some({} {}\n{}    {})\n  {}\n{}\n",
+        name, "{", name, "{", name, "{", from_json, "}", "}", "}"
+    )
+    .as_str();
+    out += format!(
+        "impl {} {}\n  pub fn to_json(&self) -> String {}\n    crate::wyst_json::ToJson::to_json(self)\n  {}\n  pub fn from_json(json: &str) -> Option<{}> {}\n    crate::wyst_json::from_str(json)\n  {}\n{}\n",
+        name, "{", "{", "}", name, "{", "}", "}"
+    )
+    .as_str();
+    out
+}
+
+/*Generates the rust around a struct for its derives, returns the code that goes before and after it.
+fields are the (name, rust name) pairs of the struct in declaration order, packed is whether it's
+@packed. Every struct gets Debug whatever it derives, that's what debug() prints it with*/
+pub fn expand_derives(
+    name: &str,
+    derives: &[String],
+    fields: &[(String, String)],
+    packed: bool,
+) -> (String, String) {
+    let mut traits: Vec<&str> = vec!["Debug"];
+    let mut after = String::new();
+    for derive in derives {
+        match derive.as_str() {
+            "to_string" => {
+                after += format!(
+                    "impl std::fmt::Display for {} {}\n  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {}\n    write!(f, \"{}\", self)\n  {}\n{}\n",
+                    name, "{", "{", "{:?}", "}", "}"
+                )
+                .as_str();
+            }
+            "equals" => {
+                traits.push("PartialEq");
+                after += format!(
+                    "impl {} {}\n  pub fn equals(&self, other: &{}) -> bool {}\n    self == other\n  {}\n{}\n",
+                    name, "{", name, "{", "}", "}"
+                )
+                .as_str();
+            }
+            "clone" => traits.push("Clone"),
+            "json" => after += expand_json(name, fields, packed).as_str(),
+            _ => {}
+        }
+    }
+    (format!("#[derive({})]\n", traits.join(", ")), after)
+}
@@ -0,0 +1,309 @@
+use crate::{
+    lexer::{inner_state, lex, LexerState, Token, TokenType},
+    parser::{Ast, AstType, Parser},
+    variable::Variables,
+};
+
+#[derive(Clone, Debug)]
+pub struct BasicBlock {
+    pub tokens: Vec<Token>,
+    pub succs: Vec<usize>,
+    pub state: LexerState,
+    // Ends with a return statement
+    pub returns: bool,
+    // Ends with exit() or abort(), the program stops there so it never reaches the exit block
+    pub stops: bool,
+    // Ends with an expression that isn't terminated by a semicolon
+    pub tail: bool,
+    // Raw rust from a `cb` block that can't be looked into
+    pub opaque: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Cfg {
+    pub blocks: Vec<BasicBlock>,
+    pub entry: usize,
+    pub exit: usize,
+}
+
+// Parses the contents of a Curly token into a tree, keeping the positions relative to the file
+fn parse_body(body: &Token) -> Vec<Ast> {
+    match lex(body.value.as_str(), false, inner_state(body)) {
+        Ok(tokens) => Parser::new(tokens, Variables::new()).parse_tree(),
+        // The transpiler reports syntax errors itself
+        Err(_) => vec![],
+    }
+}
+
+// Whether a break in these statements leaves the loop they're the body of
+fn breaks(asts: &[Ast]) -> bool {
+    asts.iter().any(|ast| match ast.ast_type {
+        AstType::For | AstType::While => false,
+        _ => {
+            ast.tokens.first().is_some_and(|token| {
+                token.token_type == TokenType::Keyword && token.value == "break"
+            }) || breaks(&ast.children)
+        }
+    })
+}
+
+impl Cfg {
+    /*Builds the control flow graph of a function body (the Curly token of the declaration)*/
+    pub fn build(body: &Token) -> Cfg {
+        let mut cfg = Cfg {
+            blocks: Vec::new(),
+            entry: 0,
+            exit: 1,
+        };
+        let state = LexerState {
+            line: body.line,
+            column: body.column,
+        };
+        cfg.new_block(state, &[]);
+        cfg.new_block(state, &[]);
+        let ends = cfg.build_seq(&parse_body(body), vec![cfg.entry]);
+        for end in ends {
+            cfg.blocks[end].succs.push(cfg.exit);
+        }
+        cfg
+    }
+
+    fn new_block(&mut self, state: LexerState, preds: &[usize]) -> usize {
+        let id = self.blocks.len();
+        self.blocks.push(BasicBlock {
+            tokens: Vec::new(),
+            succs: Vec::new(),
+            state,
+            returns: false,
+            stops: false,
+            tail: false,
+            opaque: false,
+        });
+        for pred in preds {
+            self.blocks[*pred].succs.push(id);
+        }
+        id
+    }
+
+    /*Adds the statements to the graph and returns the blocks that fall through to whatever comes next*/
+    fn build_seq(&mut self, asts: &[Ast], preds: Vec<usize>) -> Vec<usize> {
+        let mut preds = preds;
+        let mut current: Option<usize> = None;
+        let mut i = 0;
+        while i < asts.len() {
+            let ast = &asts[i];
+            i += 1;
+            if ast.tokens.is_empty() || ast.tokens[0].token_type == TokenType::Comment {
+                continue;
+            }
+            let token = &ast.tokens[0];
+            let state = LexerState {
+                line: token.line,
+                column: token.column,
+            };
+            if ast.ast_type == AstType::If {
+                if let Some(block) = current.take() {
+                    preds = vec![block];
+                }
+                let mut cond = self.new_block(state, &preds);
+                self.blocks[cond]
+                    .tokens
+                    .extend(ast.tokens[..2].iter().cloned());
+                let mut ends = self.build_seq(&ast.children, vec![cond]);
+                let mut has_else = false;
+                while i < asts.len()
+                    && (asts[i].ast_type == AstType::ElseIf || asts[i].ast_type == AstType::Else)
+                {
+                    let branch = &asts[i];
+                    i += 1;
+                    if branch.ast_type == AstType::Else {
+                        ends.extend(self.build_seq(&branch.children, vec![cond]));
+                        has_else = true;
+                        break;
+                    }
+                    let state = LexerState {
+                        line: branch.tokens[0].line,
+                        column: branch.tokens[0].column,
+                    };
+                    cond = self.new_block(state, &[cond]);
+                    self.blocks[cond]
+                        .tokens
+                        .extend(branch.tokens[..2].iter().cloned());
+                    ends.extend(self.build_seq(&branch.children, vec![cond]));
+                }
+                if !has_else {
+                    ends.push(cond);
+                }
+                preds = ends;
+            } else if ast.ast_type == AstType::For || ast.ast_type == AstType::While {
+                // The body loops back to the condition, which is the only way out
+                if let Some(block) = current.take() {
+                    preds = vec![block];
+                }
+                let cond = self.new_block(state, &preds);
+                let header = &ast.tokens[..ast.tokens.len() - 1];
+                self.blocks[cond].tokens.extend(header.iter().cloned());
+                for end in self.build_seq(&ast.children, vec![cond]) {
+                    self.blocks[end].succs.push(cond);
+                }
+                // A while (true) or a for (;;) without a break never gets past its condition
+                let endless = match ast.ast_type {
+                    AstType::While => ast.tokens[1].value.trim() == "true",
+                    _ => ast.tokens[2].value.trim().is_empty(),
+                } && !breaks(&ast.children);
+                preds = if endless { vec![] } else { vec![cond] };
+            } else if ast.tokens.len() == 1 && token.token_type == TokenType::Curly {
+                if let Some(block) = current.take() {
+                    preds = vec![block];
+                }
+                preds = self.build_seq(&ast.children, preds);
+            } else if ast.ast_type == AstType::CodeBlock {
+                if let Some(block) = current.take() {
+                    preds = vec![block];
+                }
+                let block = self.new_block(state, &preds);
+                self.blocks[block].tokens.push(token.clone());
+                self.blocks[block].opaque = true;
+                preds = vec![block];
+            } else {
+                let block = match current {
+                    Some(block) => block,
+                    None => self.new_block(state, &preds),
+                };
+                current = Some(block);
+                self.blocks[block].tokens.extend(ast.tokens.iter().cloned());
+                self.blocks[block].tail = token.token_type != TokenType::Semicolon;
+                let stops = ast.ast_type == AstType::Intrinsic
+                    && (token.value == "exit" || token.value == "abort");
+                if ast.ast_type == AstType::Return || stops {
+                    // Anything after the semicolon is unreachable
+                    let semicolon = asts.get(i).and_then(|next| next.tokens.first());
+                    if semicolon.is_some_and(|next| next.token_type == TokenType::Semicolon) {
+                        i += 1;
+                    }
+                    if stops {
+                        self.blocks[block].stops = true;
+                    } else {
+                        self.blocks[block].returns = true;
+                        self.blocks[block].succs.push(self.exit);
+                    }
+                    self.blocks[block].tail = false;
+                    current = None;
+                    preds = vec![];
+                }
+            }
+        }
+        match current {
+            Some(block) => vec![block],
+            None => preds,
+        }
+    }
+
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut seen = vec![false; self.blocks.len()];
+        let mut stack = vec![self.entry];
+        while let Some(id) = stack.pop() {
+            if seen[id] {
+                continue;
+            }
+            seen[id] = true;
+            stack.extend(self.blocks[id].succs.iter());
+        }
+        seen
+    }
+
+    // The first statement no path from the entry gets to, in source order
+    pub fn unreachable(&self) -> Option<usize> {
+        let reachable = self.reachable();
+        (0..self.blocks.len())
+            .filter(|id| !reachable[*id] && !self.blocks[*id].tokens.is_empty())
+            .min_by_key(|id| (self.blocks[*id].state.line, self.blocks[*id].state.column))
+    }
+
+    /*Returns the reachable blocks that run into the end of the function without producing a value*/
+    pub fn missing_returns(&self) -> Vec<usize> {
+        let reachable = self.reachable();
+        let mut missing = Vec::new();
+        for (id, block) in self.blocks.iter().enumerate() {
+            if reachable[id]
+                && block.succs.contains(&self.exit)
+                && !(block.returns || block.tail || block.opaque)
+            {
+                missing.push(id);
+            }
+        }
+        missing
+    }
+
+    pub fn to_dot(&self, name: &str) -> String {
+        let mut out = format!("digraph \"{}\" {}\n", name, "{");
+        for (id, block) in self.blocks.iter().enumerate() {
+            let label = if id == self.entry {
+                "entry".to_string()
+            } else if id == self.exit {
+                "exit".to_string()
+            } else {
+                let mut label = format!("{}:{}\\n", block.state.line, block.state.column);
+                let values: Vec<String> = block
+                    .tokens
+                    .iter()
+                    .map(|token| token.value.replace('\\', "\\\\").replace('"', "\\\""))
+                    .collect();
+                label += values.join(" ").replace('\n', "\\n").as_str();
+                label
+            };
+            out += format!("  b{} [shape=box, label=\"{}\"];\n", id, label).as_str();
+        }
+        for (id, block) in self.blocks.iter().enumerate() {
+            for succ in &block.succs {
+                out += format!("  b{} -> b{};\n", id, succ).as_str();
+            }
+        }
+        out += "}\n";
+        out
+    }
+}
+
+// Renders the graph of every function declared at the top of a file, Err when it doesn't lex
+pub fn dump_dot(code: &str, path: &str) -> Result<String, String> {
+    let tokens = lex(code, false, LexerState { line: 1, column: 0 }).map_err(|(state, _)| {
+        format!("Invalid syntax at {}:{}:{}", path, state.line, state.column)
+    })?;
+    let mut out = String::new();
+    for ast in Parser::new(tokens, Variables::new()).parse_nodes() {
+        if ast.ast_type == AstType::FunctionDeceleration
+            || ast.ast_type == AstType::VoidFunctionDeceleration
+        {
+            out += Cfg::build(&ast.tokens[3])
+                .to_dot(&ast.tokens[1].value)
+                .as_str();
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Whether a function with this body can run into its end without a value
+    fn misses_return(body: &str) -> bool {
+        let body = Token {
+            token_type: TokenType::Curly,
+            value: body.to_string(),
+            line: 1,
+            column: 0,
+        };
+        !Cfg::build(&body).missing_returns().is_empty()
+    }
+
+    #[test]
+    fn endless_loops() {
+        assert!(!misses_return("while (true) { return 1; }"));
+        assert!(!misses_return("for (;;) { return 1; }"));
+        assert!(misses_return("while (true) { if (a == 1) { break; } }"));
+        assert!(misses_return("while (a < 2) { return 1; }"));
+        // The break belongs to the inner loop
+        assert!(!misses_return("while (true) { while (a < 2) { break; } }"));
+    }
+}
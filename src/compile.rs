@@ -3,6 +3,8 @@ use std::io::{Error, ErrorKind};
 use std::path::Path;
 use std::process::Command;
 
+use crate::manifest::{Manifest, MANIFEST};
+
 pub fn write_to_rust_file(transpiled_code: &str, file_name: &str) -> Result<(), Error> {
     // Determine the file extension based on the provided file_name
     let mut output_file = String::from(file_name);
@@ -24,9 +26,15 @@ pub fn write_to_rust_file(transpiled_code: &str, file_name: &str) -> Result<(),
     Ok(())
 }
 
-pub fn compile_to_executable(output_exe: &str) -> Result<(), Error> {
-    let output = Command::new("rustc")
+pub fn compile_to_executable(
+    toolchain: &Toolchain,
+    output_exe: &str,
+    rustc_args: &[String],
+) -> Result<(), Error> {
+    let output = Command::new(&toolchain.path)
         .arg("main.rs".to_string())
+        .args(rustc_args)
+        .args(&toolchain.flags)
         .arg("-o")
         .arg(output_exe)
         .output()?;
@@ -55,3 +63,103 @@ pub fn compile_to_executable(output_exe: &str) -> Result<(), Error> {
 
     Ok(())
 }
+
+#[derive(Clone, Debug)]
+pub struct Toolchain {
+    // The rustc the generated code is compiled with
+    pub path: String,
+    pub flags: Vec<String>,
+}
+
+impl Toolchain {
+    /*Uses [toolchain.rust] path and flags from wyst.toml, without a path rustc is looked for
+    on the PATH and then in ~/.cargo/bin*/
+    pub fn from_manifest(manifest: &Manifest) -> Result<Toolchain, String> {
+        let flags = manifest
+            .get_list("toolchain.rust", "flags")?
+            .unwrap_or_default();
+        if let Some(path) = manifest.get("toolchain.rust", "path") {
+            if !works(path) {
+                return Err(format!(
+                    "[toolchain.rust] path in {} is {}, which doesn't run as a Rust compiler",
+                    MANIFEST, path
+                ));
+            }
+            return Ok(Toolchain {
+                path: path.to_string(),
+                flags,
+            });
+        }
+        let mut candidates = vec!["rustc".to_string()];
+        if let Some(home) = dirs::home_dir() {
+            candidates.push(format!("{}/.cargo/bin/rustc", home.display()));
+        }
+        match candidates.into_iter().find(|path| works(path)) {
+            Some(path) => Ok(Toolchain { path, flags }),
+            None => Err(format!(
+                "no Rust compiler found; install one with rustup or set [toolchain.rust].path in {}",
+                MANIFEST
+            )),
+        }
+    }
+
+    // The target rustc builds for when it isn't given one
+    pub fn host_triple(&self) -> Option<String> {
+        let output = Command::new(&self.path).arg("-vV").output().ok()?;
+        let info = String::from_utf8_lossy(&output.stdout).to_string();
+        info.lines()
+            .find_map(|line| line.strip_prefix("host: "))
+            .map(|host| host.to_string())
+    }
+}
+
+fn works(path: &str) -> bool {
+    Command::new(path)
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+// The toolchain for a build, the build can't go on without one
+pub fn load_toolchain() -> Toolchain {
+    match Manifest::load().and_then(|manifest| Toolchain::from_manifest(&manifest)) {
+        Ok(toolchain) => toolchain,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/*Runs a prebuild/postbuild command from wyst.toml through the shell,
+it sees the build through WYST_PROFILE, WYST_TARGET, WYST_OUT_DIR and WYST_OUTPUT*/
+pub fn run_hook(
+    name: &str,
+    command: &str,
+    profile: &str,
+    target: &str,
+    output: &str,
+) -> Result<(), Error> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    let out_dir = fs::canonicalize("build")?;
+    let status = shell
+        .arg(command)
+        .env("WYST_PROFILE", profile)
+        .env("WYST_TARGET", target)
+        .env("WYST_OUT_DIR", out_dir)
+        .env("WYST_OUTPUT", output)
+        .status()?;
+    if !status.success() {
+        eprintln!("{} command `{}` failed with {}", name, command, status);
+        return Err(Error::other(format!("{} failed", name)));
+    }
+    Ok(())
+}
@@ -0,0 +1,198 @@
+use clap::{Arg, Command};
+
+pub const SHELLS: [&str; 4] = ["bash", "zsh", "fish", "powershell"];
+
+// The flags of a command, --long or -s when there's no long form
+fn flags(cmd: &Command) -> Vec<(String, &Arg)> {
+    cmd.get_arguments()
+        .filter(|arg| !arg.is_positional())
+        .filter_map(|arg| {
+            let flag = match (arg.get_long(), arg.get_short()) {
+                (Some(long), _) => format!("--{}", long),
+                (None, Some(short)) => format!("-{}", short),
+                (None, None) => return None,
+            };
+            Some((flag, arg))
+        })
+        .collect()
+}
+
+fn help(arg: &Arg) -> String {
+    arg.get_help()
+        .map(|help| help.to_string())
+        .unwrap_or_default()
+}
+
+fn about(cmd: &Command) -> String {
+    cmd.get_about()
+        .map(|about| about.to_string())
+        .unwrap_or_default()
+}
+
+fn values(arg: &Arg) -> Vec<String> {
+    arg.get_possible_values()
+        .iter()
+        .map(|value| value.get_name().to_string())
+        .collect()
+}
+
+// The top level first with the name "", then every subcommand
+fn commands(cmd: &Command) -> Vec<(String, &Command)> {
+    let mut out = vec![(String::new(), cmd)];
+    out.extend(
+        cmd.get_subcommands()
+            .map(|sub| (sub.get_name().to_string(), sub)),
+    );
+    out
+}
+
+fn bash(cmd: &Command) -> String {
+    let name = cmd.get_name();
+    let subs: Vec<&str> = cmd.get_subcommands().map(|sub| sub.get_name()).collect();
+    let mut out = format!(
+        "_{name}() {{\n    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\" prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\" sub=\"\"\n    for word in \"${{COMP_WORDS[@]:1:COMP_CWORD-1}}\"; do\n        case \"$word\" in\n            {}) sub=\"$word\"; break ;;\n        esac\n    done\n    case \"$prev\" in\n",
+        subs.join("|")
+    );
+    for (flag, arg) in commands(cmd).iter().flat_map(|(_, sub)| flags(sub)) {
+        let values = values(arg);
+        if !values.is_empty() && !out.contains(&format!("        {})", flag)) {
+            out += format!(
+                "        {}) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")); return ;;\n",
+                flag,
+                values.join(" ")
+            )
+            .as_str();
+        }
+    }
+    out += "    esac\n    case \"$sub\" in\n";
+    for (sub, command) in commands(cmd) {
+        let mut words: Vec<String> = flags(command).into_iter().map(|(flag, _)| flag).collect();
+        if sub.is_empty() {
+            words.extend(subs.iter().map(|sub| sub.to_string()));
+        }
+        out += format!("        \"{}\") opts=\"{}\" ;;\n", sub, words.join(" ")).as_str();
+    }
+    out += format!(
+        "    esac\n    COMPREPLY=($(compgen -W \"$opts\" -- \"$cur\"))\n}}\ncomplete -o default -F _{name} {name}\n"
+    )
+    .as_str();
+    out
+}
+
+// Brackets and colons end a description in _arguments and _describe
+fn zsh_escape(text: &str) -> String {
+    text.replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+fn zsh(cmd: &Command) -> String {
+    let name = cmd.get_name();
+    let spec = |command: &Command| -> String {
+        flags(command)
+            .iter()
+            .map(|(flag, arg)| {
+                let mut spec = format!("'{}[{}]", flag, zsh_escape(&help(arg)));
+                let values = values(arg);
+                if !values.is_empty() {
+                    spec += format!(":{}:({})", flag.trim_start_matches('-'), values.join(" "))
+                        .as_str();
+                } else if arg.get_action().takes_values() {
+                    spec += format!(":{}:_files", flag.trim_start_matches('-')).as_str();
+                }
+                spec + "'"
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let mut out =
+        format!("#compdef {name}\n\n_{name}() {{\n    local -a subcommands\n    subcommands=(\n");
+    for sub in cmd.get_subcommands() {
+        out += format!("        '{}:{}'\n", sub.get_name(), zsh_escape(&about(sub))).as_str();
+    }
+    out += format!(
+        "    )\n    if (( CURRENT == 2 )); then\n        _describe 'command' subcommands\n        _arguments {}\n        return\n    fi\n    case $words[2] in\n",
+        spec(cmd)
+    )
+    .as_str();
+    for sub in cmd.get_subcommands() {
+        out += format!(
+            "        {}) _arguments {} '*:file:_files' ;;\n",
+            sub.get_name(),
+            spec(sub)
+        )
+        .as_str();
+    }
+    out += format!("    esac\n}}\n\n_{name} \"$@\"\n").as_str();
+    out
+}
+
+fn fish(cmd: &Command) -> String {
+    let name = cmd.get_name();
+    let mut out = String::new();
+    for sub in cmd.get_subcommands() {
+        out += format!(
+            "complete -c {} -n __fish_use_subcommand -f -a {} -d '{}'\n",
+            name,
+            sub.get_name(),
+            about(sub).replace('\'', "\\'")
+        )
+        .as_str();
+    }
+    for (sub, command) in commands(cmd) {
+        let condition = if sub.is_empty() {
+            "__fish_use_subcommand".to_string()
+        } else {
+            format!("'__fish_seen_subcommand_from {}'", sub)
+        };
+        for (flag, arg) in flags(command) {
+            let option = match flag.strip_prefix("--") {
+                Some(long) => format!("-l {}", long),
+                None => format!("-s {}", flag.trim_start_matches('-')),
+            };
+            out += format!("complete -c {} -n {} {}", name, condition, option).as_str();
+            let values = values(arg);
+            if !values.is_empty() {
+                out += format!(" -x -a '{}'", values.join(" ")).as_str();
+            } else if arg.get_action().takes_values() {
+                out += " -r";
+            }
+            out += format!(" -d '{}'\n", help(arg).replace('\'', "\\'")).as_str();
+        }
+    }
+    out
+}
+
+fn powershell(cmd: &Command) -> String {
+    let name = cmd.get_name();
+    let mut out = format!(
+        "Register-ArgumentCompleter -Native -CommandName '{name}' -ScriptBlock {{\n    param($wordToComplete, $commandAst, $cursorPosition)\n    $words = @($commandAst.CommandElements | Select-Object -Skip 1 | ForEach-Object {{ $_.ToString() }})\n    $sub = $words | Where-Object {{ $_ -notlike '-*' }} | Select-Object -First 1\n    if ($sub -eq $wordToComplete) {{ $sub = $null }}\n    $options = switch ($sub) {{\n"
+    );
+    for (sub, command) in commands(cmd) {
+        let mut words: Vec<String> = flags(command).into_iter().map(|(flag, _)| flag).collect();
+        let label = if sub.is_empty() {
+            words.extend(cmd.get_subcommands().map(|sub| sub.get_name().to_string()));
+            "$null".to_string()
+        } else {
+            format!("'{}'", sub)
+        };
+        let words: Vec<String> = words.iter().map(|word| format!("'{}'", word)).collect();
+        out += format!("        {} {{ @({}) }}\n", label, words.join(", ")).as_str();
+    }
+    out += "    }\n    $options | Where-Object { $_ -like \"$wordToComplete*\" } | ForEach-Object {\n        [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)\n    }\n}\n";
+    out
+}
+
+/*The completion script for a shell, built from the argument definitions so a new subcommand or
+flag shows up in it without touching this file*/
+pub fn script(shell: &str, mut cmd: Command) -> String {
+    // Building adds the global flags to every subcommand, and --help
+    cmd.build();
+    match shell {
+        "bash" => bash(&cmd),
+        "zsh" => zsh(&cmd),
+        "fish" => fish(&cmd),
+        _ => powershell(&cmd),
+    }
+}
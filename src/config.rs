@@ -0,0 +1,120 @@
+use crate::manifest::{Manifest, MANIFEST};
+use std::{fmt, fs, path::PathBuf};
+
+// The settings that can be given in a config file, [settings] key, with their defaults
+pub const SETTINGS: [(&str, Option<&str>); 3] = [
+    ("locale", Some("en")),
+    ("error-limit", None),
+    ("runtime-dir", None),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Origin {
+    Default,
+    User(PathBuf),
+    Project,
+    Flag,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::User(path) => write!(f, "{}", path.display()),
+            Origin::Project => write!(f, "{}", MANIFEST),
+            Origin::Flag => write!(f, "command line"),
+        }
+    }
+}
+
+// Every setting with its value and the layer that set it, in the order of SETTINGS
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub settings: Vec<(&'static str, Option<String>, Origin)>,
+}
+
+// ~/.config/wyst/config.toml, or under $XDG_CONFIG_HOME when it's set
+pub fn user_config() -> Option<PathBuf> {
+    let dir = match std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => dirs::home_dir()?.join(".config"),
+    };
+    Some(dir.join("wyst").join("config.toml"))
+}
+
+impl Config {
+    /*Layers the defaults, the user config, the [settings] of wyst.toml and the flags, each one
+    overriding the ones before it. flags has the value given for each setting, if any*/
+    pub fn load(flags: &[(&str, Option<String>)]) -> Result<Config, String> {
+        let mut settings: Vec<(&'static str, Option<String>, Origin)> = SETTINGS
+            .iter()
+            .map(|(name, default)| (*name, default.map(str::to_string), Origin::Default))
+            .collect();
+        let mut layers: Vec<(Manifest, Origin)> = Vec::new();
+        if let Some(path) = user_config().filter(|path| path.is_file()) {
+            let text =
+                fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+            let manifest = Manifest::parse(&text)
+                .map_err(|err| err.replacen(MANIFEST, &path.display().to_string(), 1))?;
+            layers.push((manifest, Origin::User(path)));
+        }
+        layers.push((Manifest::load()?, Origin::Project));
+        for (manifest, origin) in layers {
+            for (name, value, from) in settings.iter_mut() {
+                if let Some(set) = manifest.get("settings", name) {
+                    *value = Some(set.to_string());
+                    *from = origin.clone();
+                }
+            }
+        }
+        for (name, flag) in flags {
+            if let Some(flag) = flag {
+                if let Some(setting) = settings.iter_mut().find(|setting| setting.0 == *name) {
+                    setting.1 = Some(flag.clone());
+                    setting.2 = Origin::Flag;
+                }
+            }
+        }
+        let config = Config { settings };
+        config.error_limit()?;
+        Ok(config)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.settings
+            .iter()
+            .find(|setting| setting.0 == name)
+            .and_then(|setting| setting.1.as_deref())
+    }
+
+    pub fn error_limit(&self) -> Result<Option<usize>, String> {
+        match self.get("error-limit") {
+            Some(limit) => match limit.parse::<usize>() {
+                Ok(limit) => Ok(Some(limit)),
+                Err(_) => Err(format!(
+                    "error-limit must be a whole number, found '{}'",
+                    limit
+                )),
+            },
+            None => Ok(None),
+        }
+    }
+
+    // `wyst config show`, one `key = value` line per setting
+    pub fn show(&self, origin: bool) -> String {
+        let mut out = String::new();
+        for (name, value, from) in &self.settings {
+            let line = match value {
+                Some(value) if value.parse::<i64>().is_ok() => format!("{} = {}", name, value),
+                Some(value) => format!("{} = {:?}", name, value),
+                None => format!("# {} is not set", name),
+            };
+            if origin {
+                out += format!("{:<28} # {}\n", line, from).as_str();
+            } else {
+                out += format!("{}\n", line).as_str();
+            }
+        }
+        out
+    }
+}
@@ -0,0 +1,131 @@
+use crate::lexer::{lex, LexerState, Token, TokenType};
+
+/*The tokens that carry meaning, brackets are opened up into their own tokens so a change deep
+inside a body is reported on its own instead of as a change to the whole body*/
+fn flatten(code: &str, state: LexerState, out: &mut Vec<Token>) -> Result<(), LexerState> {
+    let tokens = lex(code, false, state).map_err(|(state, _)| state)?;
+    for token in tokens {
+        let (open, close) = match token.token_type {
+            TokenType::Whitespace | TokenType::Newline | TokenType::Comment => continue,
+            TokenType::Round => ("(", ")"),
+            TokenType::Curly => ("{", "}"),
+            TokenType::Square => ("[", "]"),
+            _ => {
+                out.push(token);
+                continue;
+            }
+        };
+        out.push(Token {
+            value: open.to_string(),
+            ..token.clone()
+        });
+        let inner = LexerState {
+            line: token.line,
+            column: token.column + 1,
+        };
+        flatten(&token.value, inner, out)?;
+        out.push(Token {
+            value: close.to_string(),
+            ..token
+        });
+    }
+    Ok(())
+}
+
+pub fn tokens(code: &str) -> Result<Vec<Token>, LexerState> {
+    let mut out = Vec::new();
+    flatten(code, LexerState { line: 1, column: 0 }, &mut out)?;
+    Ok(out)
+}
+
+fn same(a: &Token, b: &Token) -> bool {
+    a.token_type == b.token_type && a.value == b.value
+}
+
+#[derive(Debug, PartialEq)]
+enum Edit {
+    Keep,
+    Remove,
+    Add,
+}
+
+// The shortest edit script from the longest common subsequence
+fn edits(old: &[Token], new: &[Token]) -> Vec<Edit> {
+    let mut lcs = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if same(&old[i], &new[j]) {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut out = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && same(&old[i], &new[j]) {
+            out.push(Edit::Keep);
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out.push(Edit::Remove);
+            i += 1;
+        } else {
+            out.push(Edit::Add);
+            j += 1;
+        }
+    }
+    out
+}
+
+/*Diffs two programs, ignoring whitespace and comments. Every changed run of tokens is printed
+as a hunk with the positions it starts at in both files:
+
+    @@ old.wt:3:9 new.wt:3:9 @@
+    - 1
+    + 2
+
+An empty result means the change only touched the layout*/
+pub fn diff(old_path: &str, old: &[Token], new_path: &str, new: &[Token]) -> String {
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    let script = edits(old, new);
+    let mut k = 0;
+    while k < script.len() {
+        if script[k] == Edit::Keep {
+            i += 1;
+            j += 1;
+            k += 1;
+            continue;
+        }
+        // Hunks point at the first changed token, or where it would go
+        let at = |tokens: &[Token], index: usize| match tokens.get(index).or(tokens.last()) {
+            Some(token) => format!("{}:{}", token.line, token.column),
+            None => "1:0".to_string(),
+        };
+        out += format!(
+            "@@ {}:{} {}:{} @@\n",
+            old_path,
+            at(old, i),
+            new_path,
+            at(new, j)
+        )
+        .as_str();
+        let mut removed = String::new();
+        let mut added = String::new();
+        while k < script.len() && script[k] != Edit::Keep {
+            if script[k] == Edit::Remove {
+                removed += format!("- {}\n", old[i].value).as_str();
+                i += 1;
+            } else {
+                added += format!("+ {}\n", new[j].value).as_str();
+                j += 1;
+            }
+            k += 1;
+        }
+        out += removed.as_str();
+        out += added.as_str();
+    }
+    out
+}
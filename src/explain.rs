@@ -0,0 +1,291 @@
+use crate::lspcom::ProblemType;
+
+pub const PROBLEM_TYPES: [ProblemType; 20] = [
+    ProblemType::VariableNotFound,
+    ProblemType::FileNotFound,
+    ProblemType::HeaderSyntaxError,
+    ProblemType::MissingReturn,
+    ProblemType::IndexOutOfBounds,
+    ProblemType::UnknownAttribute,
+    ProblemType::MisplacedAttribute,
+    ProblemType::IntrinsicArguments,
+    ProblemType::UnknownMethod,
+    ProblemType::SpawnTarget,
+    ProblemType::LiteralSuffix,
+    ProblemType::Deprecated,
+    ProblemType::BufferLength,
+    ProblemType::FeatureGate,
+    ProblemType::UnusedSuppression,
+    ProblemType::StructLayout,
+    ProblemType::SyntaxError,
+    ProblemType::Unreachable,
+    ProblemType::ImplTarget,
+    ProblemType::Sandboxed,
+];
+
+impl ProblemType {
+    // Codes are never reused, a removed problem keeps its number
+    pub fn code(&self) -> &'static str {
+        match self {
+            ProblemType::VariableNotFound => "E0001",
+            ProblemType::FileNotFound => "E0002",
+            ProblemType::HeaderSyntaxError => "E0003",
+            ProblemType::MissingReturn => "E0004",
+            ProblemType::IndexOutOfBounds => "E0005",
+            ProblemType::UnknownAttribute => "E0006",
+            ProblemType::MisplacedAttribute => "E0007",
+            ProblemType::IntrinsicArguments => "E0008",
+            ProblemType::UnknownMethod => "E0009",
+            ProblemType::SpawnTarget => "E0010",
+            ProblemType::LiteralSuffix => "E0011",
+            ProblemType::Deprecated => "E0012",
+            ProblemType::BufferLength => "E0013",
+            ProblemType::FeatureGate => "E0014",
+            ProblemType::UnusedSuppression => "E0015",
+            ProblemType::StructLayout => "E0016",
+            ProblemType::SyntaxError => "E0017",
+            ProblemType::Unreachable => "E0018",
+            ProblemType::ImplTarget => "E0019",
+            ProblemType::Sandboxed => "E0020",
+        }
+    }
+
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            ProblemType::VariableNotFound => {
+                r#"A name was used that isn't declared in this scope or any scope around it.
+
+    int main() {
+      int total = 1;
+      return totl; // E0001, 'totl' was never declared
+    }
+
+Check the spelling, or declare the variable before it's used. Variables declared
+inside a block, like the body of an if, can't be used after it."#
+            }
+            ProblemType::FileNotFound => {
+                r#"An #include names a file that couldn't be found.
+
+    #include <missing.wt> // E0002
+
+<file> is looked up in lib/, then in the dependencies from wyst.toml and then in
+the standard library. "file" is relative to the project. Run `wyst fetch` if the
+file comes from a dependency that hasn't been downloaded."#
+            }
+            ProblemType::HeaderSyntaxError => {
+                r#"An included .h file isn't a valid wyst header.
+
+A header is a json object naming the compiled library and its symbol map:
+
+    {"file_path": "lib.rs", "map_path": "lib.map"}
+
+Generate headers with `wyst --dll` instead of writing them by hand."#
+            }
+            ProblemType::MissingReturn => {
+                r#"A function that returns a value can reach its end without a return.
+
+    int check(int x) {
+      if (x == 0) {
+        return 1;
+      }
+    } // E0004, nothing is returned when x isn't 0
+
+Add a return at the end, or in an else branch. Functions that return nothing
+are declared void."#
+            }
+            ProblemType::IndexOutOfBounds => {
+                r#"A constant index is outside of an array whose size is known.
+
+    int xs[3];
+    xs[3] = 1; // E0005, the valid indexes are 0, 1 and 2
+
+Arrays start at 0, so the last element of an array of length n is at n - 1."#
+            }
+            ProblemType::UnknownAttribute => {
+                r#"An attribute doesn't exist, or @derive was asked for something it can't generate.
+
+    @tset // E0006
+    void check() {}
+
+The attributes are @test, @deprecated(...), @derive(...), @packed and @align(n).
+@derive accepts to_string, equals, clone and json."#
+            }
+            ProblemType::MisplacedAttribute => {
+                r#"An attribute was put on a declaration it doesn't apply to.
+
+    @test // E0007, @test goes on functions
+    struct Point { int x; }
+
+@derive, @packed and @align go on structs, @test goes on functions and @deprecated
+goes on either."#
+            }
+            ProblemType::IntrinsicArguments => {
+                r#"A builtin was called with the wrong number or kind of arguments.
+
+    assert_eq(1); // E0008, assert_eq compares two values
+    string s = "hi";
+    s.substr(1);  // E0008, substr takes a start and a length
+
+embed! and env! also need string literals, since they are resolved while
+compiling, and so does the format trace! takes first. count_ones, leading_zeros,
+trailing_zeros, rotate_left, rotate_right, byte_swap and the checked_, wrapping_
+and saturating_ arithmetic only take integers."#
+            }
+            ProblemType::UnknownMethod => {
+                r#"A method was called on a builtin type that doesn't have it.
+
+    vec<int> xs = [1, 2];
+    xs.pop(); // E0009
+
+string has len, substr, find, split and to_int. vec has len, push and contains,
+and map has len, insert, has and remove."#
+            }
+            ProblemType::SpawnTarget => {
+                r#"spawn was given something other than the name of a function that takes no
+arguments and returns int.
+
+    void work() {}
+    thread t = spawn(work); // E0010, work returns nothing
+
+Threads hand their result back through join, so the function has to return an
+int. Return 0 if there is nothing to report. The thread calls the function
+without arguments, so anything it needs has to come from a global."#
+            }
+            ProblemType::LiteralSuffix => {
+                r#"A numeric literal has a suffix that isn't in the suffix table, or the
+expanded value doesn't fit in an int.
+
+    int size = 4kib; // E0011, the suffix is kb
+    int big = 3gb;   // E0011, more than 2147483647
+
+The built-in suffixes are kb, mb, gb, ms, s and min. More can be added in
+wyst.toml:
+
+    [suffixes]
+    fps = 60"#
+            }
+            ProblemType::Deprecated => {
+                r#"A function or struct marked @deprecated is used. This is a warning, the
+program still builds.
+
+    @deprecated("use area", since="1.2")
+    int size(int w, int h) { return w * h; }
+
+    int main() {
+      return size(2, 3); // E0012, 'size' is deprecated since 1.2: use area
+    }
+
+Move to the replacement named in the message. `wyst check --deny deprecated`
+turns these warnings into errors, to keep new uses out."#
+            }
+            ProblemType::BufferLength => {
+                r#"A buffer's length isn't a constant, or the buffer doesn't have exactly a type
+and a length.
+
+    int n = 4;
+    buffer<int, n> a;      // E0013, n is a variable
+    buffer<int, 2 * 8> b;  // fine, 16 ints
+
+A buffer is an array on the stack, so its length has to be known while
+compiling. It can be a number or arithmetic on numbers."#
+            }
+            ProblemType::FeatureGate => {
+                r#"Experimental syntax is used without turning its feature on, or a pragma
+names a feature that doesn't exist.
+
+    int work() { return 1; }
+
+    int main() {
+      return join(spawn(work)); // E0014, spawn needs the threads feature
+    }
+
+Put `#pragma feature(threads)` at the top of the file. Experimental features
+can still change between releases, the pragma keeps that choice per file."#
+            }
+            ProblemType::UnusedSuppression => {
+                r#"A `// wyst-ignore:` comment names a warning that isn't reported on the line it
+covers. This is a warning.
+
+    // wyst-ignore: deprecated
+    return area(2, 3); // E0015, area isn't deprecated
+
+The comment covers its own line, or the next one when it's alone on its line.
+Remove it once the warning it was for is fixed, so it can't hide a new one."#
+            }
+            ProblemType::StructLayout => {
+                r#"The layout attributes of a struct don't fit together.
+
+    @align(12)    // E0016, 12 isn't a power of two
+    struct Block { int a; }
+
+    @packed
+    @align(16)    // E0016, a struct is either packed or aligned
+    struct Header { int a; }
+
+@packed leaves out the padding between fields, @align(n) starts the struct on
+a multiple of n bytes. Either one keeps the fields in the order they're written."#
+            }
+            ProblemType::SyntaxError => {
+                r#"Something that has to be followed by a bracket isn't.
+
+    int main() {
+      cb        // E0017, cb takes a block of rust code
+      return 0;
+    }
+
+cb is followed by {...} and # by [...]. This is usually a file that was cut
+short or a bracket that went missing while editing. An else or else if that
+doesn't come right after an if or else if is reported the same way, and so is a
+for loop header that isn't (init; condition; step) or (name in values), and a
+generic type that's cut short or has the wrong number of arguments, like
+map<string>."#
+            }
+            ProblemType::Unreachable => {
+                r#"A statement comes after something that always leaves, so it never runs.
+This is a warning, the program still builds.
+
+    int main() {
+      exit(2);
+      return 0; // E0018, exit(2) has already ended the program
+    }
+
+exit(code) ends the program like returning code from main: the coverage report
+is written and buffered output is flushed. abort() stops it on the spot, with
+neither. A return is checked the same way. Remove the dead code, or move it
+before the statement that ends the path."#
+            }
+            ProblemType::ImplTarget => {
+                r#"An impl block names something that isn't a struct declared in the program.
+
+    struct Point { int x; int y; }
+
+    impl Pont {   // E0019, there's no struct called Pont
+      int zero() { return 0; }
+    }
+
+The functions in an impl become methods of the struct, called as value.name().
+Check the spelling, or declare the struct. It can come before or after the impl."#
+            }
+            ProblemType::Sandboxed => {
+                r#"The playground was asked to read something from the machine it runs on.
+
+    #include <math>            // E0020
+    string home = env!("HOME"); // E0020
+
+`wyst serve --playground` compiles code sent by anyone who can reach it, so
+#include, embed! and env! are refused there and wyst.toml isn't read. Paste
+what the file would have brought in into the program, or build it locally."#
+            }
+        }
+    }
+}
+
+/*The extended explanation for `wyst explain E0001`, the code is case insensitive and the
+leading zeros can be left out*/
+pub fn explain(code: &str) -> Option<&'static str> {
+    let number = code.trim_start_matches(['E', 'e']).parse::<u32>().ok()?;
+    PROBLEM_TYPES
+        .iter()
+        .find(|problem_type| problem_type.code()[1..].parse::<u32>() == Ok(number))
+        .map(|problem_type| problem_type.explanation())
+}
@@ -4,7 +4,12 @@ use dirs::home_dir;
 
 use crate::{
     dllmgr::{read_dll, HeaderConfig},
+    lexer::LexerState,
+    lint,
     lspcom::{Problem, ProblemType},
+    manifest,
+    messages::message,
+    package::{self, Features},
     transpiler::Transpiler,
     variable::{Variable, Variables},
 };
@@ -30,6 +35,16 @@ fn join_directories(dir1: &str, dir2: &str) -> String {
     result
 }
 
+// The standard library, a vendored copy in the project is used over the installed one
+pub fn stdlib_dir() -> String {
+    if Path::new(package::VENDOR_STD).is_dir() {
+        return package::VENDOR_STD.to_string();
+    }
+    let homedir_ = home_dir().expect("Err_HOMEDIR_NOTFOUND");
+    let homedir = homedir_.to_str().expect("ERR_HOMEDIR_STR");
+    join_directories(homedir, &join_directories("wyst", "lib"))
+}
+
 #[derive(Clone, Debug)]
 pub struct Module {
     pub file_ws: String,
@@ -39,11 +54,108 @@ pub struct Module {
     pub code_rs: String,
 }
 
+pub const RUNTIMES: [&str; 10] = [
+    "wyst_json",
+    "wyst_coverage",
+    "wyst_string",
+    "wyst_collections",
+    "wyst_time",
+    "wyst_random",
+    "wyst_fs",
+    "wyst_env",
+    "wyst_process",
+    "wyst_panic",
+];
+
+// Support code that generated programs may need, only written to build/ when used
+pub fn runtime_source(name: &str) -> &'static str {
+    match name {
+        "wyst_json" => include_str!("../runtime/wyst_json.rs"),
+        "wyst_coverage" => include_str!("../runtime/wyst_coverage.rs"),
+        "wyst_string" => include_str!("../runtime/wyst_string.rs"),
+        "wyst_collections" => include_str!("../runtime/wyst_collections.rs"),
+        "wyst_time" => include_str!("../runtime/wyst_time.rs"),
+        "wyst_random" => include_str!("../runtime/wyst_random.rs"),
+        "wyst_fs" => include_str!("../runtime/wyst_fs.rs"),
+        "wyst_env" => include_str!("../runtime/wyst_env.rs"),
+        "wyst_process" => include_str!("../runtime/wyst_process.rs"),
+        "wyst_panic" => include_str!("../runtime/wyst_panic.rs"),
+        _ => "",
+    }
+}
+
+/*Writes every bundled runtime module to dir, so builds can share it through --runtime-dir*/
+pub fn install_runtime(dir: &str) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir, err))?;
+    for name in RUNTIMES {
+        let path = Path::new(dir).join(format!("{}.rs", name));
+        fs::write(&path, runtime_source(name))
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+    }
+    Ok(())
+}
+
+// Checks a --runtime-dir before a build and makes it absolute, rustc runs from inside build/
+pub fn load_runtime_dir(dir: &str) -> String {
+    let missing: Vec<&str> = RUNTIMES
+        .iter()
+        .filter(|name| !Path::new(dir).join(format!("{}.rs", name)).is_file())
+        .copied()
+        .collect();
+    if !missing.is_empty() {
+        eprintln!(
+            "{}: missing runtime {}, install it with `wyst runtime {}`",
+            dir,
+            missing.join(", "),
+            dir
+        );
+        std::process::exit(1);
+    }
+    match fs::canonicalize(dir) {
+        Ok(path) => path.to_string_lossy().to_string(),
+        Err(err) => {
+            eprintln!("{}: {}", dir, err);
+            std::process::exit(1);
+        }
+    }
+}
+
+// A problem with an include itself, reported at the include in the file that has it
+fn include_problem(
+    problem_type: ProblemType,
+    problem_msg: String,
+    at: LexerState,
+    includer: &str,
+) -> Problem {
+    Problem {
+        problem_type,
+        problem_msg,
+        state: at,
+        file: includer.to_string(),
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct FileWriter {
     pub files: Vec<Module>,
     pub mod_num: u32,
     pub _projpath: String,
+    pub runtime: Vec<String>,
+    // Dependency directories from wyst.toml, searched for <includes> after lib/
+    pub roots: Vec<String>,
+    // Files from embed!, named by the hash of their contents so each one is written once
+    pub assets: Vec<(String, Vec<u8>)>,
+    // An absolute directory with a preinstalled runtime, used in place of the bundled one
+    pub runtime_dir: Option<String>,
+    // Whether trace! calls are kept, here so included files get it too
+    pub trace: bool,
+    // time:: and random:: give the same values on every run
+    pub deterministic: bool,
+    // What each numeric literal suffix multiplies by, `4kb` is 4 * suffixes["kb"]
+    pub suffixes: HashMap<String, i64>,
+    // Problems and warnings found in an included file, the transpiler that included it takes them
+    pub problems: Vec<Problem>,
+    pub warnings: Vec<Problem>,
 }
 
 impl FileWriter {
@@ -52,12 +164,69 @@ impl FileWriter {
             files: Vec::new(),
             mod_num: 0,
             _projpath,
+            runtime: Vec::new(),
+            roots: Vec::new(),
+            assets: Vec::new(),
+            runtime_dir: None,
+            trace: true,
+            deterministic: false,
+            suffixes: manifest::default_suffixes(),
+            problems: Vec::new(),
+            warnings: Vec::new(),
         }
     }
+    /*Takes what the project's wyst.toml says about includes and literals, main reads it once and
+    hands it to every file through the writer*/
+    pub fn configure(&mut self, features: &Features) {
+        self.roots = features.roots.clone();
+        self.suffixes = features.suffixes.clone();
+    }
     pub fn write(&mut self) {
         for module in &self.files {
             fs::write(&module.file_rs, &module.code_rs).expect("WriteErr: can't write to file");
         }
+        if self.runtime_dir.is_none() {
+            for name in &self.runtime {
+                fs::write(format!("build/{}.rs", name), runtime_source(name))
+                    .expect("WriteErr: can't write runtime");
+            }
+        }
+        for (name, bytes) in &self.assets {
+            fs::write(format!("build/{}", name), bytes).expect("WriteErr: can't write asset");
+        }
+    }
+    /*Adds an embedded file to the build and returns its file name there*/
+    pub fn embed(&mut self, bytes: Vec<u8>) -> String {
+        let name = format!("embed_{}.bin", package::hash(&bytes));
+        if !self.assets.iter().any(|(asset, _)| *asset == name) {
+            self.assets.push((name.clone(), bytes));
+        }
+        name
+    }
+    pub fn use_runtime(&mut self, name: &str) {
+        if !self.runtime.contains(&name.to_string()) {
+            self.runtime.push(name.to_string());
+        }
+    }
+    /*The mod declarations the crate root needs for the runtime in use*/
+    pub fn runtime_mods(&self) -> String {
+        let mut mods = String::new();
+        for name in &self.runtime {
+            if let Some(dir) = &self.runtime_dir {
+                let path = Path::new(dir).join(format!("{}.rs", name));
+                mods += format!("#[path = {:?}]\n", path.to_string_lossy()).as_str();
+            }
+            mods += format!("mod {};\n", name).as_str();
+        }
+        mods
+    }
+    /*The source of a runtime module, read from the runtime directory when there is one*/
+    fn runtime_code(&self, name: &str) -> String {
+        match &self.runtime_dir {
+            Some(dir) => fs::read_to_string(Path::new(dir).join(format!("{}.rs", name)))
+                .expect("ReadErr: can't read runtime"),
+            None => runtime_source(name).to_string(),
+        }
     }
     /*Checks if a path has already been imported*/
     pub fn check(&mut self, filepath: String) -> Option<&Module> {
@@ -68,41 +237,135 @@ impl FileWriter {
         }
         return None;
     }
+    fn visit(&self, module: &Module, order: &mut Vec<String>) {
+        if order.contains(&module.mod_rs) {
+            return;
+        }
+        order.push(module.mod_rs.clone());
+        let at = order.len() - 1;
+        for dep in &self.files {
+            if module.code_rs.contains(&format!("mod {};\n", dep.mod_rs)) {
+                self.visit(dep, order);
+            }
+        }
+        // Dependencies go in front of the module that includes them
+        let name = order.remove(at);
+        order.push(name);
+    }
+    /*Puts the runtime, every module and the root code into one file. Modules become inline mods
+    at the crate root in dependency order, each one once*/
+    pub fn amalgamate(&self, root: &str) -> String {
+        let mut order: Vec<String> = Vec::new();
+        for module in &self.files {
+            self.visit(module, &mut order);
+        }
+        let mut names = self.runtime.clone();
+        names.extend(order.iter().cloned());
+        let strip = |code: &str| {
+            let mut code = code.replace(&self.runtime_mods(), "");
+            for name in &names {
+                code = code.replace(&format!("mod {};\n", name), "");
+            }
+            for (name, bytes) in &self.assets {
+                code = code.replace(
+                    &format!("include_bytes!({:?})", name),
+                    &format!("(&{:?})", bytes),
+                );
+            }
+            code
+        };
+        let mut out = String::new();
+        for name in &self.runtime {
+            out += format!(
+                "mod {} {}\n{}\n{}\n",
+                name,
+                "{",
+                self.runtime_code(name),
+                "}"
+            )
+            .as_str();
+        }
+        for name in &order {
+            if let Some(module) = self.files.iter().find(|module| module.mod_rs == *name) {
+                out += format!(
+                    "mod {} {}\n{}\n{}\n",
+                    name,
+                    "{",
+                    strip(&module.code_rs),
+                    "}"
+                )
+                .as_str();
+            }
+        }
+        out += strip(root).as_str();
+        out
+    }
     pub fn expand(&mut self, writer: FileWriter) {
         for module in writer.files {
             self.files.push(module);
         }
+        for name in writer.runtime {
+            self.use_runtime(&name);
+        }
+        for (_, bytes) in writer.assets {
+            self.embed(bytes);
+        }
     }
-    pub fn add(
-        &mut self,
+    /*The file an include points at, <library> paths are looked up in lib/, the package roots
+    and the standard library in that order. A file that isn't found is reported at the include,
+    `at` in the file `includer`*/
+    pub fn resolve(
+        &self,
         filepath: String,
-        variables: &mut Variables,
         global: bool,
+        at: LexerState,
+        includer: &str,
     ) -> Result<String, Problem> {
         let mut filepath = filepath;
         if global {
-            let homedir_ = home_dir().expect("Err_HOMEDIR_NOTFOUND");
-            let homedir = homedir_.to_str().expect("ERR_HOMEDIR_STR");
-            let gdir = join_directories(&homedir, &join_directories("wyst", "lib"));
+            let gdir = stdlib_dir();
             let lpath = join_directories("lib", &filepath);
             let gpath = join_directories(&gdir, &filepath);
+            let dpath = self
+                .roots
+                .iter()
+                .map(|root| join_directories(root, &filepath))
+                .find(|path| Path::new(path).exists());
             if Path::new(&lpath).exists() {
                 filepath = lpath;
+            } else if let Some(dpath) = dpath {
+                filepath = dpath;
             } else if Path::new(&gpath).exists() {
                 filepath = gpath;
             } else {
-                return Err(Problem {
-                    problem_type: ProblemType::FileNotFound,
-                    problem_msg: format!("failed to import {}: Not found", filepath),
-                });
+                return Err(include_problem(
+                    ProblemType::FileNotFound,
+                    message("import_not_found", &[&filepath]),
+                    at,
+                    includer,
+                ));
             }
         }
         if !Path::new(&filepath).exists() {
-            return Err(Problem {
-                problem_type: ProblemType::FileNotFound,
-                problem_msg: format!("failed to import {}: Not found", filepath),
-            });
+            return Err(include_problem(
+                ProblemType::FileNotFound,
+                message("import_not_found", &[&filepath]),
+                at,
+                includer,
+            ));
         }
+        Ok(filepath)
+    }
+
+    pub fn add(
+        &mut self,
+        filepath: String,
+        variables: &mut Variables,
+        global: bool,
+        at: LexerState,
+        includer: &str,
+    ) -> Result<String, Problem> {
+        let filepath = self.resolve(filepath, global, at, includer)?;
         if let Some(module) = self.check(filepath.clone()) {
             return Ok(module.mod_rs.clone());
         }
@@ -110,11 +373,17 @@ impl FileWriter {
             "wt" => {
                 let mut trsp = Transpiler {
                     writer: self.clone(),
+                    file: filepath.clone(),
+                    // Everything in an included file is visible to the includer
+                    auto_pub: true,
                     ..Default::default()
                 };
                 let mut vars = Variables::new();
                 let code = fs::read_to_string(filepath.clone()).expect("failed to read");
                 let code_rs = trsp.transpile(code.clone(), 0, &mut vars);
+                lint::suppress(&code, &filepath, &mut trsp.warnings);
+                // Files the module included itself took the numbers before it
+                self.mod_num = self.mod_num.max(trsp.writer.mod_num);
                 let module = Module {
                     file_ws: filepath,
                     mod_rs: format!("mod_{}", self.mod_num),
@@ -124,6 +393,8 @@ impl FileWriter {
                 };
                 self.files.push(module.clone());
                 self.expand(trsp.writer);
+                self.problems.extend(trsp.problems);
+                self.warnings.extend(trsp.warnings);
                 variables.expand(vars);
                 self.mod_num += 1;
                 Ok(module.mod_rs)
@@ -157,41 +428,45 @@ impl FileWriter {
                                     variables.expand(vars);
                                     Ok(module.mod_rs)
                                 }
-                                Err(_) => Err(Problem {
-                                    problem_type: ProblemType::FileNotFound,
-                                    problem_msg: format!(
-                                        "failed to import {}: Not found",
-                                        hdrcnf.map_path
-                                    ),
-                                }),
+                                Err(_) => Err(include_problem(
+                                    ProblemType::FileNotFound,
+                                    message("import_not_found", &[&hdrcnf.map_path]),
+                                    at,
+                                    includer,
+                                )),
                             },
-                            Err(_) => Err(Problem {
-                                problem_type: ProblemType::FileNotFound,
-                                problem_msg: format!(
-                                    "failed to import {}: Not found",
-                                    hdrcnf.file_path
-                                ),
-                            }),
+                            Err(_) => Err(include_problem(
+                                ProblemType::FileNotFound,
+                                message("import_not_found", &[&hdrcnf.file_path]),
+                                at,
+                                includer,
+                            )),
                         }
                     }
-                    None => Err(Problem {
-                        problem_type: ProblemType::HeaderSyntaxError,
-                        problem_msg: format!("failed to deserialize"),
-                    }),
+                    None => Err(include_problem(
+                        ProblemType::HeaderSyntaxError,
+                        message("header_syntax", &[]),
+                        at,
+                        includer,
+                    )),
                 }
             }
             "dll" => {
                 read_dll(filepath.clone());
                 return Ok("xyz".to_string());
             }
-            "rs" => Err(Problem {
-                problem_type: ProblemType::FileNotFound,
-                problem_msg: format!("failed to import {}: Not found", filepath),
-            }),
-            _ => Err(Problem {
-                problem_type: ProblemType::FileNotFound,
-                problem_msg: format!("failed to import {}: Not found", filepath),
-            }),
+            "rs" => Err(include_problem(
+                ProblemType::FileNotFound,
+                message("import_not_found", &[&filepath]),
+                at,
+                includer,
+            )),
+            _ => Err(include_problem(
+                ProblemType::FileNotFound,
+                message("import_not_found", &[&filepath]),
+                at,
+                includer,
+            )),
         }
     }
 }
@@ -0,0 +1,177 @@
+use crate::{diff, file_writer::FileWriter, lexer::LexerState, variable::Variables};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::HashSet;
+
+// A line with nothing but an include on it, lines with more code after the include are left alone
+static INCLUDE_LINE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"^#include *(<(.*?)>|"(.*?)")\s*$"#).unwrap());
+
+// <library> includes sort before "local" ones
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Include {
+    local: bool,
+    path: String,
+}
+
+impl Include {
+    fn line(&self) -> String {
+        if self.local {
+            format!("#include \"{}\"", self.path)
+        } else {
+            format!("#include <{}>", self.path)
+        }
+    }
+}
+
+// The path of the include on a line, and whether it's a <library> one
+pub fn include_on(line: &str) -> Option<(String, bool)> {
+    let caps = INCLUDE_LINE.captures(line)?;
+    match caps.get(2) {
+        Some(path) => Some((path.as_str().to_string(), true)),
+        None => Some((caps[3].to_string(), false)),
+    }
+}
+
+/*The comment a file starts with, either `//` lines or one block comment, without the comment
+markers. That's where a module says what it's for*/
+pub fn summary(code: &str) -> Option<String> {
+    let code = code.trim_start();
+    let text = if let Some(block) = code.strip_prefix("/*") {
+        block.split_once("*/")?.0.to_string()
+    } else {
+        code.lines()
+            .map_while(|line| line.trim_start().strip_prefix("//"))
+            .map(str::trim)
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    let text = text.trim();
+    if text.is_empty() {
+        None
+    } else {
+        Some(text.to_string())
+    }
+}
+
+/*The names an include brings into scope, or None when the file can't be loaded. Those are
+kept, the build reports them*/
+fn symbols(include: &Include, roots: &[String]) -> Option<Vec<String>> {
+    let mut writer = FileWriter::new(".".to_string());
+    writer.roots = roots.to_vec();
+    let mut vars = Variables::new();
+    // Nothing is reported from here, where the include is doesn't matter
+    let at = LexerState { line: 0, column: 0 };
+    writer
+        .add(include.path.clone(), &mut vars, !include.local, at, "")
+        .ok()?;
+    Some(
+        vars.vars
+            .into_keys()
+            .filter(|name| name != "void")
+            .collect(),
+    )
+}
+
+/*Sorts the include lines of a file into one block where the first include was, merges the
+duplicates and drops the ones none of whose names are used. Err is the position of a syntax
+error, the code isn't touched then. `roots` are the dependency directories <includes> are
+searched in*/
+pub fn organize(code: &str, roots: &[String]) -> Result<String, (usize, usize)> {
+    let mut includes: Vec<Include> = Vec::new();
+    let mut rest: Vec<&str> = Vec::new();
+    let mut block = None;
+    for line in code.split_inclusive('\n') {
+        match INCLUDE_LINE.captures(line) {
+            Some(caps) => {
+                block.get_or_insert(rest.len());
+                includes.push(match caps.get(2) {
+                    Some(path) => Include {
+                        local: false,
+                        path: path.as_str().to_string(),
+                    },
+                    None => Include {
+                        local: true,
+                        path: caps[3].to_string(),
+                    },
+                });
+            }
+            None => rest.push(line),
+        }
+    }
+    let Some(block) = block else {
+        return Ok(code.to_string());
+    };
+    let body = rest.concat();
+    let mut used: HashSet<String> = HashSet::new();
+    for token in diff::tokens(&body).map_err(|state| (state.line, state.column))? {
+        // Paths like math::sqrt or p.x use each of their parts
+        used.extend(
+            token
+                .value
+                .split(['.', ':'])
+                .filter(|part| !part.is_empty())
+                .map(str::to_string),
+        );
+    }
+    includes.sort();
+    includes.dedup();
+    includes.retain(|include| {
+        symbols(include, roots).is_none_or(|names| names.iter().any(|name| used.contains(name)))
+    });
+    let mut out = rest[..block].concat();
+    for include in &includes {
+        out += include.line().as_str();
+        out += "\n";
+    }
+    out += rest[block..].concat().as_str();
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn roots() -> Vec<String> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/imports");
+        vec![dir.to_string_lossy().to_string()]
+    }
+
+    #[test]
+    fn sorts_merges_and_drops_unused() {
+        let code = "#include \"local.wt\"\n#include <units.wt>\n#include <geo.wt>\n#include <geo.wt>\n\nint main() {\n  return area(2, 3);\n}\n";
+        assert_eq!(
+            organize(code, &roots()).unwrap(),
+            "#include <geo.wt>\n#include \"local.wt\"\n\nint main() {\n  return area(2, 3);\n}\n"
+        );
+    }
+
+    #[test]
+    fn code_without_includes_is_untouched() {
+        let code = "int main() {\n  return 0;\n}\n";
+        assert_eq!(organize(code, &roots()).unwrap(), code);
+    }
+
+    #[test]
+    fn syntax_errors_leave_the_code_alone() {
+        assert!(organize(
+            "#include <geo.wt>\nint main() {\n  return 0;\n}\n$\n",
+            &roots()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn include_lines() {
+        assert_eq!(
+            include_on("#include <geo.wt>"),
+            Some(("geo.wt".to_string(), true))
+        );
+        assert_eq!(
+            include_on("#include \"a.wt\"  "),
+            Some(("a.wt".to_string(), false))
+        );
+        assert_eq!(include_on("#include <geo.wt> int x;"), None);
+    }
+}
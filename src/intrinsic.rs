@@ -0,0 +1,298 @@
+use crate::lexer::{MAP_TYPE, RUST_SUFFIXES, STRING_TYPE, VEC_TYPE};
+use std::ops::RangeInclusive;
+
+// Builtin calls that are lowered by the transpiler instead of being looked up as variables
+pub const INTRINSICS: [&str; 39] = [
+    "assert",
+    "assert_eq",
+    "embed!",
+    "env!",
+    "trace!",
+    "debug",
+    "spawn",
+    "join",
+    "count_ones",
+    "leading_zeros",
+    "trailing_zeros",
+    "rotate_left",
+    "rotate_right",
+    "byte_swap",
+    "checked_add",
+    "checked_sub",
+    "checked_mul",
+    "wrapping_add",
+    "wrapping_sub",
+    "wrapping_mul",
+    "saturating_add",
+    "saturating_sub",
+    "saturating_mul",
+    "time::now",
+    "time::millis",
+    "random::int",
+    "random::seed",
+    "fs::read_to_string",
+    "fs::write",
+    "fs::exists",
+    "fs::lines",
+    "env::args",
+    "env::var",
+    "env::has",
+    "env::flag",
+    "env::option",
+    "exit",
+    "abort",
+    "panic",
+];
+
+// The intrinsics that work on the bits of an integer
+pub const BIT_INTRINSICS: [&str; 6] = [
+    "count_ones",
+    "leading_zeros",
+    "trailing_zeros",
+    "rotate_left",
+    "rotate_right",
+    "byte_swap",
+];
+
+/*Integer arithmetic that behaves the same in every profile, plain operators trap on overflow
+with runtime-checks and wrap without them*/
+pub const ARITH_INTRINSICS: [&str; 9] = [
+    "checked_add",
+    "checked_sub",
+    "checked_mul",
+    "wrapping_add",
+    "wrapping_sub",
+    "wrapping_mul",
+    "saturating_add",
+    "saturating_sub",
+    "saturating_mul",
+];
+
+pub fn arity(name: &str) -> RangeInclusive<usize> {
+    match name {
+        "assert_eq" | "rotate_left" | "rotate_right" => 2..=2,
+        name if ARITH_INTRINSICS.contains(&name) => 2..=2,
+        "time::now" | "time::millis" | "env::args" | "abort" => 0..=0,
+        "random::int" | "fs::write" | "env::option" => 2..=2,
+        // env! can be given a default
+        "env!" => 1..=2,
+        // A format string and what goes in it
+        "trace!" => 1..=usize::MAX,
+        _ => 1..=1,
+    }
+}
+
+pub const STRING_METHODS: [&str; 5] = ["len", "substr", "find", "split", "to_int"];
+pub const VEC_METHODS: [&str; 3] = ["len", "push", "contains"];
+pub const MAP_METHODS: [&str; 4] = ["len", "insert", "has", "remove"];
+
+// The builtin types with methods, by the name wyst programs know them as
+pub fn receiver(data_type: &str) -> Option<&'static str> {
+    if data_type == STRING_TYPE {
+        Some("string")
+    } else if data_type.starts_with(&format!("{}<", VEC_TYPE)) {
+        Some("vec")
+    } else if data_type.starts_with(&format!("{}<", MAP_TYPE)) {
+        Some("map")
+    } else {
+        None
+    }
+}
+
+// What a vec holds, Vec<i32> for a vec<vec<int>>
+pub fn vec_element(data_type: &str) -> Option<&str> {
+    data_type
+        .strip_prefix(&format!("{}<", VEC_TYPE))?
+        .strip_suffix('>')
+}
+
+pub fn methods(receiver: &str) -> &'static [&'static str] {
+    match receiver {
+        "string" => &STRING_METHODS,
+        "vec" => &VEC_METHODS,
+        _ => &MAP_METHODS,
+    }
+}
+
+// Whether a rust type is one of the sized integers the bit intrinsics take
+pub fn is_integer(data_type: &str) -> bool {
+    RUST_SUFFIXES[..12].contains(&data_type)
+}
+
+// The name a program uses for a rust type, for messages
+pub fn wyst_type(data_type: &str) -> String {
+    match data_type {
+        STRING_TYPE => "string".to_string(),
+        "i32" => "int".to_string(),
+        "f32" => "float".to_string(),
+        _ => data_type.to_string(),
+    }
+}
+
+/*Lowers a bit intrinsic to the integer method rust has for it. The counts are u32 in rust and
+become ints, a literal without a suffix is taken as an int so its type isn't ambiguous*/
+pub fn bit_op(name: &str, args: &[String]) -> String {
+    let value = operand(&args[0]);
+    match name {
+        "count_ones" | "leading_zeros" | "trailing_zeros" => {
+            format!("({}.{}() as i32)", value, name)
+        }
+        "rotate_left" | "rotate_right" => format!("{}.{}(({}) as u32)", value, name, args[1]),
+        _ => format!("{}.swap_bytes()", value),
+    }
+}
+
+/*An integer operand as the receiver of a method call. A literal, negative or not, needs a type
+or rust can't tell which integer's method it is*/
+fn operand(arg: &str) -> String {
+    let arg = arg.trim();
+    let digits = arg.strip_prefix('-').unwrap_or(arg).trim_start();
+    if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit() || c == '_') {
+        if digits.len() < arg.len() {
+            format!("(-{}i32)", digits)
+        } else {
+            format!("{}i32", digits)
+        }
+    } else {
+        format!("({})", arg)
+    }
+}
+
+/*Lowers an arithmetic intrinsic to the integer method rust has for it. checked_ stops the
+program like a failed assert when the result doesn't fit, whatever the profile*/
+pub fn arith_op(name: &str, args: &[String], location: &str) -> String {
+    let call = format!("{}.{}({})", operand(&args[0]), name, args[1]);
+    if !name.starts_with("checked_") {
+        return call;
+    }
+    format!(
+        "{}.unwrap_or_else(|| crate::wyst_panic::fail({:?}, {:?}))",
+        call,
+        format!("arithmetic overflow in {}", name),
+        location
+    )
+}
+
+// The number of arguments a builtin method takes, besides the value it's called on
+pub fn method_arity(receiver: &str, name: &str) -> Option<usize> {
+    match (receiver, name) {
+        (_, "len") | ("string", "to_int") => Some(0),
+        ("string", "find" | "split") => Some(1),
+        ("string", "substr") => Some(2),
+        ("vec", "push" | "contains") => Some(1),
+        ("map", "has" | "remove") => Some(1),
+        ("map", "insert") => Some(2),
+        _ => None,
+    }
+}
+
+/*Splits the contents of a Round token on the commas that aren't nested in brackets or strings*/
+pub fn split_args(input: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    for c in input.chars() {
+        if let Some(q) = quote {
+            current.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' => quote = Some(c),
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(current.trim().to_string());
+                current = String::new();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() || !args.is_empty() {
+        args.push(current.trim().to_string());
+    }
+    args
+}
+
+// The text of a plain "..." literal argument with its escapes resolved, None for anything that
+// has to be evaluated. Callers that put the text back into rust re-escape it with {:?}
+pub fn string_literal(arg: &str) -> Option<String> {
+    let inner = arg.strip_prefix('"')?.strip_suffix('"')?;
+    let mut text = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return None,
+            '\\' => text.push(match chars.next()? {
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                '0' => '\0',
+                '\\' => '\\',
+                '"' => '"',
+                '\'' => '\'',
+                _ => return None,
+            }),
+            _ => text.push(c),
+        }
+    }
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn bit_intrinsics_type_negative_literals() {
+        for name in BIT_INTRINSICS {
+            let out = bit_op(name, &args(&["-5", "1"]));
+            assert!(out.contains("(-5i32)."), "{}: {}", name, out);
+        }
+    }
+
+    #[test]
+    fn arith_intrinsics_type_negative_literals() {
+        for name in ARITH_INTRINSICS {
+            let out = arith_op(name, &args(&["-5", "3"]), "main.wt:1:0");
+            assert!(out.starts_with("(-5i32)."), "{}: {}", name, out);
+        }
+    }
+
+    #[test]
+    fn operands() {
+        assert_eq!(operand("5"), "5i32");
+        assert_eq!(operand("1_000"), "1_000i32");
+        assert_eq!(operand("- 7 "), "(-7i32)");
+        assert_eq!(operand("x - 1"), "(x - 1)");
+        assert_eq!(operand("-x"), "(-x)");
+    }
+
+    #[test]
+    fn string_literals() {
+        assert_eq!(string_literal("\"plain\""), Some("plain".to_string()));
+        assert_eq!(
+            string_literal(r#""say \"hi\"\n""#),
+            Some("say \"hi\"\n".to_string())
+        );
+        assert_eq!(string_literal(r#""C:\\dir""#), Some("C:\\dir".to_string()));
+        assert_eq!(string_literal(r#""a"b""#), None);
+        assert_eq!(string_literal(r#""\q""#), None);
+        assert_eq!(string_literal(r#""end\""#), None);
+        assert_eq!(string_literal("name"), None);
+    }
+}
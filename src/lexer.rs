@@ -21,19 +21,36 @@ pub enum TokenType {
     Ptr,
     Operator,
     SecondOperator,
+    Equal,
+    NotEqual,
+    LessEqual,
+    GreaterEqual,
+    And,
+    Or,
+    ShiftLeft,
+    ShiftRight,
+    PathSep,
+    // The one character operators the parser looks for, the others are Operator tokens
+    Assign,
+    Ampersand,
+    Star,
+    Colon,
     Semicolon,
     Round,
     Curly,
     Square,
     Angle,
     Include,
+    Pragma,
     String,
     Comment,
     StaticExecution,
+    Attribute,
     // EOF,
 }
 
-#[derive(Clone)]
+// Two tokens are equal when their type, text and position are
+#[derive(Clone, PartialEq)]
 pub struct Token {
     pub token_type: TokenType,
     pub value: String,
@@ -65,7 +82,89 @@ pub struct Node {
     token_regex: Lazy<Regex>,
 }
 
-const SYNTAX: [Node; 17] = [
+// Strings only come from literals, so they can all be static
+pub const STRING_TYPE: &str = "&'static str";
+// vec<T> and map<K, V>, the leading :: keeps the paths working inside modules
+pub const VEC_TYPE: &str = "Vec";
+pub const MAP_TYPE: &str = "::std::collections::HashMap";
+// What spawn gives back, join waits for the thread and returns its int
+pub const THREAD_TYPE: &str = "::std::thread::JoinHandle<i32>";
+
+// Literal suffixes rust already understands, passed through as they are
+pub const RUST_SUFFIXES: [&str; 14] = [
+    "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize", "f32",
+    "f64",
+];
+
+static TYPE_NAME: Lazy<Regex> = Lazy::new(|| Regex::new(r"[_a-zA-Z][a-zA-Z0-9_]*").unwrap());
+
+/*Maps the wyst names in a generic type like "map<string, vec<int>>" to the rust ones*/
+pub fn map_type(ty: &str) -> String {
+    TYPE_NAME
+        .replace_all(ty, |caps: &regex::Captures| match &caps[0] {
+            "int" => "i32".to_string(),
+            "float" => "f32".to_string(),
+            "string" => STRING_TYPE.to_string(),
+            "vec" => VEC_TYPE.to_string(),
+            "map" => MAP_TYPE.to_string(),
+            "thread" => THREAD_TYPE.to_string(),
+            name => name.to_string(),
+        })
+        .to_string()
+}
+
+// The arguments of a mapped generic type, "Vec<i32>" gives ["i32"]
+pub fn type_args(ty: &str) -> Vec<String> {
+    let inner = match ty.split_once('<') {
+        Some((_, inner)) => inner.strip_suffix('>').unwrap_or(inner),
+        None => return Vec::new(),
+    };
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    for c in inner.chars() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(current.trim().to_string());
+                current = String::new();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    args.push(current.trim().to_string());
+    args
+}
+
+/*Operators longer than one character, each gets its own token type. They are matched before
+anything else outside of brackets so `<=` doesn't open an angle bracket and `&&` isn't read
+as two references*/
+pub const OPERATORS: [(&str, TokenType); 10] = [
+    ("==", TokenType::Equal),
+    ("!=", TokenType::NotEqual),
+    ("<=", TokenType::LessEqual),
+    (">=", TokenType::GreaterEqual),
+    ("&&", TokenType::And),
+    ("||", TokenType::Or),
+    ("<<", TokenType::ShiftLeft),
+    (">>", TokenType::ShiftRight),
+    ("->", TokenType::Ptr),
+    ("::", TokenType::PathSep),
+];
+
+// The operator code starts with, a leading :: on a path like ::std::thread stays in the identifier
+fn operator(code: &str) -> Option<(&'static str, TokenType)> {
+    let (op, token_type) = OPERATORS.iter().find(|(op, _)| code.starts_with(op))?;
+    if *op == "::" && code[2..].starts_with(|c: char| c == '_' || c.is_ascii_alphabetic()) {
+        return None;
+    }
+    Some((op, *token_type))
+}
+
+const SYNTAX: [Node; 22] = [
     Node {
         token_type: TokenType::Semicolon,
         token_regex: Lazy::new(|| Regex::new(r"^\;").unwrap()),
@@ -85,7 +184,10 @@ const SYNTAX: [Node; 17] = [
     Node {
         token_type: TokenType::Keyword,
         token_regex: Lazy::new(|| {
-            Regex::new(r"^(pub|mut|try|catch|return|fn|let|use|cb|struct|impl|in|as)\b").unwrap()
+            Regex::new(
+                r"^((pub|mut|try|catch|return|break|continue|fn|let|use|cb|struct|impl|in|as|assert|assert_eq|spawn|join)\b|embed!|env!|trace!|(time::(now|millis)|random::(int|seed)|fs::(read_to_string|write|exists|lines)|env::(args|var|has|flag|option))\b)",
+            )
+            .unwrap()
         }),
     },
     Node {
@@ -98,21 +200,38 @@ const SYNTAX: [Node; 17] = [
         token_type: TokenType::Keyword2,
         token_regex: Lazy::new(|| Regex::new(r"^(else)\b").unwrap()),
     },
+    Node {
+        token_type: TokenType::Attribute,
+        token_regex: Lazy::new(|| Regex::new(r"^@[_a-zA-Z][a-zA-Z0-9_]*").unwrap()),
+    },
     Node {
         token_type: TokenType::Identifier,
-        token_regex: Lazy::new(|| Regex::new(r"^[.:_a-zA-Z][a-zA-Z0-9_.:]*").unwrap()),
+        // A path can start with ::, a single : is a Colon
+        token_regex: Lazy::new(|| Regex::new(r"^(::)?[._a-zA-Z][a-zA-Z0-9_.:]*").unwrap()),
     },
     Node {
         token_type: TokenType::Number,
-        token_regex: Lazy::new(|| Regex::new(r"^\d+").unwrap()),
+        token_regex: Lazy::new(|| Regex::new(r"^\d+([_a-zA-Z][a-zA-Z0-9_]*)?").unwrap()),
+    },
+    Node {
+        token_type: TokenType::Assign,
+        token_regex: Lazy::new(|| Regex::new(r"^=").unwrap()),
+    },
+    Node {
+        token_type: TokenType::Ampersand,
+        token_regex: Lazy::new(|| Regex::new(r"^&").unwrap()),
     },
     Node {
-        token_type: TokenType::Ptr,
-        token_regex: Lazy::new(|| Regex::new(r"^->").unwrap()),
+        token_type: TokenType::Star,
+        token_regex: Lazy::new(|| Regex::new(r"^\*").unwrap()),
+    },
+    Node {
+        token_type: TokenType::Colon,
+        token_regex: Lazy::new(|| Regex::new(r"^:").unwrap()),
     },
     Node {
         token_type: TokenType::Operator,
-        token_regex: Lazy::new(|| Regex::new(r"^[\&\&|\-|\+|\*|\=|\!|\&|\:]").unwrap()),
+        token_regex: Lazy::new(|| Regex::new(r"^[\-|\+|\!]").unwrap()),
     },
     Node {
         token_type: TokenType::Round,
@@ -134,12 +253,63 @@ const SYNTAX: [Node; 17] = [
         token_type: TokenType::Include,
         token_regex: Lazy::new(|| Regex::new(r#"^#include *"(.*?)""#).unwrap()),
     },
+    Node {
+        token_type: TokenType::Pragma,
+        token_regex: Lazy::new(|| Regex::new(r"^#pragma +feature\((.*?)\)").unwrap()),
+    },
     Node {
         token_type: TokenType::StaticExecution,
         token_regex: Lazy::new(|| Regex::new(r"^#").unwrap()),
     },
 ];
 
+/*Whether the `<` code starts with opens a generic like `vec<int>` rather than being a
+comparison. A generic follows a name with no space in between and closes before anything
+that can't be part of a type, or of the constant length of a buffer*/
+fn opens_generic(before: &str, code: &str) -> bool {
+    if !before.ends_with(|c: char| c == '_' || c.is_ascii_alphanumeric()) {
+        return false;
+    }
+    let mut depth = 0;
+    for c in code.chars() {
+        match c {
+            '<' => depth += 1,
+            '>' => {
+                depth -= 1;
+                if depth == 0 {
+                    return true;
+                }
+            }
+            '_' | ':' | ',' | ' ' | '+' | '-' | '*' | '/' => {}
+            c if c.is_ascii_alphanumeric() => {}
+            _ => return false,
+        }
+    }
+    false
+}
+
+// Where the text of a group token starts, one past its opening bracket
+pub fn inner_state(group: &Token) -> LexerState {
+    LexerState {
+        line: group.line,
+        column: group.column + 1,
+    }
+}
+
+// Where the lexer is after text that starts at state. Positions count characters, a tab is one
+// column like in the lsp
+pub fn advance(mut state: LexerState, text: &str) -> LexerState {
+    for c in text.chars() {
+        if c == '\n' {
+            state.line += 1;
+            state.column = 0;
+        } else {
+            state.column += 1;
+        }
+    }
+    state
+}
+
 fn get_first_char(value: &str) -> String {
     value.chars().next().unwrap().to_string()
 }
@@ -149,15 +319,31 @@ pub fn lex(
     use_whitespace: bool,
     state: LexerState,
 ) -> Result<Vec<Token>, (LexerState, Vec<Token>)> {
+    let input = code;
     let mut state = state;
     let mut tokens: Vec<Token> = Vec::new();
     let mut brstr: String = String::new();
     let mut br_state: LexerState = LexerState { line: 0, column: 0 };
     let mut brtp: Vec<u8> = Vec::new();
+    let mut start = code;
     while !code.is_empty() {
+        state = advance(state, &start[..start.len() - code.len()]);
+        start = code;
         let mut is_match = false;
         let fch = get_first_char(code);
         let brln = brtp.len();
+        if brln == 0 {
+            if let Some((op, token_type)) = operator(code) {
+                tokens.push(Token {
+                    token_type,
+                    value: op.to_string(),
+                    line: state.line,
+                    column: state.column,
+                });
+                code = &code[op.len()..];
+                continue;
+            }
+        }
         match fch.as_str() {
             "/" => {
                 brstr += "/";
@@ -327,6 +513,22 @@ pub fn lex(
                 }
             }
             "<" => {
+                // Strings and comments (0, 1, 4 and 5) keep it as text
+                let in_text = brln > 0 && [0, 1, 4, 5].contains(&brtp[brln - 1]);
+                if in_text || !opens_generic(&input[..input.len() - code.len()], code) {
+                    code = code.strip_prefix(fch.as_str()).expect("");
+                    if brln > 0 {
+                        brstr += fch.as_str();
+                    } else {
+                        tokens.push(Token {
+                            token_type: TokenType::Operator,
+                            value: fch,
+                            line: state.line,
+                            column: state.column,
+                        });
+                    }
+                    continue;
+                }
                 code = code.strip_prefix(fch.as_str()).expect("");
                 if brln > 0 {
                     brstr += fch.as_str();
@@ -351,6 +553,13 @@ pub fn lex(
                     } else {
                         brstr += fch.as_str();
                     }
+                } else if brln == 0 {
+                    tokens.push(Token {
+                        token_type: TokenType::Operator,
+                        value: fch,
+                        line: state.line,
+                        column: state.column,
+                    });
                 } else {
                     brstr += fch.as_str();
                 }
@@ -379,8 +588,6 @@ pub fn lex(
                     });
                     brstr = String::new();
                 }
-                state.line += 1;
-                state.column = 0;
             }
             _ => {
                 if brln > 0 {
@@ -412,6 +619,22 @@ pub fn lex(
                                             column: state.column,
                                         });
                                     }
+                                    "string" => {
+                                        tokens.push(Token {
+                                            token_type: s.token_type,
+                                            value: STRING_TYPE.to_string(),
+                                            line: state.line,
+                                            column: state.column,
+                                        });
+                                    }
+                                    "thread" => {
+                                        tokens.push(Token {
+                                            token_type: s.token_type,
+                                            value: THREAD_TYPE.to_string(),
+                                            line: state.line,
+                                            column: state.column,
+                                        });
+                                    }
                                     _ => {
                                         tokens.push(Token {
                                             token_type: s.token_type,
@@ -422,7 +645,6 @@ pub fn lex(
                                     }
                                 }
                             }
-                            state.column += caps[0].len();
                         } else {
                             continue;
                         };
@@ -437,3 +659,231 @@ pub fn lex(
     }
     Ok(tokens)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(code: &str) -> Vec<(TokenType, String)> {
+        lex(code, false, LexerState { line: 1, column: 0 })
+            .expect("lex failed")
+            .into_iter()
+            .map(|token| (token.token_type, token.value))
+            .collect()
+    }
+
+    // Longer operators have the kind the table gives them
+    fn op(value: &str) -> (TokenType, String) {
+        let kind = match OPERATORS.iter().find(|(op, _)| *op == value) {
+            Some((_, kind)) => *kind,
+            None => TokenType::Operator,
+        };
+        (kind, value.to_string())
+    }
+
+    fn ident(value: &str) -> (TokenType, String) {
+        (TokenType::Identifier, value.to_string())
+    }
+
+    fn number(value: &str) -> (TokenType, String) {
+        (TokenType::Number, value.to_string())
+    }
+
+    #[test]
+    fn generic_after_a_name() {
+        assert_eq!(
+            kinds("vec<int> xs"),
+            vec![
+                ident("vec"),
+                (TokenType::Angle, "int".to_string()),
+                ident("xs")
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_generics_are_one_group() {
+        assert_eq!(
+            kinds("map<string, vec<int>> m"),
+            vec![
+                ident("map"),
+                (TokenType::Angle, "string, vec<int>".to_string()),
+                ident("m")
+            ]
+        );
+    }
+
+    #[test]
+    fn generic_with_a_constant_length() {
+        assert_eq!(
+            kinds("buffer<int, 2 * 8> b"),
+            vec![
+                ident("buffer"),
+                (TokenType::Angle, "int, 2 * 8".to_string()),
+                ident("b")
+            ]
+        );
+    }
+
+    #[test]
+    fn spaced_comparison() {
+        assert_eq!(kinds("a < b"), vec![ident("a"), op("<"), ident("b")]);
+        assert_eq!(kinds("a > b"), vec![ident("a"), op(">"), ident("b")]);
+    }
+
+    #[test]
+    fn unspaced_comparison_that_never_closes() {
+        assert_eq!(
+            kinds("a<b;"),
+            vec![
+                ident("a"),
+                op("<"),
+                ident("b"),
+                (TokenType::Semicolon, ";".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn chained_comparisons() {
+        assert_eq!(
+            kinds("a<b && c > d"),
+            vec![
+                ident("a"),
+                op("<"),
+                ident("b"),
+                op("&&"),
+                ident("c"),
+                op(">"),
+                ident("d")
+            ]
+        );
+        assert_eq!(
+            kinds("a <= b || c >= d"),
+            vec![
+                ident("a"),
+                op("<="),
+                ident("b"),
+                op("||"),
+                ident("c"),
+                op(">="),
+                ident("d")
+            ]
+        );
+    }
+
+    #[test]
+    fn operator_table() {
+        for (value, kind) in OPERATORS {
+            assert_eq!(
+                kinds(&format!("a {} b", value)),
+                vec![ident("a"), (kind, value.to_string()), ident("b")],
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn unspaced_operators_are_one_token() {
+        assert_eq!(kinds("a==b"), vec![ident("a"), op("=="), ident("b")]);
+        assert_eq!(kinds("a<<2"), vec![ident("a"), op("<<"), number("2")]);
+        assert_eq!(
+            kinds("a>>=b"),
+            vec![
+                ident("a"),
+                (TokenType::ShiftRight, ">>".to_string()),
+                (TokenType::Assign, "=".to_string()),
+                ident("b")
+            ]
+        );
+        assert_eq!(kinds("!=b"), vec![op("!="), ident("b")]);
+    }
+
+    #[test]
+    fn path_separator_stays_in_a_path() {
+        assert_eq!(kinds("::std::thread"), vec![ident("::std::thread")]);
+        assert_eq!(kinds("a :: b"), vec![ident("a"), op("::"), ident("b")]);
+    }
+
+    #[test]
+    fn parser_punctuation_has_its_own_kind() {
+        assert_eq!(
+            kinds("a = &b * c : d"),
+            vec![
+                ident("a"),
+                (TokenType::Assign, "=".to_string()),
+                (TokenType::Ampersand, "&".to_string()),
+                ident("b"),
+                (TokenType::Star, "*".to_string()),
+                ident("c"),
+                (TokenType::Colon, ":".to_string()),
+                ident("d")
+            ]
+        );
+        assert_eq!(kinds("-b"), vec![op("-"), ident("b")]);
+    }
+
+    #[test]
+    fn comparison_inside_a_group() {
+        assert_eq!(
+            kinds("if (a < b) {}"),
+            vec![
+                (TokenType::Keyword1, "if".to_string()),
+                (TokenType::Round, "a < b".to_string()),
+                (TokenType::Curly, "".to_string())
+            ]
+        );
+    }
+
+    // Looks through the groups the way the transpiler re-lexes them
+    fn find(code: &str, state: LexerState, value: &str) -> Option<Token> {
+        for token in lex(code, false, state).expect("lex failed") {
+            if token.value == value {
+                return Some(token);
+            }
+            if [TokenType::Round, TokenType::Curly, TokenType::Square].contains(&token.token_type) {
+                if let Some(found) = find(&token.value, inner_state(&token), value) {
+                    return Some(found);
+                }
+            }
+        }
+        None
+    }
+
+    fn position(code: &str, value: &str) -> (usize, usize) {
+        let token = find(code, LexerState { line: 1, column: 0 }, value).expect("not found");
+        (token.line, token.column)
+    }
+
+    #[test]
+    fn deeply_nested_tokens_have_file_positions() {
+        let code = "int main() {\n  int a = 1;\n  if (a == 1) {\n    while (a < 3) {\n      if (a == 2) {\n        a = a + missing;\n      }\n    }\n  }\n}\n";
+        assert_eq!(position(code, "missing"), (6, 16));
+        assert_eq!(position(code, "3"), (4, 15));
+    }
+
+    #[test]
+    fn tabs_are_one_column() {
+        assert_eq!(position("{\n\t{\n\t\tx;\n\t}\n}", "x"), (3, 2));
+    }
+
+    #[test]
+    fn columns_continue_after_a_group() {
+        assert_eq!(position("f(a) + b;", "b"), (1, 7));
+        assert_eq!(position("if (x) { y; } z;", "z"), (1, 14));
+    }
+
+    #[test]
+    fn trailing_whitespace_ends_the_line() {
+        assert_eq!(position("int a;  \nint b;", "b"), (2, 4));
+    }
+
+    #[test]
+    fn angle_in_a_string_is_text() {
+        assert_eq!(
+            kinds("\"x<y\""),
+            vec![(TokenType::String, "\"x<y\"".to_string())]
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use crate::{
+    lexer::LexerState,
+    lspcom::{did_you_mean, Problem, ProblemType},
+    messages::message,
+};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+// The warnings that can be silenced with `// wyst-ignore: name` or turned into errors with --deny
+pub const LINTS: [&str; 2] = ["deprecated", "unreachable"];
+
+static IGNORE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"//\s*wyst-ignore:\s*([a-z][a-z, \-]*)").unwrap());
+
+pub fn lint(problem_type: &ProblemType) -> Option<&'static str> {
+    match problem_type {
+        ProblemType::Deprecated => Some("deprecated"),
+        ProblemType::Unreachable => Some("unreachable"),
+        _ => None,
+    }
+}
+
+struct Suppression {
+    name: String,
+    // The line it silences, the comment's own line or the one after it when it's alone
+    line: usize,
+    state: LexerState,
+    used: bool,
+}
+
+fn suppressions(code: &str) -> Vec<Suppression> {
+    let mut out = Vec::new();
+    for (i, text) in code.lines().enumerate() {
+        let Some(caps) = IGNORE.captures(text) else {
+            continue;
+        };
+        let at = caps.get(0).unwrap().start();
+        let line = if text[..at].trim().is_empty() {
+            i + 2
+        } else {
+            i + 1
+        };
+        for name in caps[1]
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            out.push(Suppression {
+                name: name.to_string(),
+                line,
+                state: LexerState {
+                    line: i + 1,
+                    column: text[..at].chars().count(),
+                },
+                used: false,
+            });
+        }
+    }
+    out
+}
+
+/*Drops the warnings a wyst-ignore comment in the code of `file` silences, and warns about the
+comments that didn't silence anything, so they don't outlive the warning they were for. Warnings
+from other files, like the ones it includes, are left alone*/
+pub fn suppress(code: &str, file: &str, warnings: &mut Vec<Problem>) {
+    let mut ignores = suppressions(code);
+    if ignores.is_empty() {
+        return;
+    }
+    warnings.retain(|warning| {
+        let Some(name) = lint(&warning.problem_type) else {
+            return true;
+        };
+        if warning.file != file {
+            return true;
+        }
+        let mut kept = true;
+        for ignore in ignores.iter_mut() {
+            if ignore.name == name && ignore.line == warning.state.line {
+                ignore.used = true;
+                kept = false;
+            }
+        }
+        kept
+    });
+    for ignore in ignores.iter().filter(|ignore| !ignore.used) {
+        let hint = if LINTS.contains(&ignore.name.as_str()) {
+            String::new()
+        } else {
+            did_you_mean(&ignore.name, LINTS.into_iter())
+        };
+        warnings.push(Problem {
+            problem_type: ProblemType::UnusedSuppression,
+            problem_msg: message("unused_suppression", &[&ignore.name, &hint]),
+            state: ignore.state,
+            file: file.to_string(),
+        });
+    }
+}
@@ -1,5 +1,12 @@
 use crate::{
-    lspcom::{get_completion, get_items, request_methods, LspServer, TextDocumentChangeParams},
+    file_writer::FileWriter,
+    imports::{include_on, organize, summary},
+    lexer::LexerState,
+    lspcom::{
+        get_completion, get_items, name_fixes, request_methods, LspServer, TextDocumentChangeParams,
+    },
+    outline::outline,
+    package,
     variable::Variables,
 };
 use lsp_types::*;
@@ -10,12 +17,61 @@ use std::{
     collections::HashMap,
     fs,
     io::{stdin, stdout, Read, Write},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
+// An open file, by the version of its last edit
+#[derive(Debug)]
+struct DocumentState {
+    text: String,
+    version: Option<i32>,
+}
+
 #[derive(Debug)]
 pub struct Server {
-    documents: HashMap<String, String>,
+    documents: HashMap<String, DocumentState>,
+    // The dependency include roots, read from wyst.toml when the server starts
+    roots: Vec<String>,
+}
+
+impl Server {
+    // The absolute path of the file included on a line of a document
+    fn include_target(&self, uri: &str, line: u32) -> Option<PathBuf> {
+        let text = &self.documents.get(uri)?.text;
+        let (path, global) = include_on(text.lines().nth(line as usize)?)?;
+        let mut writer = FileWriter::new(".".to_string());
+        writer.roots = self.roots.clone();
+        let at = LexerState {
+            line: line as usize + 1,
+            column: 0,
+        };
+        let path = writer.resolve(path, global, at, uri).ok()?;
+        fs::canonicalize(path).ok()
+    }
+}
+
+/*The file:// uri of an absolute path, like Url::from_file_path. Every byte outside the unreserved
+set is percent-encoded so spaces, `#` and `?` stay part of the path, and a windows drive path gets
+its separators turned around and a leading slash*/
+fn file_uri(path: &Path) -> Option<Uri> {
+    if !path.is_absolute() {
+        return None;
+    }
+    let text = path.to_str()?.replace('\\', "/");
+    let mut uri = String::from("file://");
+    if !text.starts_with('/') {
+        uri.push('/');
+    }
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(byte as char)
+            }
+            b':' if uri.len() == "file:///".len() + 1 => uri.push(':'),
+            _ => uri += &format!("%{:02X}", byte),
+        }
+    }
+    uri.parse().ok()
 }
 
 impl LspServer for Server {
@@ -26,25 +82,146 @@ impl LspServer for Server {
                     trigger_characters: Some(vec![".".to_string()]),
                     ..Default::default()
                 }),
+                code_action_provider: Some(CodeActionProviderCapability::Options(
+                    CodeActionOptions {
+                        code_action_kinds: Some(vec![
+                            CodeActionKind::QUICKFIX,
+                            CodeActionKind::SOURCE_ORGANIZE_IMPORTS,
+                        ]),
+                        ..Default::default()
+                    },
+                )),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                definition_provider: Some(OneOf::Left(true)),
+                document_symbol_provider: Some(OneOf::Left(true)),
                 ..Default::default()
             },
             ..Default::default()
         }
     }
     fn completion(&mut self, params: CompletionParams) -> CompletionResponse {
-        let text = self
+        let Some(document) = self
             .documents
             .get(params.text_document_position.text_document.uri.as_str())
-            .expect("err_textdoc");
+        else {
+            return CompletionResponse::Array(vec![]);
+        };
         let items: Variables = get_completion(
-            text.clone(),
+            document.text.clone(),
             params.text_document_position.position.line as usize + 1,
             params.text_document_position.position.character as usize,
+            &self.roots,
         );
         CompletionResponse::Array(get_items(items, "".to_string()))
     }
+    fn code_action(&mut self, params: CodeActionParams) -> CodeActionResponse {
+        let uri = params.text_document.uri;
+        let Some(text) = self
+            .documents
+            .get(uri.as_str())
+            .map(|document| &document.text)
+        else {
+            return vec![];
+        };
+        let mut actions = Vec::new();
+        // A typo in a name on the lines asked about is replaced by the name it's closest to
+        let lines = params.range.start.line..=params.range.end.line;
+        for (token, known) in name_fixes(text, &self.roots) {
+            let line = token.line as u32 - 1;
+            if !lines.contains(&line) {
+                continue;
+            }
+            let edit = TextEdit {
+                range: Range {
+                    start: Position::new(line, token.column as u32),
+                    end: Position::new(line, (token.column + token.value.chars().count()) as u32),
+                },
+                new_text: known.clone(),
+            };
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Change '{}' to '{}'", token.value, known),
+                kind: Some(CodeActionKind::QUICKFIX),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                    ..Default::default()
+                }),
+                is_preferred: Some(true),
+                ..Default::default()
+            }));
+        }
+        if let Ok(organized) = organize(text, &self.roots) {
+            if organized != *text {
+                // The whole document is replaced, the end is past the last line
+                let edit = TextEdit {
+                    range: Range {
+                        start: Position::new(0, 0),
+                        end: Position::new(text.lines().count() as u32 + 1, 0),
+                    },
+                    new_text: organized,
+                };
+                actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: "Organize includes".to_string(),
+                    kind: Some(CodeActionKind::SOURCE_ORGANIZE_IMPORTS),
+                    edit: Some(WorkspaceEdit {
+                        changes: Some(HashMap::from([(uri, vec![edit])])),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }));
+            }
+        }
+        actions
+    }
+    fn hover(&mut self, params: HoverParams) -> Option<Hover> {
+        let position = params.text_document_position_params;
+        let path =
+            self.include_target(position.text_document.uri.as_str(), position.position.line)?;
+        let mut value = format!("`{}`", path.display());
+        if let Some(summary) = fs::read_to_string(&path).ok().as_deref().and_then(summary) {
+            value += "\n\n";
+            value += summary.as_str();
+        }
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: None,
+        })
+    }
+    fn goto_definition(&mut self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let position = params.text_document_position_params;
+        let path =
+            self.include_target(position.text_document.uri.as_str(), position.position.line)?;
+        let uri = file_uri(&path)?;
+        Some(GotoDefinitionResponse::Scalar(Location::new(
+            uri,
+            Range::default(),
+        )))
+    }
+    fn document_symbol(&mut self, params: DocumentSymbolParams) -> Option<DocumentSymbolResponse> {
+        let document = self.documents.get(params.text_document.uri.as_str())?;
+        Some(DocumentSymbolResponse::Nested(outline(&document.text)))
+    }
     fn did_change(&mut self, params: TextDocumentChangeParams) {
-        self.documents.insert(params.uri, params.text);
+        // Edits can arrive out of order, an older version than the one held is dropped
+        if let (Some(held), Some(version)) = (
+            self.documents
+                .get(&params.uri)
+                .and_then(|document| document.version),
+            params.version,
+        ) {
+            if version <= held {
+                return;
+            }
+        }
+        self.documents.insert(
+            params.uri,
+            DocumentState {
+                text: params.text,
+                version: params.version,
+            },
+        );
     }
 }
 
@@ -56,6 +233,7 @@ pub fn run_lsp_server() {
     let mut reader = stdin();
     let mut server = Server {
         documents: HashMap::new(),
+        roots: package::roots(),
     };
     loop {
         let mut input = String::new();
@@ -88,6 +266,30 @@ pub fn run_lsp_server() {
                     "result": server.completion(serde_json::from_value(serde_json::to_value(client_json["params"].as_object()).expect("err_pars2")).unwrap())
                 }))
                 .unwrap(),
+                request_methods::CODE_ACTION => serde_json::to_string(&json!({
+                    "jsonrpc": "2.0",
+                    "id": client_json["id"].as_u64().unwrap(),
+                    "result": server.code_action(serde_json::from_value(serde_json::to_value(client_json["params"].as_object()).expect("err_pars2")).unwrap())
+                }))
+                .unwrap(),
+                request_methods::HOVER => serde_json::to_string(&json!({
+                    "jsonrpc": "2.0",
+                    "id": client_json["id"].as_u64().unwrap(),
+                    "result": server.hover(serde_json::from_value(serde_json::to_value(client_json["params"].as_object()).expect("err_pars2")).unwrap())
+                }))
+                .unwrap(),
+                request_methods::DEFINITION => serde_json::to_string(&json!({
+                    "jsonrpc": "2.0",
+                    "id": client_json["id"].as_u64().unwrap(),
+                    "result": server.goto_definition(serde_json::from_value(serde_json::to_value(client_json["params"].as_object()).expect("err_pars2")).unwrap())
+                }))
+                .unwrap(),
+                request_methods::DOCUMENT_SYMBOL => serde_json::to_string(&json!({
+                    "jsonrpc": "2.0",
+                    "id": client_json["id"].as_u64().unwrap(),
+                    "result": server.document_symbol(serde_json::from_value(serde_json::to_value(client_json["params"].as_object()).expect("err_pars2")).unwrap())
+                }))
+                .unwrap(),
                 request_methods::DID_CHANGE =>{
                     server.did_change(serde_json::from_value(serde_json::to_value(client_json["params"].as_object()).expect("err_pars2")).expect("err_pars3"));
                     "None".to_string()},
@@ -1,12 +1,19 @@
 use crate::{
+    diff,
+    lexer::{LexerState, Token, TokenType},
+    messages::message,
     transpiler::Transpiler,
     variable::{VariableType, Variables},
 };
 use lsp_types::{
-    CompletionItem, CompletionItemKind, CompletionParams, CompletionResponse, InitializeResult,
+    CodeActionParams, CodeActionResponse, CompletionItem, CompletionItemKind,
+    CompletionItemLabelDetails, CompletionItemTag, CompletionParams, CompletionResponse,
+    DocumentSymbolParams, DocumentSymbolResponse, GotoDefinitionParams, GotoDefinitionResponse,
+    Hover, HoverParams, InitializeResult,
 };
 use rand::{thread_rng, Rng};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 pub fn place_at(input: String, in2: String, line_goal: usize, column_goal: usize) -> String {
     let mut line: usize = 1;
@@ -26,24 +33,144 @@ pub fn place_at(input: String, in2: String, line_goal: usize, column_goal: usize
     out
 }
 
-pub fn get_completion(input: String, line: usize, column: usize) -> Variables {
+pub fn get_completion(input: String, line: usize, column: usize, roots: &[String]) -> Variables {
     let rand_id: u32 = thread_rng().gen();
     let rand_id: String = rand_id.to_string();
     let mut transpiler = Transpiler {
         peek: rand_id.clone(),
         ..Default::default()
     };
-    let mut vars = Variables::new();
-    transpiler.transpile(place_at(input, rand_id, line, column), 0, &mut vars);
+    transpiler.writer.roots = roots.to_vec();
+    let input = place_at(input, rand_id.clone(), line, column);
+    let offset = input.find(&rand_id).unwrap_or(input.len());
+    analyze_up_to(&input, offset, &mut transpiler);
     transpiler.matched_vars
 }
 
+/*Every use of a name that isn't declared and has a close match, with the name to put there. A
+file that doesn't lex has none*/
+pub fn name_fixes(source: &str, roots: &[String]) -> Vec<(Token, String)> {
+    let Ok(tokens) = diff::tokens(source) else {
+        return vec![];
+    };
+    let mut transpiler = Transpiler::default();
+    transpiler.writer.roots = roots.to_vec();
+    transpiler.transpile(source.to_string(), 0, &mut Variables::new());
+    tokens
+        .into_iter()
+        .filter(|token| token.token_type == TokenType::Identifier)
+        .filter_map(|token| {
+            let (_, known) = transpiler
+                .suggestions
+                .iter()
+                .find(|(name, _)| *name == token.value)?;
+            let known = known.clone();
+            Some((token, known))
+        })
+        .collect()
+}
+
+/*The source with the bodies of the functions after the top level item that `offset` is in
+emptied, their signatures and everything else stay so the names are all still declared. Newlines
+in the bodies are kept, the lines of what's left don't move*/
+fn skim(source: &str, offset: usize) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut depth = 0usize;
+    let mut ended = false;
+    let mut skipping = false;
+    // The last character outside of brackets, a `{` after `)` opens a function body
+    let mut last = ' ';
+    let mut chars = source.char_indices().peekable();
+    while let Some((at, c)) = chars.next() {
+        let mut text = String::from(c);
+        match c {
+            '"' => {
+                while let Some((_, c)) = chars.next() {
+                    text.push(c);
+                    if c == '\\' {
+                        if let Some((_, c)) = chars.next() {
+                            text.push(c);
+                        }
+                    } else if c == '"' {
+                        break;
+                    }
+                }
+            }
+            '/' if matches!(chars.peek(), Some((_, '/'))) => {
+                while let Some(&(_, c)) = chars.peek() {
+                    if c == '\n' {
+                        break;
+                    }
+                    text.push(c);
+                    chars.next();
+                }
+            }
+            '/' if matches!(chars.peek(), Some((_, '*'))) => {
+                let mut prev = ' ';
+                for (_, c) in chars.by_ref() {
+                    text.push(c);
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            '(' | '[' | '{' => {
+                if c == '{' && depth == 0 && ended && last == ')' {
+                    skipping = true;
+                    out.push(c);
+                }
+                depth += 1;
+                if skipping {
+                    continue;
+                }
+            }
+            ')' | ']' | '}' => {
+                depth = depth.saturating_sub(1);
+                if skipping && depth == 0 {
+                    skipping = false;
+                    out.push(c);
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        if skipping {
+            out.extend(text.chars().filter(|c| *c == '\n'));
+            continue;
+        }
+        out += text.as_str();
+        if depth == 0 {
+            if !c.is_whitespace() {
+                last = c;
+            }
+            if at >= offset && (c == ';' || c == '}') {
+                ended = true;
+            }
+        }
+    }
+    out
+}
+
+/*Analyzes a file as far as it takes to answer a request at the byte `offset`, for completion on
+every keystroke. Function bodies past the top level item at `offset` aren't transpiled, so only
+the problems before it are complete. Gives the top level names, the transpiler has the rest*/
+pub fn analyze_up_to(source: &str, offset: usize, transpiler: &mut Transpiler) -> Variables {
+    let mut vars = Variables::new();
+    transpiler.transpile(skim(source, offset), 0, &mut vars);
+    vars
+}
+
 pub mod request_methods {
     pub const INITIALIZE: &str = "initialize";
     pub const COMPLETION: &str = "textDocument/completion";
     pub const INITIALIZED: &str = "initialized";
     pub const SHUTDOWN: &str = "shutdown";
     pub const DID_CHANGE: &str = "textDocument/didChange";
+    pub const CODE_ACTION: &str = "textDocument/codeAction";
+    pub const HOVER: &str = "textDocument/hover";
+    pub const DEFINITION: &str = "textDocument/definition";
+    pub const DOCUMENT_SYMBOL: &str = "textDocument/documentSymbol";
 }
 
 pub trait LspServer {
@@ -54,18 +181,47 @@ pub trait LspServer {
     fn initialize(&mut self) -> InitializeResult {
         InitializeResult::default()
     }
+    fn code_action(&mut self, _params: CodeActionParams) -> CodeActionResponse {
+        vec![]
+    }
+    fn hover(&mut self, _params: HoverParams) -> Option<Hover> {
+        None
+    }
+    fn goto_definition(&mut self, _params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        None
+    }
+    fn document_symbol(&mut self, _params: DocumentSymbolParams) -> Option<DocumentSymbolResponse> {
+        None
+    }
 }
 
 #[derive(Debug, Eq, PartialEq, Clone, Default, Deserialize, Serialize)]
 pub struct TextDocumentChangeParams {
     pub uri: String,
     pub text: String,
+    // Goes up with every edit, clients that don't send it always replace the text
+    #[serde(default)]
+    pub version: Option<i32>,
 }
 
 pub fn get_items(mut items: Variables, lname: String) -> Vec<CompletionItem> {
     let mut completion_items: Vec<CompletionItem> = Vec::new();
     for (name, var) in items.iter_mut() {
         let mut item = CompletionItem::new_simple(lname.clone() + &name, var.desc.clone());
+        if var.deprecated.is_some() {
+            item.tags = Some(vec![CompletionItemTag::DEPRECATED]);
+        }
+        if var.vtype == VariableType::Func {
+            let params: Vec<String> = var
+                .signature
+                .iter()
+                .map(|param| format!("{} {}", param.data_type, param.name))
+                .collect();
+            item.label_details = Some(CompletionItemLabelDetails {
+                detail: Some(format!("({})", params.join(", "))),
+                description: None,
+            });
+        }
         if var.vtype != VariableType::Func {
             completion_items.extend(
                 get_items(var.params.clone(), name.to_owned() + "::")
@@ -94,17 +250,37 @@ pub fn get_items(mut items: Variables, lname: String) -> Vec<CompletionItem> {
             VariableType::Namespace => {
                 item.kind = Some(CompletionItemKind::MODULE);
             }
+            VariableType::Alias => {
+                item.kind = Some(CompletionItemKind::STRUCT);
+            }
         }
         completion_items.push(item);
     }
     completion_items
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum ProblemType {
     VariableNotFound,
     FileNotFound,
     HeaderSyntaxError,
+    MissingReturn,
+    IndexOutOfBounds,
+    UnknownAttribute,
+    MisplacedAttribute,
+    IntrinsicArguments,
+    UnknownMethod,
+    SpawnTarget,
+    LiteralSuffix,
+    Deprecated,
+    BufferLength,
+    FeatureGate,
+    UnusedSuppression,
+    StructLayout,
+    SyntaxError,
+    Unreachable,
+    ImplTarget,
+    Sandboxed,
 }
 
 #[derive(Clone, Debug)]
@@ -112,4 +288,124 @@ pub struct Problem {
     //pub code: u32,
     pub problem_type: ProblemType,
     pub problem_msg: String,
+    pub state: LexerState,
+    // The source file, empty until the transpiler fills it in for the passes that don't know it
+    pub file: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.state.line > 0 {
+            write!(
+                f,
+                "{:?}[{}]: {}:{}:{}: {}",
+                self.problem_type,
+                self.problem_type.code(),
+                self.file,
+                self.state.line,
+                self.state.column,
+                self.problem_msg
+            )
+        } else {
+            write!(
+                f,
+                "{:?}[{}]: {}",
+                self.problem_type,
+                self.problem_type.code(),
+                self.problem_msg
+            )
+        }
+    }
+}
+
+// Edits to turn a into b, swapping two neighbouring letters counts as one like the other typos
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/*The candidate closest to a name that wasn't found, None when none of them is close enough to
+be a typo*/
+pub fn closest<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let limit = (name.chars().count() / 3).max(1);
+    let mut best: Option<(usize, &str)> = None;
+    for candidate in candidates {
+        let distance = edit_distance(name, candidate);
+        if distance <= limit && best.is_none_or(|(closest, _)| distance < closest) {
+            best = Some((distance, candidate));
+        }
+    }
+    best.map(|(_, candidate)| candidate)
+}
+
+// ", did you mean 'length'?" for the closest candidate, or nothing
+pub fn did_you_mean<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> String {
+    match closest(name, candidates) {
+        Some(candidate) => message("did_you_mean", &[&candidate]),
+        None => String::new(),
+    }
+}
+
+// Warnings don't stop the build, each one is printed once
+pub fn warnings(warnings: &[Problem]) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for warning in warnings {
+        let line = format!("warning: {}", warning);
+        if !lines.contains(&line) {
+            lines.push(line);
+        }
+    }
+    lines
+}
+
+/*The lines to print for a list of problems. Repeats of a problem at the same place, like the
+ones from a file included twice, are counted under the first one, and at most limit distinct
+problems are shown. A place is a file and a position in it*/
+pub fn summarize(problems: &[Problem], limit: Option<usize>) -> Vec<String> {
+    let mut groups: Vec<(&Problem, usize)> = Vec::new();
+    for problem in problems {
+        match groups.iter_mut().find(|(first, _)| {
+            first.problem_type == problem.problem_type
+                && first.problem_msg == problem.problem_msg
+                && first.file == problem.file
+                && first.state == problem.state
+        }) {
+            Some((_, repeats)) => *repeats += 1,
+            None => groups.push((problem, 0)),
+        }
+    }
+    let shown = limit.unwrap_or(groups.len()).min(groups.len());
+    let mut lines = Vec::new();
+    for (first, repeats) in &groups[..shown] {
+        lines.push(first.to_string());
+        if *repeats > 0 {
+            lines.push(message("similar_suppressed", &[repeats]));
+        }
+    }
+    if shown < groups.len() {
+        lines.push(message("more_not_shown", &[&(groups.len() - shown)]));
+    }
+    if !lines.is_empty() {
+        lines.push(message("explain_hint", &[]));
+    }
+    lines
 }
@@ -1,22 +1,154 @@
+mod analysis;
+mod ast_json;
+mod ast_text;
+mod attribute;
+mod cfg;
 mod compile;
+mod completions;
+mod config;
+mod diff;
 mod dllmgr;
+mod explain;
 mod file_writer;
+mod imports;
+mod intrinsic;
 mod lexer;
+mod lint;
 mod lsp;
 mod lspcom;
+mod manifest;
+mod messages;
+mod outline;
+mod package;
 mod parser;
+mod playground;
+mod pragma;
+mod provenance;
+mod summary;
+mod testing;
 mod transpiler;
+mod types;
 mod variable;
-use clap::Parser;
-use std::{fs, path::Path};
+use clap::{CommandFactory, Parser, Subcommand};
+use std::{fs, io::Read, path::Path, time::Instant};
 use transpiler::Transpiler;
 use variable::{Variable, VariableType, Variables};
 
 use crate::lsp::run_lsp_server;
 
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print the value of every setting after the config files and flags are applied
+    Show {
+        /// Also print where each value came from
+        #[clap(long)]
+        origin: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Compile main.wt with its @test functions and run each of them
+    Test {
+        /// Append the lcov coverage of the tests to coverage.lcov
+        #[clap(long)]
+        coverage: bool,
+        /// Run the ```wyst examples in the comments of main.wt instead of the @test functions
+        #[clap(long)]
+        doc: bool,
+    },
+    /// Download the dependencies in wyst.toml at the revisions in wyst.lock
+    Fetch,
+    /// Resolve the dependencies in wyst.toml again and rewrite wyst.lock
+    Update,
+    /// Copy the dependencies and the standard library into vendor/ for offline builds
+    Vendor,
+    /// Write the runtime modules to a directory, for builds with --runtime-dir
+    Runtime { dir: String },
+    /// Print the long explanation of an error code, like E0001
+    Explain { code: String },
+    /// Settings come from ~/.config/wyst/config.toml, then [settings] in wyst.toml, then flags
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Print the completion script for a shell
+    Completions {
+        #[clap(value_parser = completions::SHELLS)]
+        shell: String,
+    },
+    /// Rewrite a source file in place
+    Fmt {
+        /// Sort the includes, merge duplicates and remove the ones that aren't used
+        #[clap(long)]
+        organize_imports: bool,
+
+        #[clap(default_value = "main.wt")]
+        file: String,
+    },
+    /// Print the rust code of a program, diagnostics go to stderr
+    Transpile {
+        /// The source file, - reads it from stdin
+        #[clap(default_value = "main.wt")]
+        file: String,
+
+        /// The language to generate
+        #[clap(long, value_parser = ["rust"], default_value = "rust")]
+        target: String,
+    },
+    /// Transpile main.wt and report its errors and warnings without compiling it
+    Check {
+        /// Treat these warnings as errors
+        #[clap(long, value_parser = lint::LINTS)]
+        deny: Vec<String>,
+    },
+    /// Print the parsed nodes of a file, json has a version field and a schema (see --schema)
+    Ast {
+        #[clap(default_value = "main.wt")]
+        file: String,
+
+        #[clap(long, value_parser = ["text", "json"], default_value = "text")]
+        format: String,
+
+        /// Print the json schema of the current version instead
+        #[clap(long)]
+        schema: bool,
+    },
+    /// Compare two programs token by token, ignoring whitespace and comments
+    Diff { old: String, new: String },
+    /// Run a local http server for web playgrounds, POST /compile transpiles a program
+    Serve {
+        #[clap(long)]
+        playground: u16,
+        /// The one origin whose web pages may call the server, like https://play.example.org
+        #[clap(long)]
+        allow_origin: Option<String>,
+    },
+    /// Compile main.wt to an executable using a profile from wyst.toml
+    Build {
+        /// Use [profile.release] instead of [profile.debug]
+        #[clap(long)]
+        release: bool,
+
+        #[clap(short, long, default_value = "main")]
+        output: String,
+
+        /// amalgamation writes the whole program to one rust file (output.rs) instead of compiling it
+        #[clap(long, value_parser = ["exe", "amalgamation"], default_value = "exe")]
+        emit: String,
+
+        /// Print how many files, lines and symbols were compiled and how long each phase took
+        #[clap(long)]
+        summary: bool,
+    },
+}
+
 // Arguments for Wyst (short and long version)
 #[derive(Parser)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     //#[clap(short, long)]
     //rust: Option<String>,
     #[clap(short, long)]
@@ -27,73 +159,338 @@ struct Args {
 
     #[clap(long)]
     stdio: bool,
+
+    #[clap(long, value_parser = ["dot"])]
+    dump_cfg: Option<String>,
+
+    /// Print the parsed main.wt in the canonical ast text form
+    #[clap(long, value_parser = ["text"])]
+    dump_ast: Option<String>,
+
+    /// Check a file written by --dump-ast or wyst ast and print it back in canonical form
+    #[clap(long)]
+    read_ast: Option<String>,
+
+    /// Features from the [features] section of wyst.toml to turn on
+    #[clap(long, global = true, value_delimiter = ',')]
+    features: Vec<String>,
+
+    /// Count how often each statement runs, the program appends an lcov report to coverage.lcov
+    #[clap(long)]
+    coverage: bool,
+
+    /// Make time:: read a fake clock and random:: start from a fixed seed, so every run is the same
+    #[clap(long, global = true)]
+    deterministic: bool,
+
+    /// The language of the diagnostics, en or es
+    #[clap(long, global = true)]
+    locale: Option<String>,
+
+    /// Stop printing errors after this many distinct ones
+    #[clap(long, global = true)]
+    error_limit: Option<usize>,
+
+    /// Use the runtime modules in this directory instead of copying the bundled ones into build/
+    #[clap(long, global = true)]
+    runtime_dir: Option<String>,
 }
 
 fn main() {
-    let args = Args::parse();
+    let mut args = Args::parse();
+    let config = config::Config::load(&[
+        ("locale", args.locale.clone()),
+        (
+            "error-limit",
+            args.error_limit.map(|limit| limit.to_string()),
+        ),
+        ("runtime-dir", args.runtime_dir.clone()),
+    ])
+    .unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+    if let Some(Command::Config {
+        action: ConfigAction::Show { origin },
+    }) = args.command
+    {
+        print!("{}", config.show(origin));
+        return;
+    }
+    // The flags stand for the layered settings from here on
+    args.error_limit = config.error_limit().unwrap_or_default();
+    args.runtime_dir = config.get("runtime-dir").map(str::to_string);
+    if let Err(err) = messages::set_locale(config.get("locale").unwrap_or("en")) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+    let runtime_dir = args
+        .runtime_dir
+        .as_deref()
+        .map(file_writer::load_runtime_dir);
+    if let Some(Command::Runtime { ref dir }) = args.command {
+        if let Err(err) = file_writer::install_runtime(dir) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(Command::Completions { ref shell }) = args.command {
+        print!("{}", completions::script(shell, Args::command()));
+        return;
+    }
+    if let Some(Command::Explain { ref code }) = args.command {
+        match explain::explain(code) {
+            Some(text) => println!("{}", text),
+            None => {
+                eprintln!("'{}' isn't an error code", code);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(Command::Fmt {
+        organize_imports,
+        ref file,
+    }) = args.command
+    {
+        if !organize_imports {
+            eprintln!("Nothing to do, wyst fmt only supports --organize-imports");
+            std::process::exit(1);
+        }
+        let code = fs::read_to_string(file).unwrap_or_else(|err| {
+            eprintln!("failed to read {}: {}", file, err);
+            std::process::exit(1);
+        });
+        match imports::organize(&code, &package::roots()) {
+            Ok(organized) => {
+                if organized != code {
+                    fs::write(file, organized).expect("failed to write");
+                }
+            }
+            Err((line, column)) => {
+                eprintln!("Invalid syntax at {}:{}:{}", file, line, column);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(Command::Transpile { ref file, .. }) = args.command {
+        let features = package::load_features(&args.features);
+        if !transpile(file, &features, args.error_limit) {
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(Command::Check { ref deny }) = args.command {
+        let features = package::load_features(&args.features);
+        if !check(&features, deny, args.error_limit) {
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(Command::Ast {
+        ref file,
+        ref format,
+        schema,
+    }) = args.command
+    {
+        if schema {
+            print!("{}", ast_json::SCHEMA);
+            return;
+        }
+        let code = fs::read_to_string(file).unwrap_or_else(|err| {
+            eprintln!("failed to read {}: {}", file, err);
+            std::process::exit(1);
+        });
+        match format.as_str() {
+            "json" => match ast_json::dump_file(&code, file) {
+                Ok(json) => print!("{}", json),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    std::process::exit(1);
+                }
+            },
+            _ => match ast_text::dump_file(&code, file) {
+                Ok(text) => print!("{}", text),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    std::process::exit(1);
+                }
+            },
+        }
+        return;
+    }
+    if let Some(Command::Diff { ref old, ref new }) = args.command {
+        let read = |path: &String| {
+            let code = fs::read_to_string(path).unwrap_or_else(|err| {
+                eprintln!("failed to read {}: {}", path, err);
+                std::process::exit(1);
+            });
+            diff::tokens(&code).unwrap_or_else(|state| {
+                eprintln!("Invalid syntax at {}:{}:{}", path, state.line, state.column);
+                std::process::exit(1);
+            })
+        };
+        let delta = diff::diff(old, &read(old), new, &read(new));
+        if delta.is_empty() {
+            println!("No semantic changes, only layout and comments differ");
+            return;
+        }
+        print!("{}", delta);
+        std::process::exit(1);
+    }
+    if let Some(Command::Serve {
+        playground,
+        ref allow_origin,
+    }) = args.command
+    {
+        playground::serve(playground, allow_origin.as_deref());
+        return;
+    }
+    if let Some(Command::Test { coverage, doc }) = args.command {
+        let features = package::load_features(&args.features);
+        if !testing::run_tests(
+            coverage,
+            doc,
+            args.deterministic,
+            &features,
+            runtime_dir,
+            args.error_limit,
+        ) {
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(Command::Fetch | Command::Update) = args.command {
+        if let Err(err) = package::fetch(matches!(args.command, Some(Command::Update))) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(Command::Vendor) = args.command {
+        let stdlib = file_writer::stdlib_dir();
+        if let Err(err) = package::vendor(Path::new(&stdlib)) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(Command::Build {
+        release,
+        ref output,
+        ref emit,
+        summary,
+    }) = args.command
+    {
+        let mut profile = manifest::load_profile(if release { "release" } else { "debug" });
+        profile.deterministic |= args.deterministic;
+        let features = package::load_features(&args.features);
+        let done = build(
+            output,
+            &profile,
+            &features,
+            args.coverage,
+            emit == "amalgamation",
+            runtime_dir,
+            args.error_limit,
+        );
+        if summary {
+            print!("{}", done.table());
+        }
+        if done.errors > 0 {
+            std::process::exit(1);
+        }
+        return;
+    }
     match args.stdio {
         true => {
             run_lsp_server();
         }
         false => {
-            match args.compile {
-                Some(ref exe_name) => {
-                    let file_content = fs::read_to_string("main.wt").expect("Error reading file");
-                    if Path::new("build").exists() {
-                        fs::remove_dir_all("build").expect("err rm build");
+            if args.dump_cfg.is_some() {
+                let file_content = fs::read_to_string("main.wt").expect("Error reading file");
+                match cfg::dump_dot(&file_content, "main.wt") {
+                    Ok(dot) => print!("{}", dot),
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        std::process::exit(1);
                     }
-                    fs::create_dir("build").expect("error making build");
-                    let mut trsp = Transpiler::default();
-                    let mut vars = Variables::new();
-                    let mut transpiled_code = trsp.transpile(file_content, 0, &mut vars);
-                    transpiled_code += "\nfn main() {";
-                    transpiled_code += "std::process::exit(";
-                    transpiled_code += vars.get_var("main".to_string(), &mut trsp).as_str();
-                    transpiled_code += "())}";
-                    for problem in &trsp.problems {
-                        println!("{:?}: {}", problem.problem_type, problem.problem_msg)
+                }
+                return;
+            }
+            if args.dump_ast.is_some() {
+                let file_content = fs::read_to_string("main.wt").expect("Error reading file");
+                match ast_text::dump_file(&file_content, "main.wt") {
+                    Ok(text) => print!("{}", text),
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        std::process::exit(1);
                     }
-                    if trsp.problems.len() > 0 {
-                        return;
+                }
+                return;
+            }
+            if let Some(ref path) = args.read_ast {
+                let text = fs::read_to_string(path).expect("Error reading file");
+                let read = match text.trim_start().starts_with('{') {
+                    true => ast_json::read(&text),
+                    false => ast_text::read(&text),
+                };
+                match read {
+                    Ok(asts) => print!("{}", ast_text::dump(&asts)),
+                    Err(err) => {
+                        eprintln!("{}:{}", path, err);
+                        std::process::exit(1);
                     }
-                    trsp.writer.write();
-
-                    compile::write_to_rust_file(&transpiled_code, "build/main.rs")
-                        .expect("Error writing to temporary Rust file");
-                    std::env::set_current_dir("build").expect("setDir err: ");
-                    compile::compile_to_executable(exe_name)
-                        .expect("Error compiling to executable");
-                    std::env::set_current_dir("..").expect("setDir0 err: ");
-                    fs::rename(Path::new("build").join(exe_name).as_path(), exe_name)
-                        .expect("RenameErrBuld: ");
-                    fs::remove_dir_all("build").expect("err rm build");
                 }
-                None => {}
+                return;
+            }
+            if let Some(ref exe_name) = args.compile {
+                let features = package::load_features(&args.features);
+                let mut profile = manifest::load_profile("debug");
+                profile.deterministic |= args.deterministic;
+                let done = build(
+                    exe_name,
+                    &profile,
+                    &features,
+                    args.coverage,
+                    false,
+                    runtime_dir.clone(),
+                    args.error_limit,
+                );
+                if done.errors > 0 {
+                    std::process::exit(1);
+                }
             }
             match args.dll {
                 Some(ref dll_path) => {
+                    package::require_lock();
+                    let features = package::load_features(&args.features);
+                    let toolchain = compile::load_toolchain();
                     let file_content = fs::read_to_string("lib.wt").expect("Error reading file");
                     if Path::new("build").exists() {
                         fs::remove_dir_all("build").expect("err rm build");
                     }
                     fs::create_dir("build").expect("error making build");
                     let mut trsp = Transpiler::default();
+                    trsp.writer.configure(&features);
+                    trsp.writer.runtime_dir = runtime_dir;
                     let mut vars = Variables::new();
                     let transpiled_code = trsp.transpile(file_content, 0, &mut vars);
-                    for problem in trsp.problems {
-                        println!("{}", problem.problem_msg)
+                    for line in lspcom::summarize(&trsp.problems, args.error_limit) {
+                        println!("{}", line)
                     }
                     trsp.writer.write();
-                    let mut dll_main = String::from(
-                        "mod wslib;use wslib::*;\nfn call_fn(fn_name: &str, params: Vec<Param>)->i32{match fn_name {",
-                    );
+                    let mut dll_main = trsp.writer.runtime_mods();
+                    dll_main += "mod wslib;use wslib::*;\nfn call_fn(fn_name: &str, params: Vec<Param>)->i32{match fn_name {";
                     for (name, var) in vars.vars.clone() {
                         if var.vtype != VariableType::Func {
                             continue;
                         }
                         let mut dparams = String::new();
                         println!("{:?}", var.params.vars);
-                        for i in 0..var.params.vars.len() {
+                        for i in 0..var.signature.len() {
                             dparams += format!("params.get({}).expect(\"Err_prms\"),", i).as_str();
                         }
                         dll_main += format!(
@@ -108,8 +505,11 @@ fn main() {
                         .expect("Error writing to temporary Rust file");
                     compile::write_to_rust_file(&dll_main, "build/main.rs")
                         .expect("Error writing to main dll");
+                    let mut rustc_args = manifest::load_profile("debug").rustc_args();
+                    rustc_args.extend(features.rustc_args());
                     std::env::set_current_dir("build").expect("setDir err: ");
-                    compile::compile_to_executable("run").expect("Error compiling to executable");
+                    compile::compile_to_executable(&toolchain, "run", &rustc_args)
+                        .expect("Error compiling to executable");
                     dllmgr::write_dll(vars, "run".to_string(), dll_path.to_string());
                     // fs::remove_dir_all("build").expect("err rm build");
                 }
@@ -118,3 +518,294 @@ fn main() {
         }
     }
 }
+
+/*Transpiles main.wt without compiling it, false if there were errors or denied warnings*/
+fn check(features: &package::Features, deny: &[String], error_limit: Option<usize>) -> bool {
+    let file_content = fs::read_to_string("main.wt").expect("Error reading file");
+    let mut trsp = Transpiler::default();
+    trsp.writer.configure(features);
+    let mut vars = Variables::new();
+    trsp.transpile(file_content.clone(), 0, &mut vars);
+    vars.get_var("main".to_string(), &mut trsp);
+    lint::suppress(&file_content, &trsp.file, &mut trsp.warnings);
+    let (denied, warnings) = std::mem::take(&mut trsp.warnings)
+        .into_iter()
+        .partition(|warning| {
+            lint::lint(&warning.problem_type)
+                .is_some_and(|name| deny.iter().any(|lint| lint == name))
+        });
+    trsp.warnings = warnings;
+    trsp.problems.extend(denied);
+    for line in lspcom::warnings(&trsp.warnings) {
+        println!("{}", line)
+    }
+    for line in lspcom::summarize(&trsp.problems, error_limit) {
+        println!("{}", line)
+    }
+    trsp.problems.is_empty()
+}
+
+// The rust code of a whole program, with a rust main that exits with what the wyst one returns
+fn program(trsp: &mut Transpiler, code: String, vars: &mut Variables) -> String {
+    let mut transpiled_code = trsp.transpile(code.clone(), 0, vars);
+    lint::suppress(&code, &trsp.file, &mut trsp.warnings);
+    if trsp.coverage {
+        trsp.writer.use_runtime("wyst_coverage");
+    }
+    let hooks = trsp.panic_hooks(vars) + trsp.exit_hooks().as_str();
+    transpiled_code = trsp.writer.runtime_mods() + transpiled_code.as_str();
+    transpiled_code += "\nfn main() {";
+    transpiled_code += hooks.as_str();
+    transpiled_code += "let code = ";
+    transpiled_code += vars.get_var("main".to_string(), trsp).as_str();
+    transpiled_code += "();";
+    if trsp.coverage {
+        transpiled_code += trsp.coverage_report().as_str();
+    }
+    transpiled_code += "std::process::exit(code)}";
+    transpiled_code
+}
+
+/*Transpiles a file, or stdin for "-", and prints the program as one rust file. Diagnostics go
+to stderr so the output can be piped on*/
+fn transpile(file: &str, features: &package::Features, error_limit: Option<usize>) -> bool {
+    let mut code = String::new();
+    let read = if file == "-" {
+        std::io::stdin().read_to_string(&mut code).map(|_| ())
+    } else {
+        fs::read_to_string(file).map(|text| code = text)
+    };
+    if let Err(err) = read {
+        eprintln!("failed to read {}: {}", file, err);
+        return false;
+    }
+    let mut trsp = Transpiler {
+        file: if file == "-" { "stdin" } else { file }.to_string(),
+        ..Default::default()
+    };
+    trsp.writer.configure(features);
+    let transpiled_code = program(&mut trsp, code, &mut Variables::new());
+    for line in lspcom::warnings(&trsp.warnings) {
+        eprintln!("{}", line)
+    }
+    for line in lspcom::summarize(&trsp.problems, error_limit) {
+        eprintln!("{}", line)
+    }
+    if !trsp.problems.is_empty() {
+        return false;
+    }
+    let source = if file == "-" { "stdin" } else { file };
+    print!(
+        "{}{}",
+        provenance::Provenance::load().banner(source),
+        trsp.writer.amalgamate(&transpiled_code)
+    );
+    true
+}
+
+/*Transpiles main.wt and compiles it with the profile's rustc flags, or writes it out as one file*/
+fn build(
+    exe_name: &str,
+    profile: &manifest::Profile,
+    features: &package::Features,
+    coverage: bool,
+    amalgamation: bool,
+    runtime_dir: Option<String>,
+    error_limit: Option<usize>,
+) -> summary::Summary {
+    let mut summary = summary::Summary::default();
+    package::require_lock();
+    let toolchain = compile::load_toolchain();
+    let target = toolchain.host_triple().unwrap_or_default();
+    let hooks = manifest::Manifest::load().unwrap_or_default();
+    let start = Instant::now();
+    if Path::new("build").exists() {
+        fs::remove_dir_all("build").expect("err rm build");
+    }
+    fs::create_dir("build").expect("error making build");
+    // prebuild can generate the sources, so it goes before anything is read
+    if let Some(command) = hooks.get("build", "prebuild") {
+        if compile::run_hook("prebuild", command, &profile.name, &target, exe_name).is_err() {
+            std::process::exit(1);
+        }
+        summary.phase("prebuild", start);
+    }
+    let file_content = fs::read_to_string("main.wt").expect("Error reading file");
+    let start = Instant::now();
+    summary.lines = file_content.lines().count();
+    let mut trsp = Transpiler {
+        coverage,
+        ..Default::default()
+    };
+    trsp.writer.configure(features);
+    trsp.writer.runtime_dir = runtime_dir;
+    trsp.writer.trace = profile.trace;
+    trsp.writer.deterministic = profile.deterministic;
+    let mut vars = Variables::new();
+    let transpiled_code = program(&mut trsp, file_content.clone(), &mut vars);
+    summary.phase("transpile", start);
+    summary.files = 1 + trsp.writer.files.len();
+    summary.lines += trsp
+        .writer
+        .files
+        .iter()
+        .map(|module| module._code.lines().count())
+        .sum::<usize>();
+    summary.symbols = vars.vars.len();
+    summary.warnings = lspcom::warnings(&trsp.warnings).len();
+    summary.errors = trsp.problems.len();
+    for line in lspcom::warnings(&trsp.warnings) {
+        println!("{}", line)
+    }
+    for line in lspcom::summarize(&trsp.problems, error_limit) {
+        println!("{}", line)
+    }
+    if !trsp.problems.is_empty() {
+        return summary;
+    }
+    let start = Instant::now();
+    if amalgamation {
+        let provenance = provenance::Provenance::load();
+        let mut code = provenance.banner("main.wt");
+        code += trsp.writer.amalgamate(&transpiled_code).as_str();
+        compile::write_to_rust_file(&code, exe_name).expect("Error writing the amalgamation");
+        let output = if exe_name.ends_with(".rs") {
+            exe_name.to_string()
+        } else {
+            format!("{}.rs", exe_name)
+        };
+        let mut sources = vec![("main.wt", file_content.as_str())];
+        sources.extend(
+            trsp.writer
+                .files
+                .iter()
+                .map(|module| (module.file_ws.as_str(), module._code.as_str())),
+        );
+        if let Err(err) = provenance.sidecar(&output, &code, &sources) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        fs::remove_dir_all("build").expect("err rm build");
+        summary.phase("write", start);
+        return summary;
+    }
+    trsp.writer.write();
+
+    compile::write_to_rust_file(&transpiled_code, "build/main.rs")
+        .expect("Error writing to temporary Rust file");
+    summary.phase("write", start);
+    let start = Instant::now();
+    std::env::set_current_dir("build").expect("setDir err: ");
+    let mut rustc_args = profile.rustc_args();
+    rustc_args.extend(features.rustc_args());
+    // rustc's own errors have been printed, build/ is kept so they can be looked at
+    if compile::compile_to_executable(&toolchain, exe_name, &rustc_args).is_err() {
+        std::process::exit(1);
+    }
+    std::env::set_current_dir("..").expect("setDir0 err: ");
+    fs::rename(Path::new("build").join(exe_name).as_path(), exe_name).expect("RenameErrBuld: ");
+    summary.phase("rustc", start);
+    if let Some(command) = hooks.get("build", "postbuild") {
+        let start = Instant::now();
+        if compile::run_hook("postbuild", command, &profile.name, &target, exe_name).is_err() {
+            std::process::exit(1);
+        }
+        summary.phase("postbuild", start);
+    }
+    fs::remove_dir_all("build").expect("err rm build");
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process::Command};
+
+    /*The diagnostics of a program, then what it prints, stdout before stderr, and its exit code
+    when rustc compiles the generated code. A rustc error is reported with its code, like
+    error[E0381], so the expected file says which one came back*/
+    fn outcome(path: &Path) -> String {
+        let code = fs::read_to_string(path).expect("program");
+        let mut trsp = Transpiler {
+            file: format!(
+                "tests/programs/{}",
+                path.file_name().unwrap().to_string_lossy()
+            ),
+            ..Default::default()
+        };
+        let transpiled_code = program(&mut trsp, code, &mut Variables::new());
+        let mut out = String::new();
+        for line in lspcom::warnings(&trsp.warnings) {
+            out += format!("{}\n", line).as_str();
+        }
+        for line in lspcom::summarize(&trsp.problems, None) {
+            out += format!("{}\n", line).as_str();
+        }
+        if !trsp.problems.is_empty() {
+            return out;
+        }
+        let stem = path.file_stem().unwrap().to_string_lossy();
+        let dir = env::temp_dir().join(format!("wyst-{}-{}", std::process::id(), stem));
+        fs::create_dir_all(&dir).expect("temp dir");
+        fs::write(
+            dir.join("main.rs"),
+            trsp.writer.amalgamate(&transpiled_code),
+        )
+        .expect("main.rs");
+        let rustc = Command::new("rustc")
+            .arg(dir.join("main.rs"))
+            .args(["-C", "overflow-checks=on", "-o"])
+            .arg(dir.join("main"))
+            .output()
+            .expect("rustc");
+        if rustc.status.success() {
+            let run = Command::new(dir.join("main")).output().expect("program");
+            out += String::from_utf8_lossy(&run.stdout).as_ref();
+            out += String::from_utf8_lossy(&run.stderr).as_ref();
+            out += format!("exit {}\n", run.status.code().unwrap_or(-1)).as_str();
+        } else {
+            for line in String::from_utf8_lossy(&rustc.stderr).lines() {
+                if line.starts_with("error[") {
+                    out += format!("rustc {}\n", line).as_str();
+                }
+            }
+            out += "rustc failed\n";
+        }
+        let _ = fs::remove_dir_all(dir);
+        out
+    }
+
+    /*Every .wt file in tests/programs is transpiled, compiled and run, and has to give what the
+    .expected file next to it holds. WYST_BLESS=1 rewrites those files*/
+    #[test]
+    fn programs() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+        let mut paths: Vec<_> = fs::read_dir(dir)
+            .expect("tests/programs")
+            .map(|entry| entry.expect("tests/programs").path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "wt"))
+            .collect();
+        paths.sort();
+        assert!(!paths.is_empty());
+        let bless = env::var_os("WYST_BLESS").is_some();
+        let mut failures = Vec::new();
+        for path in paths {
+            let got = outcome(&path);
+            let expected_path = path.with_extension("expected");
+            if bless {
+                fs::write(&expected_path, &got).expect("program output");
+                continue;
+            }
+            let expected = fs::read_to_string(&expected_path).unwrap_or_default();
+            if got != expected {
+                failures.push(format!(
+                    "{}\nexpected:\n{}got:\n{}",
+                    path.display(),
+                    expected,
+                    got
+                ));
+            }
+        }
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}
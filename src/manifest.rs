@@ -0,0 +1,261 @@
+use std::{collections::HashMap, fs, path::Path};
+
+pub const MANIFEST: &str = "wyst.toml";
+
+// The parts of toml wyst.toml needs: [sections] of key = value, and # comments
+#[derive(Clone, Debug, Default)]
+pub struct Manifest {
+    pub sections: HashMap<String, HashMap<String, String>>,
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+impl Manifest {
+    pub fn parse(text: &str) -> Result<Manifest, String> {
+        let mut manifest = Manifest::default();
+        let mut section = String::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                section = line[1..line.len() - 1].trim().to_string();
+                manifest.sections.entry(section.clone()).or_default();
+                continue;
+            }
+            match line.split_once('=') {
+                Some((key, value)) => {
+                    let value = value.trim();
+                    let value = value
+                        .strip_prefix('"')
+                        .and_then(|value| value.strip_suffix('"'))
+                        .unwrap_or(value);
+                    manifest
+                        .sections
+                        .entry(section.clone())
+                        .or_default()
+                        .insert(key.trim().to_string(), value.to_string());
+                }
+                None => return Err(format!("{}:{}: expected `key = value`", MANIFEST, i + 1)),
+            }
+        }
+        Ok(manifest)
+    }
+
+    /*Reads wyst.toml from the project directory, a project without one gets the defaults*/
+    pub fn load() -> Result<Manifest, String> {
+        if !Path::new(MANIFEST).exists() {
+            return Ok(Manifest::default());
+        }
+        let text = fs::read_to_string(MANIFEST).map_err(|err| format!("{}: {}", MANIFEST, err))?;
+        Manifest::parse(&text)
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.sections
+            .get(section)?
+            .get(key)
+            .map(|value| value.as_str())
+    }
+
+    pub fn get_bool(&self, section: &str, key: &str) -> Result<Option<bool>, String> {
+        match self.get(section, key) {
+            Some("true") => Ok(Some(true)),
+            Some("false") => Ok(Some(false)),
+            Some(value) => Err(format!(
+                "{}: [{}] {} must be true or false, found '{}'",
+                MANIFEST, section, key, value
+            )),
+            None => Ok(None),
+        }
+    }
+
+    // An inline table, `foo = { git = "https://...", rev = "v1" }`
+    pub fn get_table(
+        &self,
+        section: &str,
+        key: &str,
+    ) -> Result<Option<HashMap<String, String>>, String> {
+        let value = match self.get(section, key) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let error = || {
+            format!(
+                "{}: [{}] {} must be a table like {{ key = \"value\" }}, found '{}'",
+                MANIFEST, section, key, value
+            )
+        };
+        let fields = value
+            .strip_prefix('{')
+            .and_then(|v| v.strip_suffix('}'))
+            .ok_or_else(error)?;
+        let mut table = HashMap::new();
+        for field in fields.split(',').filter(|field| !field.trim().is_empty()) {
+            let (name, value) = field.split_once('=').ok_or_else(error)?;
+            table.insert(
+                name.trim().to_string(),
+                value.trim().trim_matches('"').to_string(),
+            );
+        }
+        Ok(Some(table))
+    }
+
+    // A list of strings, `defines = ["A", "B"]`
+    pub fn get_list(&self, section: &str, key: &str) -> Result<Option<Vec<String>>, String> {
+        let value = match self.get(section, key) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let items = match value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            Some(items) => items,
+            None => {
+                return Err(format!(
+                    "{}: [{}] {} must be a list, found '{}'",
+                    MANIFEST, section, key, value
+                ))
+            }
+        };
+        Ok(Some(
+            items
+                .split(',')
+                .map(|item| item.trim().trim_matches('"').to_string())
+                .filter(|item| !item.is_empty())
+                .collect(),
+        ))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Profile {
+    pub name: String,
+    pub opt_level: String,
+    // Integer overflow traps in the generated rust, indexing is always bounds checked there
+    // and wyst pointers are references, so they can't be null
+    pub runtime_checks: bool,
+    // Keeps the line tables so panics and debuggers can point at the generated code
+    pub line_directives: bool,
+    // Keeps the trace! calls, without it they're left out along with their arguments
+    pub trace: bool,
+    // A fake clock and a fixed random seed, so the runs of a test suite can be compared
+    pub deterministic: bool,
+    // Passed on as --cfg, so `cb` blocks can test them with #[cfg(NAME)]
+    pub defines: Vec<String>,
+}
+
+impl Profile {
+    pub fn from_manifest(manifest: &Manifest, name: &str) -> Result<Profile, String> {
+        let section = format!("profile.{}", name);
+        let debug = name != "release";
+        let opt_level = manifest
+            .get(&section, "opt-level")
+            .unwrap_or(if debug { "0" } else { "3" })
+            .to_string();
+        if !["0", "1", "2", "3", "s", "z"].contains(&opt_level.as_str()) {
+            return Err(format!(
+                "{}: [{}] opt-level must be one of 0, 1, 2, 3, s, z, found '{}'",
+                MANIFEST, section, opt_level
+            ));
+        }
+        // Checked with the profile so a build stops on a bad [suffixes] too
+        suffixes(manifest)?;
+        Ok(Profile {
+            name: name.to_string(),
+            opt_level,
+            runtime_checks: manifest
+                .get_bool(&section, "runtime-checks")?
+                .unwrap_or(debug),
+            line_directives: manifest
+                .get_bool(&section, "line-directives")?
+                .unwrap_or(debug),
+            trace: manifest.get_bool(&section, "trace")?.unwrap_or(debug),
+            deterministic: manifest
+                .get_bool(&section, "deterministic")?
+                .unwrap_or(false),
+            defines: manifest.get_list(&section, "defines")?.unwrap_or_default(),
+        })
+    }
+
+    // The flags rustc gets when compiling the generated code
+    pub fn rustc_args(&self) -> Vec<String> {
+        let checks = if self.runtime_checks { "on" } else { "off" };
+        let mut args = vec![
+            "-C".to_string(),
+            format!("opt-level={}", self.opt_level),
+            "-C".to_string(),
+            format!("overflow-checks={}", checks),
+            "-C".to_string(),
+            format!("debug-assertions={}", checks),
+        ];
+        if self.line_directives {
+            args.push("-C".to_string());
+            args.push("debuginfo=line-tables-only".to_string());
+        }
+        for define in &self.defines {
+            args.push("--cfg".to_string());
+            args.push(define.clone());
+        }
+        args
+    }
+}
+
+// Suffixes every project has, sizes in bytes and durations in milliseconds
+const DEFAULT_SUFFIXES: [(&str, i64); 6] = [
+    ("kb", 1024),
+    ("mb", 1024 * 1024),
+    ("gb", 1024 * 1024 * 1024),
+    ("ms", 1),
+    ("s", 1000),
+    ("min", 60 * 1000),
+];
+
+/*The numeric literal suffixes, [suffixes] in wyst.toml adds to the defaults or replaces them*/
+pub fn suffixes(manifest: &Manifest) -> Result<HashMap<String, i64>, String> {
+    let mut suffixes: HashMap<String, i64> = DEFAULT_SUFFIXES
+        .iter()
+        .map(|(name, factor)| (name.to_string(), *factor))
+        .collect();
+    if let Some(section) = manifest.sections.get("suffixes") {
+        for (name, factor) in section {
+            match factor.parse::<i64>() {
+                Ok(factor) if factor > 0 => {
+                    suffixes.insert(name.clone(), factor);
+                }
+                _ => {
+                    return Err(format!(
+                        "{}: [suffixes] {} must be a positive integer, found '{}'",
+                        MANIFEST, name, factor
+                    ))
+                }
+            }
+        }
+    }
+    Ok(suffixes)
+}
+
+// The suffixes of a project without a manifest
+pub fn default_suffixes() -> HashMap<String, i64> {
+    suffixes(&Manifest::default()).unwrap()
+}
+
+// Loads a profile for a build, a broken manifest stops the build
+pub fn load_profile(name: &str) -> Profile {
+    match Manifest::load().and_then(|manifest| Profile::from_manifest(&manifest, name)) {
+        Ok(profile) => profile,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
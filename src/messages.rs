@@ -0,0 +1,300 @@
+use std::{fmt::Display, sync::OnceLock};
+
+// The locales the catalog has text for, in the order of each entry's translations
+pub const LOCALES: [&str; 2] = ["en", "es"];
+
+static LOCALE: OnceLock<usize> = OnceLock::new();
+
+/*Diagnostic text by message id. {0}, {1}, ... are filled in with the arguments, in order,
+so a translation can move them around*/
+const CATALOG: [(&str, [&str; 2]); 43] = [
+    (
+        "variable_not_found",
+        [
+            "Variable '{0}' doesn't exist{1}",
+            "La variable '{0}' no existe{1}",
+        ],
+    ),
+    (
+        "did_you_mean",
+        [", did you mean '{0}'?", ", ¿quisiste decir '{0}'?"],
+    ),
+    (
+        "missing_return",
+        [
+            "not all control paths return a value",
+            "no todos los caminos de ejecución devuelven un valor",
+        ],
+    ),
+    (
+        "type_syntax",
+        ["'{0}' isn't a type", "'{0}' no es un tipo"],
+    ),
+    (
+        "generic_arity",
+        [
+            "'{0}' takes {1} type arguments, found {2}",
+            "'{0}' recibe {1} argumentos de tipo, se encontraron {2}",
+        ],
+    ),
+    (
+        "const_value",
+        [
+            "the value of const '{0}' has to be an int known at compile time",
+            "el valor de la constante '{0}' tiene que ser un int conocido al compilar",
+        ],
+    ),
+    (
+        "impl_target",
+        [
+            "impl for '{0}', which isn't a struct{1}",
+            "impl para '{0}', que no es un struct{1}",
+        ],
+    ),
+    (
+        "sandboxed",
+        [
+            "{0} isn't available in the playground",
+            "{0} no está disponible en el playground",
+        ],
+    ),
+    (
+        "unreachable",
+        ["unreachable code", "código inalcanzable"],
+    ),
+    (
+        "index_out_of_bounds",
+        [
+            "index {0} is out of bounds for '{1}' of length {2}",
+            "el índice {0} está fuera de los límites de '{1}' de longitud {2}",
+        ],
+    ),
+    (
+        "misplaced_attribute",
+        [
+            "'@{0}' can't be used on {1}",
+            "'@{0}' no se puede usar en {1}",
+        ],
+    ),
+    (
+        "unknown_attribute",
+        [
+            "unknown attribute '@{0}'{1}",
+            "atributo desconocido '@{0}'{1}",
+        ],
+    ),
+    (
+        "packed_and_aligned",
+        [
+            "a struct can't be both @packed and @align",
+            "un struct no puede ser @packed y @align a la vez",
+        ],
+    ),
+    (
+        "align_value",
+        [
+            "@align takes a power of two number of bytes, found '{0}'",
+            "@align recibe un número de bytes que sea potencia de dos, se encontró '{0}'",
+        ],
+    ),
+    (
+        "unknown_derive",
+        [
+            "can't derive '{0}', expected one of: {1}",
+            "no se puede derivar '{0}', se esperaba uno de: {1}",
+        ],
+    ),
+    (
+        "import_not_found",
+        [
+            "failed to import {0}: Not found",
+            "no se pudo importar {0}: no encontrado",
+        ],
+    ),
+    (
+        "header_syntax",
+        ["failed to deserialize", "no se pudo deserializar"],
+    ),
+    (
+        "intrinsic_arity",
+        [
+            "'{0}' takes {1} argument(s) but {2} were given",
+            "'{0}' recibe {1} argumento(s) pero se dieron {2}",
+        ],
+    ),
+    ("arity_range", ["{0} to {1}", "{0} a {1}"]),
+    ("arity_at_least", ["at least {0}", "al menos {0}"]),
+    (
+        "else_without_if",
+        [
+            "'{0}' has no if before it",
+            "'{0}' no tiene un if antes",
+        ],
+    ),
+    (
+        "for_header",
+        [
+            "a for loop takes (init; condition; step) or (name in values)",
+            "un bucle for recibe (inicio; condición; paso) o (nombre in valores)",
+        ],
+    ),
+    (
+        "expected_after",
+        [
+            "expected {0} after '{1}'",
+            "se esperaba {0} después de '{1}'",
+        ],
+    ),
+    (
+        "bit_operand",
+        [
+            "'{0}' works on integers, but '{1}' is {2}",
+            "'{0}' funciona con enteros, pero '{1}' es {2}",
+        ],
+    ),
+    (
+        "unknown_method",
+        [
+            "{0} has no method '{1}', expected one of: {2}",
+            "{0} no tiene el método '{1}', se esperaba uno de: {2}",
+        ],
+    ),
+    (
+        "method_arity",
+        [
+            "'{0}.{1}' takes {2} argument(s) but {3} were given",
+            "'{0}.{1}' recibe {2} argumento(s) pero se dieron {3}",
+        ],
+    ),
+    (
+        "unknown_suffix",
+        [
+            "unknown suffix '{0}' on '{1}', expected one of: {2}",
+            "sufijo desconocido '{0}' en '{1}', se esperaba uno de: {2}",
+        ],
+    ),
+    (
+        "suffix_overflow",
+        ["'{0}' doesn't fit in an int", "'{0}' no cabe en un int"],
+    ),
+    (
+        "spawn_target",
+        [
+            "spawn needs the name of a function that takes nothing and returns int, found '{0}'",
+            "spawn necesita el nombre de una función sin parámetros que devuelva int, se encontró '{0}'",
+        ],
+    ),
+    (
+        "embed_literal",
+        [
+            "'embed!' takes a string literal path",
+            "'embed!' recibe una ruta como literal de cadena",
+        ],
+    ),
+    (
+        "embed_failed",
+        ["failed to embed {0}: {1}", "no se pudo incrustar {0}: {1}"],
+    ),
+    (
+        "env_literal",
+        [
+            "'env!' takes string literals",
+            "'env!' recibe literales de cadena",
+        ],
+    ),
+    (
+        "trace_literal",
+        [
+            "'trace!' takes a string literal to format first",
+            "'trace!' recibe primero un literal de cadena para formatear",
+        ],
+    ),
+    (
+        "env_unset",
+        [
+            "environment variable '{0}' isn't set and has no default",
+            "la variable de entorno '{0}' no está definida y no tiene valor por defecto",
+        ],
+    ),
+    (
+        "buffer_length",
+        [
+            "a buffer takes a type and a constant length, found '{0}'",
+            "un buffer recibe un tipo y una longitud constante, se encontró '{0}'",
+        ],
+    ),
+    (
+        "unknown_feature",
+        [
+            "unknown feature '{0}'{1}",
+            "característica desconocida '{0}'{1}",
+        ],
+    ),
+    (
+        "feature_gated",
+        [
+            "'{0}' is experimental, add `#pragma feature({1})` to the top of the file to use it",
+            "'{0}' es experimental, añade `#pragma feature({1})` al principio del archivo para usarlo",
+        ],
+    ),
+    (
+        "unused_suppression",
+        [
+            "no '{0}' warning is silenced by this wyst-ignore{1}",
+            "este wyst-ignore no silencia ninguna advertencia '{0}'{1}",
+        ],
+    ),
+    (
+        "deprecated",
+        ["'{0}' is deprecated{1}", "'{0}' está obsoleto{1}"],
+    ),
+    ("deprecated_since", [" since {0}", " desde {0}"]),
+    (
+        "similar_suppressed",
+        [
+            "  ({0} similar error(s) suppressed)",
+            "  ({0} error(es) similar(es) omitido(s))",
+        ],
+    ),
+    (
+        "more_not_shown",
+        [
+            "{0} more error(s) not shown, raise --error-limit to see them",
+            "{0} error(es) más sin mostrar, aumenta --error-limit para verlos",
+        ],
+    ),
+    (
+        "explain_hint",
+        [
+            "Run `wyst explain <code>` for more about an error",
+            "Ejecuta `wyst explain <código>` para saber más sobre un error",
+        ],
+    ),
+];
+
+// Picks the language of the diagnostics, before anything is transpiled
+pub fn set_locale(locale: &str) -> Result<(), String> {
+    match LOCALES.iter().position(|known| *known == locale) {
+        Some(index) => {
+            let _ = LOCALE.set(index);
+            Ok(())
+        }
+        None => Err(format!(
+            "unknown locale '{}', expected one of: {}",
+            locale,
+            LOCALES.join(", ")
+        )),
+    }
+}
+
+pub fn message(id: &str, args: &[&dyn Display]) -> String {
+    let locale = *LOCALE.get().unwrap_or(&0);
+    let mut text = match CATALOG.iter().find(|(known, _)| *known == id) {
+        Some((_, translations)) => translations[locale].to_string(),
+        None => id.to_string(),
+    };
+    for (i, arg) in args.iter().enumerate() {
+        text = text.replace(&format!("{{{}}}", i), &arg.to_string());
+    }
+    text
+}
@@ -0,0 +1,115 @@
+use crate::{
+    lexer::{inner_state, lex, LexerState, Token},
+    parser::{AstType, Parser},
+    variable::Variables,
+};
+use lsp_types::{DocumentSymbol, Position, Range, SymbolKind};
+
+fn position(state: LexerState) -> Position {
+    Position::new(state.line as u32 - 1, state.column as u32)
+}
+
+// Just past the closing bracket of a group, the lexer counts every character as one column
+fn group_end(group: &Token) -> LexerState {
+    let mut state = inner_state(group);
+    for c in group.value.chars() {
+        if c == '\n' {
+            state.line += 1;
+            state.column = 0;
+        } else {
+            state.column += 1;
+        }
+    }
+    state.column += 1;
+    state
+}
+
+fn start(token: &Token) -> LexerState {
+    LexerState {
+        line: token.line,
+        column: token.column,
+    }
+}
+
+#[allow(deprecated)]
+fn symbol(
+    name: String,
+    kind: SymbolKind,
+    range: (LexerState, LexerState),
+    name_token: &Token,
+    children: Vec<DocumentSymbol>,
+) -> DocumentSymbol {
+    let name_end = LexerState {
+        line: name_token.line,
+        column: name_token.column + name_token.value.chars().count(),
+    };
+    DocumentSymbol {
+        name,
+        detail: None,
+        kind,
+        tags: None,
+        deprecated: None,
+        range: Range::new(position(range.0), position(range.1)),
+        selection_range: Range::new(position(start(name_token)), position(name_end)),
+        children: if children.is_empty() {
+            None
+        } else {
+            Some(children)
+        },
+    }
+}
+
+fn symbols(code: &str, state: LexerState) -> Vec<DocumentSymbol> {
+    let Ok(tokens) = lex(code, false, state) else {
+        return vec![];
+    };
+    let asts = Parser::new(tokens.clone(), Variables::new()).parse_nodes();
+    let mut out = Vec::new();
+    for ast in asts {
+        let (kind, name, body) = match ast.ast_type {
+            AstType::Namespace => (SymbolKind::NAMESPACE, 0, 1),
+            AstType::StructDeceleration => (SymbolKind::STRUCT, 0, 1),
+            AstType::Impl => (SymbolKind::OBJECT, 0, 1),
+            AstType::FunctionDeceleration | AstType::VoidFunctionDeceleration => {
+                (SymbolKind::FUNCTION, 1, 3)
+            }
+            _ => continue,
+        };
+        let (name_token, body) = (&ast.tokens[name], &ast.tokens[body]);
+        // The parser leaves out the struct, namespace and impl keywords, the range starts at them
+        let mut first = start(&ast.tokens[0]);
+        if name == 0 {
+            if let Some(at) = tokens
+                .iter()
+                .position(|token| token.line == first.line && token.column == first.column)
+                .filter(|at| *at > 0)
+            {
+                first = start(&tokens[at - 1]);
+            }
+        }
+        let children = match ast.ast_type {
+            AstType::Namespace | AstType::Impl => symbols(&body.value, inner_state(body)),
+            _ => vec![],
+        };
+        let label = if ast.ast_type == AstType::Impl {
+            format!("impl {}", name_token.value)
+        } else {
+            name_token.value.clone()
+        };
+        out.push(symbol(
+            label,
+            kind,
+            (first, group_end(body)),
+            name_token,
+            children,
+        ));
+    }
+    out
+}
+
+/*The declarations of a file nested the way they're written, namespaces and impl blocks hold
+what's declared in them. Each one has the range of all of its code and of its name, so an
+editor can tell which declarations the cursor is inside of*/
+pub fn outline(code: &str) -> Vec<DocumentSymbol> {
+    symbols(code, LexerState { line: 1, column: 0 })
+}
@@ -0,0 +1,444 @@
+use std::{collections::HashMap, ffi::OsStr, fs, path::Path, process::Command};
+
+use crate::manifest::{suffixes, Manifest, MANIFEST};
+
+// Where `wyst fetch` puts git dependencies
+pub const DEPS_DIR: &str = "wyst_deps";
+pub const LOCKFILE: &str = "wyst.lock";
+// Where `wyst vendor` copies dependencies, std/ holds the copy of the standard library
+pub const VENDOR_DIR: &str = "vendor";
+pub const VENDOR_STD: &str = "vendor/std";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    Git { url: String, rev: Option<String> },
+    Path(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct Dependency {
+    pub name: String,
+    pub source: Source,
+    // Only used when a feature turns it on with "dep:name"
+    pub optional: bool,
+}
+
+impl Dependency {
+    // Where the dependency lives when it isn't vendored
+    pub fn root(&self) -> String {
+        match &self.source {
+            Source::Git { .. } => format!("{}/{}", DEPS_DIR, self.name),
+            Source::Path(path) => path.clone(),
+        }
+    }
+
+    fn vendored(&self) -> Option<String> {
+        let dir = format!("{}/{}", VENDOR_DIR, self.name);
+        Some(dir).filter(|dir| Path::new(dir).is_dir())
+    }
+
+    // The directory includes are looked up in, the vendored copy wins when there is one
+    pub fn include_root(&self) -> String {
+        self.vendored().unwrap_or_else(|| self.root())
+    }
+}
+
+/*Reads [dependencies], each one is `name = { git = "url", rev = "tag" }` or `name = { path = "dir" }`*/
+pub fn dependencies(manifest: &Manifest) -> Result<Vec<Dependency>, String> {
+    let mut names: Vec<&String> = match manifest.sections.get("dependencies") {
+        Some(section) => section.keys().collect(),
+        None => return Ok(vec![]),
+    };
+    names.sort();
+    let mut deps = Vec::new();
+    for name in names {
+        let table = manifest
+            .get_table("dependencies", name)?
+            .unwrap_or_default();
+        let source = match (table.get("git"), table.get("path")) {
+            (Some(url), None) => Source::Git {
+                url: url.clone(),
+                rev: table.get("rev").cloned(),
+            },
+            (None, Some(path)) => Source::Path(path.clone()),
+            _ => {
+                return Err(format!(
+                    "{}: dependency '{}' needs either git or path",
+                    MANIFEST, name
+                ))
+            }
+        };
+        deps.push(Dependency {
+            name: name.clone(),
+            source,
+            optional: table.get("optional").map(|value| value.as_str()) == Some("true"),
+        });
+    }
+    Ok(deps)
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Features {
+    pub enabled: Vec<String>,
+    pub defines: Vec<String>,
+    // The include roots of the dependencies in use
+    pub roots: Vec<String>,
+    // The numeric literal suffixes from [suffixes]
+    pub suffixes: HashMap<String, i64>,
+}
+
+impl Features {
+    // Every enabled feature is visible as feature="name", its defines as plain cfgs
+    pub fn rustc_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        for feature in &self.enabled {
+            args.push("--cfg".to_string());
+            args.push(format!("feature=\"{}\"", feature));
+        }
+        for define in &self.defines {
+            args.push("--cfg".to_string());
+            args.push(define.clone());
+        }
+        args
+    }
+}
+
+/*Turns on the requested features and the default ones. A feature lists defines, other features,
+and "dep:name" for the optional dependencies it needs*/
+pub fn features(manifest: &Manifest, requested: &[String]) -> Result<Features, String> {
+    let deps = dependencies(manifest)?;
+    let mut features = Features::default();
+    let mut pending: Vec<String> = requested.to_vec();
+    if manifest.get("features", "default").is_some() {
+        pending.push("default".to_string());
+    }
+    let mut optional: Vec<String> = Vec::new();
+    while let Some(feature) = pending.pop() {
+        if features.enabled.contains(&feature) {
+            continue;
+        }
+        let items = match manifest.get_list("features", &feature)? {
+            Some(items) => items,
+            None => return Err(format!("{}: unknown feature '{}'", MANIFEST, feature)),
+        };
+        for item in items {
+            if let Some(dep) = item.strip_prefix("dep:") {
+                if !deps.iter().any(|d| d.name == dep && d.optional) {
+                    return Err(format!(
+                        "{}: feature '{}' needs '{}', which isn't an optional dependency",
+                        MANIFEST, feature, dep
+                    ));
+                }
+                optional.push(dep.to_string());
+            } else if manifest.get("features", &item).is_some() {
+                pending.push(item);
+            } else if !features.defines.contains(&item) {
+                features.defines.push(item);
+            }
+        }
+        features.enabled.push(feature);
+    }
+    features.enabled.sort();
+    features.roots = deps
+        .iter()
+        .filter(|dep| !dep.optional || optional.contains(&dep.name))
+        .map(|dep| dep.include_root())
+        .collect();
+    features.suffixes = suffixes(manifest)?;
+    Ok(features)
+}
+
+// The features for a build, a broken manifest or unknown feature stops it
+pub fn load_features(requested: &[String]) -> Features {
+    match Manifest::load().and_then(|manifest| features(&manifest, requested)) {
+        Ok(features) => features,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+// The dependency roots with the default features, used when resolving includes
+pub fn roots() -> Vec<String> {
+    match Manifest::load().and_then(|manifest| features(&manifest, &[])) {
+        Ok(features) => features.roots,
+        // Broken manifests are reported by the commands that need them
+        Err(_) => vec![],
+    }
+}
+
+fn git(args: &[&str], dir: &str) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|err| format!("failed to run git: {}", err))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed:\n{}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// What wyst.lock records for a dependency, a change in wyst.toml changes it
+fn source_id(dep: &Dependency) -> String {
+    match &dep.source {
+        Source::Git {
+            url,
+            rev: Some(rev),
+        } => format!("git+{}#{}", url, rev),
+        Source::Git { url, rev: None } => format!("git+{}", url),
+        Source::Path(path) => format!("path+{}", path),
+    }
+}
+
+// FNV-1a over the paths and contents of the files, it has to stay the same between wyst versions
+fn hash_dir(dir: &Path, hash: &mut u64) -> Result<(), String> {
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .map_err(|err| format!("{}: {}", dir.display(), err))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.file_name() != Some(OsStr::new(".git")))
+        .collect();
+    entries.sort();
+    for path in entries {
+        let name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let mut bytes = name.into_bytes();
+        if path.is_dir() {
+            hash_bytes(&bytes, hash);
+            hash_dir(&path, hash)?;
+            continue;
+        }
+        bytes.extend(fs::read(&path).map_err(|err| format!("{}: {}", path.display(), err))?);
+        hash_bytes(&bytes, hash);
+    }
+    Ok(())
+}
+
+fn hash_bytes(bytes: &[u8], hash: &mut u64) {
+    for byte in bytes {
+        *hash ^= *byte as u64;
+        *hash = hash.wrapping_mul(0x100000001b3);
+    }
+}
+
+pub fn hash(bytes: &[u8]) -> String {
+    let mut hash = 0xcbf29ce484222325;
+    hash_bytes(bytes, &mut hash);
+    format!("{:016x}", hash)
+}
+
+fn content_hash(root: &str) -> Result<String, String> {
+    let mut hash = 0xcbf29ce484222325;
+    hash_dir(Path::new(root), &mut hash)?;
+    Ok(format!("{:016x}", hash))
+}
+
+fn read_lock() -> Result<Option<Manifest>, String> {
+    if !Path::new(LOCKFILE).exists() {
+        return Ok(None);
+    }
+    let text = fs::read_to_string(LOCKFILE).map_err(|err| format!("{}: {}", LOCKFILE, err))?;
+    Manifest::parse(&text).map(Some)
+}
+
+/*Clones git dependencies into wyst_deps/ and pins each one in wyst.lock.
+Dependencies that are already locked are checked out at their locked revision unless update is set*/
+pub fn fetch(update: bool) -> Result<(), String> {
+    let deps = dependencies(&Manifest::load()?)?;
+    let lock = if update { None } else { read_lock()? };
+    let mut out = String::from("# Generated by wyst, run `wyst update` to change it\n");
+    for dep in &deps {
+        let source = source_id(dep);
+        out += format!("\n[{}]\nsource = \"{}\"\n", dep.name, source).as_str();
+        let Source::Git { url, rev } = &dep.source else {
+            if !Path::new(&dep.root()).is_dir() {
+                return Err(format!(
+                    "dependency '{}': {} is not a directory",
+                    dep.name,
+                    dep.root()
+                ));
+            }
+            // A path has no revision, its files are all there is to pin
+            out += format!("hash = \"{}\"\n", content_hash(&dep.include_root())?).as_str();
+            continue;
+        };
+        let root = dep.root();
+        if !Path::new(&root).exists() {
+            fs::create_dir_all(DEPS_DIR).map_err(|err| err.to_string())?;
+            println!("Fetching {} from {}", dep.name, url);
+            // The url and name come from wyst.toml, neither may be read as an option
+            git(&["clone", "--quiet", "--", url, &dep.name], DEPS_DIR)?;
+        } else if update {
+            git(&["fetch", "--quiet"], &root)?;
+        }
+        let locked = lock
+            .as_ref()
+            .filter(|lock| lock.get(&dep.name, "source") == Some(source.as_str()))
+            .and_then(|lock| lock.get(&dep.name, "rev"));
+        // Nor may the rev, it's resolved to a commit first since checkout can't be told so
+        let resolve = |target: &str| {
+            git(
+                &[
+                    "rev-parse",
+                    "--verify",
+                    "--end-of-options",
+                    &format!("{}^{{commit}}", target),
+                ],
+                &root,
+            )
+        };
+        let commit = match (locked, rev) {
+            (Some(commit), _) => resolve(commit)?,
+            /*A branch is read from origin, the local one was made by the clone and fetch doesn't
+            move it. Tags and commits aren't there*/
+            (None, Some(rev)) => resolve(&format!("origin/{}", rev)).or_else(|_| resolve(rev))?,
+            (None, None) => resolve("origin/HEAD")?,
+        };
+        git(&["checkout", "--quiet", "--detach", &commit, "--"], &root)?;
+        out += format!(
+            "rev = \"{}\"\nhash = \"{}\"\n",
+            commit,
+            content_hash(&root)?
+        )
+        .as_str();
+    }
+    fs::write(LOCKFILE, out).map_err(|err| format!("{}: {}", LOCKFILE, err))
+}
+
+/*Checks that wyst.lock matches wyst.toml and that the fetched dependencies are the locked ones*/
+pub fn verify() -> Result<(), String> {
+    let deps = dependencies(&Manifest::load()?)?;
+    let lock = match read_lock()? {
+        Some(lock) => lock,
+        None if deps.is_empty() => return Ok(()),
+        None => return Err(format!("{} is missing, run `wyst fetch`", LOCKFILE)),
+    };
+    for name in lock.sections.keys() {
+        if !name.is_empty() && !deps.iter().any(|dep| &dep.name == name) {
+            return Err(format!(
+                "'{}' is in {} but not in {}, run `wyst update`",
+                name, LOCKFILE, MANIFEST
+            ));
+        }
+    }
+    for dep in &deps {
+        match lock.get(&dep.name, "source") {
+            None => {
+                return Err(format!(
+                    "'{}' is in {} but not in {}, run `wyst update`",
+                    dep.name, MANIFEST, LOCKFILE
+                ))
+            }
+            Some(source) if source != source_id(dep) => {
+                return Err(format!(
+                    "'{}' is locked to {} but {} asks for {}, run `wyst update`",
+                    dep.name,
+                    source,
+                    MANIFEST,
+                    source_id(dep)
+                ))
+            }
+            _ => {}
+        }
+        if let Source::Path(_) = dep.source {
+            let root = dep.include_root();
+            if lock.get(&dep.name, "hash") != Some(content_hash(&root)?.as_str()) {
+                return Err(format!(
+                    "the files of '{}' in {} changed since it was locked, run `wyst update`",
+                    dep.name, root
+                ));
+            }
+        }
+        if let Source::Git { .. } = dep.source {
+            let root = dep.include_root();
+            if !Path::new(&root).exists() {
+                return Err(format!(
+                    "'{}' hasn't been fetched, run `wyst fetch`",
+                    dep.name
+                ));
+            }
+            // Vendored copies have no git history, their hash is all there is to check
+            let commit = match dep.vendored() {
+                Some(_) => None,
+                None => Some(git(&["rev-parse", "HEAD"], &root)?),
+            };
+            if commit.is_some() && lock.get(&dep.name, "rev") != commit.as_deref() {
+                return Err(format!(
+                    "'{}' is at {} but {} pins {}, run `wyst fetch`",
+                    dep.name,
+                    commit.unwrap_or_default(),
+                    LOCKFILE,
+                    lock.get(&dep.name, "rev").unwrap_or("nothing")
+                ));
+            }
+            if lock.get(&dep.name, "hash") != Some(content_hash(&root)?.as_str()) {
+                return Err(format!(
+                    "the files of '{}' in {} changed since it was locked",
+                    dep.name, root
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<(), String> {
+    fs::create_dir_all(to).map_err(|err| format!("{}: {}", to.display(), err))?;
+    let entries = fs::read_dir(from).map_err(|err| format!("{}: {}", from.display(), err))?;
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if path.file_name() == Some(OsStr::new(".git")) {
+            continue;
+        }
+        let target = to.join(entry.file_name());
+        if path.is_dir() {
+            copy_dir(&path, &target)?;
+        } else {
+            fs::copy(&path, &target).map_err(|err| format!("{}: {}", path.display(), err))?;
+        }
+    }
+    Ok(())
+}
+
+/*Copies every locked dependency and the standard library into vendor/, includes resolve there from then on*/
+pub fn vendor(stdlib: &Path) -> Result<(), String> {
+    verify()?;
+    let deps = dependencies(&Manifest::load()?)?;
+    let staging = format!("{}.tmp", VENDOR_DIR);
+    if Path::new(&staging).exists() {
+        fs::remove_dir_all(&staging).map_err(|err| err.to_string())?;
+    }
+    // Copied from the current sources, which may be the old vendored ones
+    for dep in &deps {
+        println!("Vendoring {}", dep.name);
+        copy_dir(
+            Path::new(&dep.include_root()),
+            &Path::new(&staging).join(&dep.name),
+        )?;
+    }
+    if stdlib.is_dir() {
+        println!("Vendoring the standard library");
+        copy_dir(stdlib, &Path::new(&staging).join("std"))?;
+    }
+    if Path::new(VENDOR_DIR).exists() {
+        fs::remove_dir_all(VENDOR_DIR).map_err(|err| err.to_string())?;
+    }
+    fs::rename(&staging, VENDOR_DIR).map_err(|err| err.to_string())
+}
+
+// Builds stop when the dependencies aren't the locked ones
+pub fn require_lock() {
+    if let Err(err) = verify() {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
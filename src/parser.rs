@@ -1,10 +1,14 @@
 use crate::{
-    lexer::{LexerState, Token, TokenType},
-    variable::Variables,
+    analysis::{buffer_type, const_value, expand_suffixes},
+    intrinsic::{split_args, INTRINSICS},
+    lexer::{advance, inner_state, lex, map_type, LexerState, Token, TokenType, RUST_SUFFIXES},
+    messages::message,
+    types::TypeNode,
+    variable::{parse_fields, parse_params, Variables},
 };
 use once_cell::sync::Lazy;
 use regex::Regex;
-use std::fmt;
+use std::{collections::HashMap, fmt};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum AstType {
@@ -18,14 +22,41 @@ pub enum AstType {
     VariableDeceleration,
     PointerDeceleration,
     MutVariableDeceleration,
-    State3,
-    State2,
+    // The type, the name and a Number token with the value, placed where the expression was
+    ConstDeceleration,
+    /*The element type, the Square size and the name, for both `int[3] xs` and `int xs[3]`. With
+    `int xs[] = {...}` the size is the number of elements in the literal*/
+    ArrayDeceleration,
+    // The Curly elements of the `{1, 2, 3}` an array is declared with
+    ArrayLiteral,
+    // The Square index after the name of an array
+    Index,
+    /*The keyword, the header and the Curly body. The header of `for (init; condition; step)` is
+    three Round tokens, the one of `for (name in values)` the Identifier and a Round token*/
+    For,
+    // The keyword, the Round condition and the Curly body
+    While,
+    // The keyword, the Round condition and the Curly body
+    If,
+    ElseIf,
+    // else and its Curly body
+    Else,
     Include,
     IncludeLocal,
+    Pragma,
     CodeBlock,
     Json,
     Impl,
     StaticExecution,
+    Attribute,
+    Intrinsic,
+    MethodCall,
+    // type, the name and the aliased type as rust
+    TypeAlias,
+    // return and the tokens of the returned expression, which are parsed into its children
+    Return,
+    // A token the parser couldn't make sense of, it holds that token
+    Error,
     Other,
 }
 
@@ -36,12 +67,124 @@ pub fn is_decl(ast: &Ast) -> bool {
         || ast.ast_type == AstType::VariableDeceleration
         || ast.ast_type == AstType::PointerDeceleration
         || ast.ast_type == AstType::MutVariableDeceleration
+        || ast.ast_type == AstType::ArrayDeceleration
+        || ast.ast_type == AstType::ConstDeceleration
         || ast.ast_type == AstType::StructDeceleration
 }
 
+/*Splits the header of a for loop into its parts, None when it's neither `init; condition; step`
+nor `name in values`. A part is a Round token placed on the bracket, semicolon or `in` before it,
+the way a group token is placed on its bracket, so inner_state gives where its text starts*/
+fn for_header(round: &Token) -> Option<Vec<Token>> {
+    let start = inner_state(round);
+    let part = |at: LexerState, value: &str| Token {
+        token_type: TokenType::Round,
+        value: value.to_string(),
+        line: at.line,
+        column: at.column.saturating_sub(1),
+    };
+    let mut parts = Vec::new();
+    let mut from = 0;
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    for (at, c) in round.value.char_indices() {
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' => quote = Some(c),
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            ';' if depth == 0 => {
+                parts.push(part(
+                    advance(start, &round.value[..from]),
+                    &round.value[from..at],
+                ));
+                from = at + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(part(
+        advance(start, &round.value[..from]),
+        &round.value[from..],
+    ));
+    if parts.len() == 3 {
+        return Some(parts);
+    }
+    static FOR_IN: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(\s*)([_a-zA-Z]\w*)\s+in\s").unwrap());
+    let caps = FOR_IN.captures(&round.value).filter(|_| parts.len() == 1)?;
+    let at = advance(start, &caps[1]);
+    // The values start right after `in`, so the Round token is placed on the last letter of `in`
+    let in_end = caps.get(0).unwrap().end() - 1;
+    Some(vec![
+        Token {
+            token_type: TokenType::Identifier,
+            value: caps[2].to_string(),
+            line: at.line,
+            column: at.column,
+        },
+        part(
+            advance(start, &round.value[..in_end]),
+            &round.value[in_end..],
+        ),
+    ])
+}
+
+// Whether these nodes end in `type name[size] =`, so a Curly after them holds the elements
+fn follows_array(asts: &[Ast]) -> bool {
+    match asts {
+        [.., decl, assign] => {
+            decl.ast_type == AstType::ArrayDeceleration
+                && assign.tokens.len() == 1
+                && assign.tokens[0].token_type == TokenType::Assign
+        }
+        _ => false,
+    }
+}
+
+// The length in the Square of an array declaration, consts in it are folded
+fn array_size(square: &Token, variables: &mut Variables) -> Option<usize> {
+    let tokens = lex(&square.value, false, inner_state(square)).ok()?;
+    usize::try_from(const_value(&tokens, variables)?).ok()
+}
+
+// Whether these nodes end in the name of an array, so a Square after them is an index
+fn indexes_array(asts: &[Ast], variables: &mut Variables) -> bool {
+    match asts.last() {
+        Some(ast) if ast.ast_type == AstType::Other && ast.tokens.len() == 1 => variables
+            .get_mut(ast.tokens[0].value.clone())
+            .is_some_and(|var| var.element.is_some()),
+        _ => false,
+    }
+}
+
+// Whether an else or else if can go after these nodes, only comments can be between it and the if
+fn continues_if(asts: &[Ast]) -> bool {
+    asts.iter()
+        .rev()
+        .find(|ast| {
+            ast.tokens
+                .first()
+                .is_none_or(|token| token.token_type != TokenType::Comment)
+        })
+        .is_some_and(|ast| ast.ast_type == AstType::If || ast.ast_type == AstType::ElseIf)
+}
+
+#[derive(PartialEq)]
 pub struct Ast {
     pub tokens: Vec<Token>,
     pub ast_type: AstType,
+    // The parsed contents of the body, only filled in by Parser::parse_tree
+    pub children: Vec<Ast>,
 }
 
 impl fmt::Display for Ast {
@@ -72,6 +215,13 @@ impl fmt::Debug for Ast {
     }
 }
 
+// Why a node became an AstType::Error, reported as a SyntaxError by the transpiler
+#[derive(Clone, Debug)]
+pub struct ParseError {
+    pub message: String,
+    pub state: LexerState,
+}
+
 pub struct Parser {
     pub tokens: Vec<Token>,
     pub index: u32,
@@ -79,6 +229,10 @@ pub struct Parser {
     pub include_regex_local: Lazy<Regex>,
     pub variables: Variables,
     pub json: bool,
+    // What parse_nodes couldn't parse, parse hands them back
+    pub errors: Vec<ParseError>,
+    // The literal suffixes of the project, so a const can be declared as 2s or 4kb
+    pub suffixes: HashMap<String, i64>,
 }
 
 impl Parser {
@@ -90,30 +244,153 @@ impl Parser {
             include_regex_local: Lazy::new(|| Regex::new(r#"^(#include *)"(.*?)""#).unwrap()),
             variables: variables,
             json: false,
+            errors: Vec::new(),
+            suffixes: HashMap::new(),
+        }
+    }
+    /*Parses like parse, then parses the body of every node into its children, all the way down.
+    The body is the Curly token of the node, or its Round one when it has none, and the children
+    keep their positions in the file. A body that doesn't lex is left without children, and the
+    children of a Return are its expression*/
+    pub fn parse_tree(&mut self) -> Vec<Ast> {
+        let mut asts = self.parse_nodes();
+        for ast in asts.iter_mut() {
+            if ast.ast_type == AstType::Return {
+                let tokens = ast.tokens[1..].to_vec();
+                ast.children = Parser::new(tokens, self.variables.clone()).parse_tree();
+                continue;
+            }
+            let body = ast
+                .tokens
+                .iter()
+                .find(|token| token.token_type == TokenType::Curly)
+                .or_else(|| {
+                    ast.tokens
+                        .iter()
+                        .find(|token| token.token_type == TokenType::Round)
+                });
+            if let Some(body) = body {
+                if let Ok(tokens) = lex(body.value.as_str(), false, inner_state(body)) {
+                    ast.children = Parser::new(tokens, self.variables.clone()).parse_tree();
+                }
+            }
+        }
+        asts
+    }
+    /*Parses the tokens, or gives the errors of every node that didn't parse. The parser goes on
+    after an error, so a file gets all of them at once*/
+    pub fn parse(&mut self) -> Result<Vec<Ast>, Vec<ParseError>> {
+        let asts = self.parse_nodes();
+        if self.errors.is_empty() {
+            Ok(asts)
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    // Makes the node an error at token, for when something else had to come after it
+    fn error(ast_res: &mut Ast, errors: &mut Vec<ParseError>, token: &Token, expected: &str) {
+        ast_res.ast_type = AstType::Error;
+        ast_res.tokens = vec![token.clone()];
+        errors.push(ParseError {
+            message: message("expected_after", &[&expected, &token.value]),
+            state: LexerState {
+                line: token.line,
+                column: token.column,
+            },
+        });
+    }
+
+    // How many elements the `= {...}` starting at the token at has, None when it isn't one
+    fn literal_len(&self, at: usize) -> Option<usize> {
+        let [assign, elements] = self.tokens.get(at..at + 2)? else {
+            return None;
+        };
+        if assign.token_type != TokenType::Assign || elements.token_type != TokenType::Curly {
+            return None;
+        }
+        // A trailing comma leaves an empty element
+        let elements = split_args(&elements.value);
+        Some(elements.iter().filter(|e| !e.is_empty()).count())
+    }
+
+    /*Registers the function whose name is the token at, start is where its declaration begins.
+    The return type is rust already*/
+    fn declare_func(&mut self, start: usize, at: usize, return_type: String) {
+        let mut desc = String::new();
+        if start > 0 && self.tokens[start - 1].token_type == TokenType::Comment {
+            desc = self.tokens[start - 1].value.clone()
+        }
+        self.variables.new_func(
+            self.tokens[at].value.clone(),
+            LexerState {
+                line: self.tokens[at].line,
+                column: self.tokens[at].column,
+            },
+            desc,
+        );
+        // The return type and the parameters, spawn checks them
+        if let Some(var) = self.variables.get_mut(self.tokens[at].value.clone()) {
+            var.data_type = Some(return_type);
+            var.signature = parse_params(&self.tokens[at + 1].value);
+        }
+    }
+
+    /*The rust for a generic type written at token, with the aliases in it resolved. A malformed
+    one is reported and mapped anyway*/
+    fn rust_type(
+        text: &str,
+        token: &Token,
+        variables: &mut Variables,
+        errors: &mut Vec<ParseError>,
+    ) -> String {
+        match TypeNode::parse(text) {
+            Ok(mut node) => {
+                node.resolve(&mut |name| variables.alias(name));
+                node.rust()
+            }
+            Err(message) => {
+                errors.push(ParseError {
+                    message,
+                    state: LexerState {
+                        line: token.line,
+                        column: token.column,
+                    },
+                });
+                map_type(text)
+            }
         }
     }
-    pub fn parse(&mut self) -> Vec<Ast> {
+
+    /*Parses every token, the ones that don't fit where they are become AstType::Error nodes and
+    parsing carries on after them*/
+    pub fn parse_nodes(&mut self) -> Vec<Ast> {
         let mut full_ast: Vec<Ast> = Vec::new();
+        let mut errors: Vec<ParseError> = Vec::new();
         while self.tokens.len() > self.index as usize {
             let mut ast_res: Ast = Ast {
                 tokens: vec![],
                 ast_type: AstType::Other,
+                children: vec![],
             };
             let index = self.index as usize;
-            if index == self.tokens.len() {
-                panic!("Reached the end of tokens")
+            // An alias stands for its type from where it's declared on
+            if self.tokens[index].token_type == TokenType::Identifier {
+                if let Some(target) = self.variables.alias(&self.tokens[index].value) {
+                    self.tokens[index].value = target;
+                }
             }
             let token = &self.tokens[index];
             if self.json
                 && self.tokens.len() - (self.index as usize) > 2
-                && self.tokens[index + 1].value == ":"
+                && self.tokens[index + 1].token_type == TokenType::Colon
             {
                 ast_res.ast_type = AstType::Json;
                 ast_res.tokens.push(self.tokens[index].clone());
                 ast_res.tokens.push(self.tokens[index + 2].clone());
                 self.index += 2;
             } else if self.tokens.len() - index > 1
-                && self.tokens[index].value == "&"
+                && self.tokens[index].token_type == TokenType::Ampersand
                 && self.tokens[index + 1].token_type == TokenType::Identifier
             {
                 ast_res.tokens.push(self.tokens[index + 1].clone());
@@ -140,6 +417,9 @@ impl Parser {
                     },
                     desc,
                 );
+                if let Some(var) = self.variables.get_mut(self.tokens[index + 1].value.clone()) {
+                    var.fields = parse_fields(&self.tokens[index + 2]);
+                }
             } else if self.tokens.len() - index > 2
                 && self.tokens[index].value == "namespace"
                 && self.tokens[index + 1].token_type == TokenType::Identifier
@@ -178,7 +458,29 @@ impl Parser {
                 ast_res.tokens.push(self.tokens[index].clone());
                 ast_res.tokens.push(self.tokens[index + 1].clone());
                 ast_res.tokens.push(self.tokens[index + 2].clone());
-                ast_res.ast_type = AstType::State3;
+                ast_res.ast_type = if token.value == "if" {
+                    AstType::If
+                } else if token.value.starts_with("else") {
+                    AstType::ElseIf
+                } else if token.value == "while" {
+                    AstType::While
+                } else {
+                    AstType::For
+                };
+                if ast_res.ast_type == AstType::For {
+                    match for_header(&self.tokens[index + 1]) {
+                        Some(header) => {
+                            ast_res.tokens.splice(1..2, header);
+                        }
+                        None => errors.push(ParseError {
+                            message: message("for_header", &[]),
+                            state: LexerState {
+                                line: token.line,
+                                column: token.column,
+                            },
+                        }),
+                    }
+                }
                 self.index += 2;
             } else if self.tokens.len() - index > 1
                 && self.tokens[index].token_type == TokenType::Keyword2
@@ -186,13 +488,149 @@ impl Parser {
             {
                 ast_res.tokens.push(self.tokens[index].clone());
                 ast_res.tokens.push(self.tokens[index + 1].clone());
-                ast_res.ast_type = AstType::State2;
+                ast_res.ast_type = AstType::Else;
+                self.index += 1;
+            } else if self.tokens.len() - index > 1
+                && token.token_type == TokenType::Identifier
+                && INTRINSICS.contains(&token.value.as_str())
+                && self.tokens[index + 1].token_type == TokenType::Round
+            {
+                // Some intrinsics lex as identifiers, they're only intrinsics when called
+                ast_res.tokens.push(token.clone());
+                ast_res.tokens.push(self.tokens[index + 1].clone());
+                ast_res.ast_type = AstType::Intrinsic;
                 self.index += 1;
             } else {
                 match token.token_type {
                     TokenType::Identifier => {
                         ast_res.tokens.push(self.tokens[index].clone());
-                        if self.tokens.len() - index > 3
+                        if self.tokens.len() - index > 2
+                            && token.value == "type"
+                            && self.tokens[index + 1].token_type == TokenType::Identifier
+                            && self.tokens[index + 2].token_type == TokenType::Assign
+                        {
+                            match self.tokens.get(index + 3) {
+                                Some(target) if target.token_type == TokenType::Identifier => {
+                                    let mut target = target.clone();
+                                    let mut text = target.value.clone();
+                                    self.index += 3;
+                                    if let Some(args) = self.tokens.get(index + 4) {
+                                        if args.token_type == TokenType::Angle {
+                                            text = format!("{}<{}>", text, args.value);
+                                            self.index += 1;
+                                        }
+                                    }
+                                    target.value = Parser::rust_type(
+                                        &text,
+                                        &target,
+                                        &mut self.variables,
+                                        &mut errors,
+                                    );
+                                    ast_res.tokens.push(self.tokens[index + 1].clone());
+                                    ast_res.tokens.push(target.clone());
+                                    ast_res.ast_type = AstType::TypeAlias;
+                                    let mut desc = String::new();
+                                    if index > 0
+                                        && self.tokens[index - 1].token_type == TokenType::Comment
+                                    {
+                                        desc = self.tokens[index - 1].value.clone()
+                                    }
+                                    self.variables.new_alias(
+                                        self.tokens[index + 1].value.clone(),
+                                        LexerState {
+                                            line: self.tokens[index + 1].line,
+                                            column: self.tokens[index + 1].column,
+                                        },
+                                        desc,
+                                        target.value,
+                                    );
+                                }
+                                _ => {
+                                    let assign = self.tokens[index + 2].clone();
+                                    Parser::error(&mut ast_res, &mut errors, &assign, "a type");
+                                    self.index += 2;
+                                }
+                            }
+                        } else if token.value == "const" {
+                            // The value runs up to the semicolon, which stays a node of its own
+                            let end = self.tokens[index..]
+                                .iter()
+                                .position(|next| next.token_type == TokenType::Semicolon)
+                                .map_or(self.tokens.len(), |at| index + at);
+                            match &self.tokens[index + 1..end] {
+                                [data_type, name, assign, expr @ ..]
+                                    if data_type.token_type == TokenType::Identifier
+                                        && name.token_type == TokenType::Identifier
+                                        && assign.token_type == TokenType::Assign
+                                        && !expr.is_empty() =>
+                                {
+                                    let data_type = self
+                                        .variables
+                                        .alias(&data_type.value)
+                                        .unwrap_or(data_type.value.clone());
+                                    let value = const_value(
+                                        &expand_suffixes(expr, &self.suffixes),
+                                        &mut self.variables,
+                                    );
+                                    // Any of rust's int types
+                                    let int = RUST_SUFFIXES.contains(&data_type.as_str())
+                                        && !data_type.starts_with('f');
+                                    if value.is_none() || !int {
+                                        errors.push(ParseError {
+                                            message: message("const_value", &[&name.value]),
+                                            state: LexerState {
+                                                line: name.line,
+                                                column: name.column,
+                                            },
+                                        });
+                                    }
+                                    let mut desc = String::new();
+                                    if index > 0
+                                        && self.tokens[index - 1].token_type == TokenType::Comment
+                                    {
+                                        desc = self.tokens[index - 1].value.clone()
+                                    }
+                                    ast_res.tokens = vec![
+                                        Token {
+                                            value: data_type.clone(),
+                                            ..self.tokens[index + 1].clone()
+                                        },
+                                        name.clone(),
+                                        Token {
+                                            token_type: TokenType::Number,
+                                            value: value.unwrap_or_default().to_string(),
+                                            line: expr[0].line,
+                                            column: expr[0].column,
+                                        },
+                                    ];
+                                    ast_res.ast_type = AstType::ConstDeceleration;
+                                    self.variables.new_const(
+                                        name.value.clone(),
+                                        LexerState {
+                                            line: name.line,
+                                            column: name.column,
+                                        },
+                                        desc,
+                                        data_type,
+                                        value,
+                                    );
+                                    self.index = end as u32 - 1;
+                                }
+                                _ => Parser::error(
+                                    &mut ast_res,
+                                    &mut errors,
+                                    token,
+                                    "`int NAME = value`",
+                                ),
+                            }
+                        } else if self.tokens.len() - index > 1
+                            && token.value.contains('.')
+                            && self.tokens[index + 1].token_type == TokenType::Round
+                        {
+                            ast_res.tokens.push(self.tokens[index + 1].clone());
+                            ast_res.ast_type = AstType::MethodCall;
+                            self.index += 1;
+                        } else if self.tokens.len() - index > 3
                             && self.tokens[index + 1].token_type == TokenType::Identifier
                             && self.tokens[index + 2].token_type == TokenType::Round
                             && self.tokens[index + 3].token_type == TokenType::Curly
@@ -206,12 +644,43 @@ impl Parser {
                                 ast_res.ast_type = AstType::FunctionDeceleration;
                             }
                             self.index += 3;
+                            self.declare_func(index, index + 1, token.value.clone());
+                        } else if self.tokens.len() - index > 4
+                            && self.tokens[index + 1].token_type == TokenType::Angle
+                            && self.tokens[index + 2].token_type == TokenType::Identifier
+                            && self.tokens[index + 3].token_type == TokenType::Round
+                            && self.tokens[index + 4].token_type == TokenType::Curly
+                        {
+                            // A generic return type, like `vec<int> evens(int n) {...}`
+                            let text = format!("{}<{}>", token.value, self.tokens[index + 1].value);
+                            ast_res.tokens[0].value =
+                                Parser::rust_type(&text, token, &mut self.variables, &mut errors);
+                            ast_res
+                                .tokens
+                                .extend(self.tokens[index + 2..index + 5].iter().cloned());
+                            ast_res.ast_type = AstType::FunctionDeceleration;
+                            self.index += 4;
+                            self.declare_func(index, index + 2, ast_res.tokens[0].value.clone());
+                        } else if self.tokens.len() - index > 1
+                            && self.tokens[index + 1].token_type == TokenType::Curly
+                        {
+                            ast_res.tokens.push(self.tokens[index + 1].clone());
+                            ast_res.ast_type = AstType::StructCall;
+                            self.index += 1;
+                        } else if self.tokens.len() - index > 2
+                            && self.tokens[index + 1].token_type == TokenType::Identifier
+                            && self.tokens[index + 2].token_type == TokenType::Curly
+                        {
+                            ast_res.tokens.push(self.tokens[index + 1].clone());
+                            ast_res.tokens.push(self.tokens[index + 2].clone());
+                            ast_res.ast_type = AstType::StructVar;
+                            self.index += 2;
                             let mut desc = String::new();
                             if index > 0 && self.tokens[index - 1].token_type == TokenType::Comment
                             {
                                 desc = self.tokens[index - 1].value.clone()
                             }
-                            self.variables.new_func(
+                            self.variables.new_var(
                                 self.tokens[index + 1].clone().value,
                                 LexerState {
                                     line: self.tokens[index + 1].clone().line,
@@ -219,32 +688,59 @@ impl Parser {
                                 },
                                 desc,
                             );
-                        } else if self.tokens.len() - index > 1
-                            && self.tokens[index + 1].token_type == TokenType::Curly
+                        } else if self.tokens.len() - index > 2
+                            && self.tokens[index + 1].token_type == TokenType::Square
+                            && self.tokens[index + 2].token_type == TokenType::Identifier
                         {
                             ast_res.tokens.push(self.tokens[index + 1].clone());
-                            ast_res.ast_type = AstType::StructCall;
-                            self.index += 1;
+                            ast_res.tokens.push(self.tokens[index + 2].clone());
+                            ast_res.ast_type = AstType::ArrayDeceleration;
+                            self.index += 2;
+                            let mut desc = String::new();
+                            if index > 0 && self.tokens[index - 1].token_type == TokenType::Comment
+                            {
+                                desc = self.tokens[index - 1].value.clone()
+                            }
+                            let size = array_size(&self.tokens[index + 1], &mut self.variables);
+                            self.variables.new_array(
+                                self.tokens[index + 2].clone().value,
+                                LexerState {
+                                    line: self.tokens[index + 2].clone().line,
+                                    column: self.tokens[index + 2].clone().column,
+                                },
+                                desc,
+                                token.value.clone(),
+                                size,
+                            );
                         } else if self.tokens.len() - index > 2
                             && self.tokens[index + 1].token_type == TokenType::Identifier
-                            && self.tokens[index + 2].token_type == TokenType::Curly
+                            && self.tokens[index + 2].token_type == TokenType::Square
                         {
+                            let mut square = self.tokens[index + 2].clone();
+                            if square.value.trim().is_empty() {
+                                if let Some(count) = self.literal_len(index + 3) {
+                                    square.value = count.to_string();
+                                }
+                            }
+                            ast_res.tokens.push(square.clone());
                             ast_res.tokens.push(self.tokens[index + 1].clone());
-                            ast_res.tokens.push(self.tokens[index + 2].clone());
-                            ast_res.ast_type = AstType::StructVar;
+                            ast_res.ast_type = AstType::ArrayDeceleration;
                             self.index += 2;
                             let mut desc = String::new();
                             if index > 0 && self.tokens[index - 1].token_type == TokenType::Comment
                             {
                                 desc = self.tokens[index - 1].value.clone()
                             }
-                            self.variables.new_var(
+                            let size = array_size(&square, &mut self.variables);
+                            self.variables.new_array(
                                 self.tokens[index + 1].clone().value,
                                 LexerState {
                                     line: self.tokens[index + 1].clone().line,
                                     column: self.tokens[index + 1].clone().column,
                                 },
                                 desc,
+                                token.value.clone(),
+                                size,
                             );
                         } else if self.tokens.len() - index > 1 {
                             if self.tokens[index + 1].token_type == TokenType::Identifier {
@@ -265,14 +761,34 @@ impl Parser {
                                     },
                                     desc,
                                 );
+                                if let Some(var) =
+                                    self.variables.get_mut(self.tokens[index + 1].value.clone())
+                                {
+                                    var.data_type = Some(token.value.clone());
+                                }
                             } else if self.tokens.len() - index > 2
                                 && self.tokens[index + 2].token_type == TokenType::Identifier
                                 && self.tokens[index + 1].token_type == TokenType::Angle
                             {
                                 ast_res.tokens.push(self.tokens[index + 2].clone());
-                                ast_res.tokens[0].value += "<";
-                                ast_res.tokens[0].value += self.tokens[index + 1].value.as_str();
-                                ast_res.tokens[0].value += ">";
+                                let buffer = if token.value == "buffer" {
+                                    buffer_type(&self.tokens[index + 1].value)
+                                } else {
+                                    None
+                                };
+                                let text =
+                                    format!("{}<{}>", token.value, self.tokens[index + 1].value);
+                                ast_res.tokens[0].value = match &buffer {
+                                    Some((ty, _)) => ty.clone(),
+                                    // The transpiler reports the length that couldn't be worked out
+                                    None if token.value == "buffer" => map_type(&text),
+                                    None => Parser::rust_type(
+                                        &text,
+                                        token,
+                                        &mut self.variables,
+                                        &mut errors,
+                                    ),
+                                };
                                 ast_res.ast_type = AstType::VariableDeceleration;
                                 self.index += 2;
                                 let mut desc = String::new();
@@ -282,15 +798,22 @@ impl Parser {
                                     desc = self.tokens[index - 1].value.clone()
                                 }
                                 self.variables.new_var(
-                                    self.tokens[index + 1].clone().value,
+                                    self.tokens[index + 2].clone().value,
                                     LexerState {
-                                        line: self.tokens[index + 1].clone().line,
-                                        column: self.tokens[index + 1].clone().column,
+                                        line: self.tokens[index + 2].clone().line,
+                                        column: self.tokens[index + 2].clone().column,
                                     },
                                     desc,
                                 );
+                                if let Some(var) =
+                                    self.variables.get_mut(self.tokens[index + 2].value.clone())
+                                {
+                                    var.data_type = Some(ast_res.tokens[0].value.clone());
+                                    // Constant indexes into a buffer are bounds checked
+                                    var.size = buffer.map(|(_, size)| size);
+                                }
                             } else if self.tokens.len() - index > 2
-                                && self.tokens[index + 1].value == "*"
+                                && self.tokens[index + 1].token_type == TokenType::Star
                                 && self.tokens[index + 2].token_type == TokenType::Identifier
                             {
                                 ast_res.tokens.push(self.tokens[index + 2].clone());
@@ -318,16 +841,16 @@ impl Parser {
                             ast_res.tokens.push(Token {
                                 token_type: TokenType::String,
                                 value: caps[2].to_owned().to_string(),
-                                line: 0,
-                                column: 0,
+                                line: token.line,
+                                column: token.column,
                             });
                             ast_res.ast_type = AstType::Include;
                         } else if let Some(caps) = self.include_regex_local.captures(&token.value) {
                             ast_res.tokens.push(Token {
                                 token_type: TokenType::String,
                                 value: caps[2].to_owned().to_string(),
-                                line: 0,
-                                column: 0,
+                                line: token.line,
+                                column: token.column,
                             });
                             ast_res.ast_type = AstType::IncludeLocal;
                         } else {
@@ -335,21 +858,77 @@ impl Parser {
                             ast_res.ast_type = AstType::Include;
                         }
                     }
+                    TokenType::Pragma => {
+                        ast_res.tokens.push(token.clone());
+                        ast_res.ast_type = AstType::Pragma;
+                    }
                     TokenType::Keyword => {
-                        if token.value == "cb"
-                            && self.tokens[index + 1].token_type == TokenType::Curly
+                        if token.value == "cb" {
+                            if self.tokens.len() - index > 1
+                                && self.tokens[index + 1].token_type == TokenType::Curly
+                            {
+                                ast_res.tokens.push(self.tokens[index + 1].clone());
+                                ast_res.ast_type = AstType::CodeBlock;
+                                self.index += 1;
+                            } else {
+                                Parser::error(&mut ast_res, &mut errors, token, "{...}");
+                            }
+                        } else if INTRINSICS.contains(&token.value.as_str())
+                            && self.tokens.len() - index > 1
+                            && self.tokens[index + 1].token_type == TokenType::Round
                         {
+                            ast_res.tokens.push(token.clone());
                             ast_res.tokens.push(self.tokens[index + 1].clone());
-                            ast_res.ast_type = AstType::CodeBlock;
+                            ast_res.ast_type = AstType::Intrinsic;
                             self.index += 1;
+                        } else if token.value == "return" {
+                            // The expression runs up to the semicolon, which stays a node of its own
+                            let end = self.tokens[index..]
+                                .iter()
+                                .position(|next| next.token_type == TokenType::Semicolon)
+                                .map_or(self.tokens.len(), |at| index + at);
+                            ast_res
+                                .tokens
+                                .extend(self.tokens[index..end].iter().cloned());
+                            let mut expr = Parser::new(
+                                self.tokens[index + 1..end].to_vec(),
+                                self.variables.clone(),
+                            );
+                            ast_res.children = expr.parse_nodes();
+                            errors.append(&mut expr.errors);
+                            ast_res.ast_type = AstType::Return;
+                            self.index = end as u32 - 1;
                         } else {
                             ast_res.tokens.push(token.clone());
                         }
                     }
+                    TokenType::Attribute => {
+                        ast_res.tokens.push(token.clone());
+                        ast_res.ast_type = AstType::Attribute;
+                        if self.tokens.len() - index > 1
+                            && self.tokens[index + 1].token_type == TokenType::Round
+                        {
+                            ast_res.tokens.push(self.tokens[index + 1].clone());
+                            self.index += 1;
+                        }
+                    }
+                    TokenType::Curly if follows_array(&full_ast) => {
+                        ast_res.tokens.push(token.clone());
+                        ast_res.ast_type = AstType::ArrayLiteral;
+                    }
+                    TokenType::Square if indexes_array(&full_ast, &mut self.variables) => {
+                        ast_res.tokens.push(token.clone());
+                        ast_res.ast_type = AstType::Index;
+                    }
                     TokenType::StaticExecution => {
-                        if self.tokens[index + 1].token_type == TokenType::Square {
+                        if self.tokens.len() - index > 1
+                            && self.tokens[index + 1].token_type == TokenType::Square
+                        {
                             ast_res.tokens.push(self.tokens[index + 1].clone());
                             ast_res.ast_type = AstType::StaticExecution;
+                            self.index += 1;
+                        } else {
+                            Parser::error(&mut ast_res, &mut errors, token, "[...]");
                         }
                     }
                     _ => {
@@ -357,9 +936,92 @@ impl Parser {
                     }
                 }
             }
+            if (ast_res.ast_type == AstType::ElseIf || ast_res.ast_type == AstType::Else)
+                && !continues_if(&full_ast)
+            {
+                errors.push(ParseError {
+                    message: message("else_without_if", &[&ast_res.tokens[0].value]),
+                    state: LexerState {
+                        line: ast_res.tokens[0].line,
+                        column: ast_res.tokens[0].column,
+                    },
+                });
+                ast_res.ast_type = AstType::Error;
+            }
             self.index += 1;
             full_ast.push(ast_res);
         }
+        self.errors.extend(errors);
         full_ast
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, panic, path::Path};
+
+    // What the lexer and the parser report for code, one line each
+    fn diagnostics(code: &str) -> String {
+        let tokens = match lex(code, false, LexerState { line: 1, column: 0 }) {
+            Ok(tokens) => tokens,
+            Err((state, _tokens)) => {
+                return format!("{}:{}: the lexer stopped\n", state.line, state.column)
+            }
+        };
+        Parser::new(tokens.clone(), Variables::new()).parse_tree();
+        let mut out = String::new();
+        if let Err(errors) = Parser::new(tokens, Variables::new()).parse() {
+            for error in errors {
+                out += format!(
+                    "{}:{}: {}\n",
+                    error.state.line, error.state.column, error.message
+                )
+                .as_str();
+            }
+        }
+        out
+    }
+
+    /*Every .wt file in tests/regressions goes through the lexer and the parser, which mustn't
+    panic on it and must report what the .expected file next to it holds. WYST_BLESS=1 rewrites
+    those files*/
+    #[test]
+    fn regressions() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/regressions");
+        let mut paths: Vec<_> = fs::read_dir(dir)
+            .expect("tests/regressions")
+            .map(|entry| entry.expect("tests/regressions").path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "wt"))
+            .collect();
+        paths.sort();
+        assert!(!paths.is_empty());
+        let bless = std::env::var_os("WYST_BLESS").is_some();
+        let mut failures = Vec::new();
+        for path in paths {
+            let code = fs::read_to_string(&path).expect("regression input");
+            let got = match panic::catch_unwind(|| diagnostics(&code)) {
+                Ok(got) => got,
+                Err(_) => {
+                    failures.push(format!("{} panicked", path.display()));
+                    continue;
+                }
+            };
+            let expected_path = path.with_extension("expected");
+            if bless {
+                fs::write(&expected_path, &got).expect("regression output");
+                continue;
+            }
+            let expected = fs::read_to_string(&expected_path).unwrap_or_default();
+            if got != expected {
+                failures.push(format!(
+                    "{}\nexpected:\n{}got:\n{}",
+                    path.display(),
+                    expected,
+                    got
+                ));
+            }
+        }
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}
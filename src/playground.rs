@@ -0,0 +1,287 @@
+use crate::{lspcom::Problem, transpiler::Transpiler, variable::Variables};
+use serde_json::{json, Value};
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    panic,
+    time::Duration,
+};
+
+// The backends a playground can ask for
+const TARGETS: [&str; 1] = ["rust"];
+// Requests with a bigger body are refused before it's read
+const MAX_BODY: usize = 1024 * 1024;
+// A client that stops sending is dropped after this, the next request waits otherwise
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn diagnostic(problem: &Problem) -> Value {
+    json!({
+        "code": problem.problem_type.code(),
+        "type": format!("{:?}", problem.problem_type),
+        "message": problem.problem_msg,
+        "line": problem.state.line,
+        "column": problem.state.column,
+    })
+}
+
+/*Transpiles one source file to a single rust file, like `wyst build --emit amalgamation`. The
+code comes from anyone who can reach the server, so it can't reach the files or environment of
+this machine*/
+fn compile(source: String) -> Result<Value, String> {
+    let result = panic::catch_unwind(|| {
+        let mut trsp = Transpiler {
+            sandbox: true,
+            ..Default::default()
+        };
+        let mut vars = Variables::new();
+        let mut code = trsp.transpile(source, 0, &mut vars);
+        code = trsp.writer.runtime_mods() + code.as_str();
+        code += "\nfn main() {let code = ";
+        code += vars.get_var("main".to_string(), &mut trsp).as_str();
+        code += "(); std::process::exit(code)}";
+        (trsp.writer.amalgamate(&code), trsp.problems)
+    });
+    match result {
+        Ok((output, problems)) => Ok(json!({
+            "output": if problems.is_empty() { output } else { String::new() },
+            "diagnostics": problems.iter().map(diagnostic).collect::<Vec<Value>>(),
+        })),
+        // The lexer panics on text it can't tokenize
+        Err(_) => Err("invalid syntax".to_string()),
+    }
+}
+
+// Lets the pages of the configured origin read the response, other sites get no header
+fn cors(origin: Option<&str>) -> String {
+    match origin {
+        Some(origin) => format!(
+            "Access-Control-Allow-Origin: {}\r\nVary: Origin\r\n",
+            origin
+        ),
+        None => String::new(),
+    }
+}
+
+fn respond(stream: &mut TcpStream, origin: Option<&str>, status: &str, body: Value) {
+    let body = body.to_string();
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        cors(origin),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes());
+}
+
+fn handle(stream: &mut TcpStream, origin: Option<&str>) {
+    if stream.set_read_timeout(Some(READ_TIMEOUT)).is_err() {
+        return;
+    }
+    let mut reader = BufReader::new(stream.try_clone().expect("err_clone_stream"));
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).is_err() || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    if content_length > MAX_BODY {
+        respond(
+            stream,
+            origin,
+            "413 Payload Too Large",
+            json!({"error": format!("the body is over {} bytes", MAX_BODY)}),
+        );
+        return;
+    }
+    let mut body = vec![0u8; content_length];
+    if reader.read_exact(&mut body).is_err() {
+        return;
+    }
+    let mut parts = request_line.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some("POST"), Some("/compile")) => {}
+        // Browsers ask before a cross origin POST with a json body
+        (Some("OPTIONS"), _) => {
+            let response = format!("HTTP/1.1 204 No Content\r\n{}Access-Control-Allow-Methods: POST\r\nAccess-Control-Allow-Headers: Content-Type\r\nConnection: close\r\n\r\n", cors(origin));
+            let _ = stream.write_all(response.as_bytes());
+            return;
+        }
+        _ => {
+            respond(
+                stream,
+                origin,
+                "404 Not Found",
+                json!({"error": "expected POST /compile"}),
+            );
+            return;
+        }
+    }
+    let request: Value = match serde_json::from_slice(&body) {
+        Ok(request) => request,
+        Err(err) => {
+            respond(
+                stream,
+                origin,
+                "400 Bad Request",
+                json!({"error": err.to_string()}),
+            );
+            return;
+        }
+    };
+    let source = match request["source"].as_str() {
+        Some(source) => source.to_string(),
+        None => {
+            respond(
+                stream,
+                origin,
+                "400 Bad Request",
+                json!({"error": "missing \"source\""}),
+            );
+            return;
+        }
+    };
+    let target = request["target"].as_str().unwrap_or("rust");
+    if !TARGETS.contains(&target) {
+        respond(
+            stream,
+            origin,
+            "400 Bad Request",
+            json!({"error": format!("unknown target '{}', expected one of: {}", target, TARGETS.join(", "))}),
+        );
+        return;
+    }
+    match compile(source) {
+        Ok(result) => respond(stream, origin, "200 OK", result),
+        Err(err) => respond(stream, origin, "400 Bad Request", json!({"error": err})),
+    }
+}
+
+/*Serves POST /compile with {"source": "...", "target": "rust"} and answers with the output and
+the diagnostics as json. Requests are handled one at a time. Only pages from `origin` may call it
+from a browser, with none only pages served from the same address can*/
+pub fn serve(port: u16, origin: Option<&str>) {
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("can't listen on port {}: {}", port, err);
+            std::process::exit(1);
+        }
+    };
+    println!("Playground listening on http://127.0.0.1:{}/compile", port);
+    for mut stream in listener.incoming().flatten() {
+        handle(&mut stream, origin);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::Shutdown, thread};
+
+    // What the server answers to a raw request
+    fn exchange(request: &[u8], origin: Option<&'static str>) -> String {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).expect("bind");
+        let port = listener.local_addr().expect("port").port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("accept");
+            handle(&mut stream, origin);
+        });
+        let mut client = TcpStream::connect(("127.0.0.1", port)).expect("connect");
+        client.write_all(request).expect("request");
+        let _ = client.shutdown(Shutdown::Write);
+        let mut response = String::new();
+        client.read_to_string(&mut response).expect("response");
+        server.join().expect("server");
+        response
+    }
+
+    fn post(body: &str) -> Vec<u8> {
+        format!(
+            "POST /compile HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .into_bytes()
+    }
+
+    fn codes(source: &str) -> Vec<String> {
+        let result = compile(source.to_string()).expect("compile");
+        assert_eq!(result["output"], "");
+        result["diagnostics"]
+            .as_array()
+            .expect("diagnostics")
+            .iter()
+            .map(|diagnostic| diagnostic["code"].as_str().unwrap_or_default().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn compiles_a_program() {
+        let result = compile("int main() {\n  return 3;\n}\n".to_string()).expect("compile");
+        assert_eq!(result["diagnostics"], json!([]));
+        assert!(result["output"]
+            .as_str()
+            .is_some_and(|output| output.contains("fn main()")));
+    }
+
+    #[test]
+    fn sandboxed_code_cant_reach_the_machine() {
+        assert_eq!(
+            codes("#include \"/etc/passwd\"\nint main() {\n  return 0;\n}\n"),
+            ["E0020"]
+        );
+        assert_eq!(
+            codes("int main() {\n  string home = env!(\"HOME\");\n  return 0;\n}\n"),
+            ["E0020"]
+        );
+        assert_eq!(
+            codes("int main() {\n  embed!(\"/etc/passwd\");\n  return 0;\n}\n"),
+            ["E0020"]
+        );
+    }
+
+    #[test]
+    fn cors_only_for_the_configured_origin() {
+        assert_eq!(cors(None), "");
+        assert_eq!(
+            cors(Some("https://play.example")),
+            "Access-Control-Allow-Origin: https://play.example\r\nVary: Origin\r\n"
+        );
+        let response = exchange(&post(r#"{"source": "int main() { return 0; }"}"#), None);
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(!response.contains("Access-Control-Allow-Origin"));
+    }
+
+    #[test]
+    fn big_bodies_are_refused() {
+        let request = format!(
+            "POST /compile HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY + 1
+        );
+        let response = exchange(request.as_bytes(), Some("https://play.example"));
+        assert!(response.starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+        assert!(response.contains("Access-Control-Allow-Origin: https://play.example\r\n"));
+    }
+
+    #[test]
+    fn bad_requests() {
+        let response = exchange(b"GET / HTTP/1.1\r\n\r\n", None);
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        let response = exchange(&post(r#"{"source": "", "target": "cobol"}"#), None);
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert!(response.contains("unknown target 'cobol'"));
+        let response = exchange(&post("{"), None);
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    }
+}
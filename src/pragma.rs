@@ -0,0 +1,56 @@
+use crate::{
+    lexer::LexerState,
+    lspcom::{did_you_mean, Problem, ProblemType},
+    messages::message,
+    parser::Ast,
+};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+// Experimental language features, a file turns them on with `#pragma feature(name)`
+pub const FEATURES: [&str; 1] = ["threads"];
+
+static PRAGMA: Lazy<Regex> = Lazy::new(|| Regex::new(r"^#pragma +feature\((.*?)\)").unwrap());
+
+// The feature a piece of syntax is gated behind, None when it's stable
+pub fn gate(name: &str) -> Option<&'static str> {
+    match name {
+        "spawn" | "join" => Some("threads"),
+        _ => None,
+    }
+}
+
+/*Turns on the features a pragma lists, `#pragma feature(threads, ...)`. Names that aren't
+features are reported and ignored*/
+pub fn enable(ast: &Ast, features: &mut Vec<String>) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let token = &ast.tokens[0];
+    let Some(caps) = PRAGMA.captures(&token.value) else {
+        return problems;
+    };
+    for name in caps[1]
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        if FEATURES.contains(&name) {
+            if !features.iter().any(|feature| feature == name) {
+                features.push(name.to_string());
+            }
+        } else {
+            problems.push(Problem {
+                problem_type: ProblemType::FeatureGate,
+                problem_msg: message(
+                    "unknown_feature",
+                    &[&name, &did_you_mean(name, FEATURES.into_iter())],
+                ),
+                state: LexerState {
+                    line: token.line,
+                    column: token.column,
+                },
+                file: String::new(),
+            });
+        }
+    }
+    problems
+}
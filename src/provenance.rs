@@ -0,0 +1,85 @@
+use crate::{manifest::Manifest, package};
+use serde_json::json;
+use std::{
+    fs,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// [generated] in wyst.toml, what goes into the rust files wyst writes for other tools
+#[derive(Clone, Debug)]
+pub struct Provenance {
+    pub banner: bool,
+    // Off by default so the same sources always give the same bytes
+    pub timestamp: bool,
+}
+
+impl Provenance {
+    pub fn from_manifest(manifest: &Manifest) -> Result<Provenance, String> {
+        Ok(Provenance {
+            banner: manifest.get_bool("generated", "banner")?.unwrap_or(true),
+            timestamp: manifest
+                .get_bool("generated", "timestamp")?
+                .unwrap_or(false),
+        })
+    }
+
+    pub fn load() -> Provenance {
+        match Manifest::load().and_then(|manifest| Provenance::from_manifest(&manifest)) {
+            Ok(provenance) => provenance,
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or(0)
+    }
+
+    // The comment generated code starts with, empty when [generated] banner = false
+    pub fn banner(&self, source: &str) -> String {
+        if !self.banner {
+            return String::new();
+        }
+        let mut out = format!(
+            "// Generated by wyst {} from {}, do not edit.\n// Change {} and build again instead.\n",
+            env!("CARGO_PKG_VERSION"),
+            source,
+            source
+        );
+        if self.timestamp {
+            out += format!("// Generated at {} (unix time)\n", Provenance::now()).as_str();
+        }
+        out
+    }
+
+    /*Writes output.json next to a generated file with the hash of every source it came from,
+    a build system compares those to the sources on disk to tell when the output is stale*/
+    pub fn sidecar(
+        &self,
+        output: &str,
+        code: &str,
+        sources: &[(&str, &str)],
+    ) -> Result<(), String> {
+        let mut meta = json!({
+            "generator": "wyst",
+            "version": env!("CARGO_PKG_VERSION"),
+            "output": output,
+            "output_hash": package::hash(code.as_bytes()),
+            "sources": sources
+                .iter()
+                .map(|(path, text)| json!({ "path": path, "hash": package::hash(text.as_bytes()) }))
+                .collect::<Vec<_>>(),
+        });
+        if self.timestamp {
+            meta["generated_at"] = json!(Provenance::now());
+        }
+        let path = format!("{}.json", output);
+        let text = serde_json::to_string_pretty(&meta).map_err(|err| err.to_string())?;
+        fs::write(&path, text + "\n").map_err(|err| format!("{}: {}", path, err))
+    }
+}
@@ -0,0 +1,41 @@
+use std::time::{Duration, Instant};
+
+// What a build did, printed by `wyst build --summary`
+#[derive(Clone, Debug, Default)]
+pub struct Summary {
+    pub files: usize,
+    pub lines: usize,
+    pub symbols: usize,
+    pub warnings: usize,
+    pub errors: usize,
+    pub phases: Vec<(&'static str, Duration)>,
+}
+
+impl Summary {
+    // Records how long a phase took, from start until now
+    pub fn phase(&mut self, name: &'static str, start: Instant) {
+        self.phases.push((name, start.elapsed()));
+    }
+
+    pub fn table(&self) -> String {
+        let mut rows: Vec<(String, String)> = vec![
+            ("files".to_string(), self.files.to_string()),
+            ("lines".to_string(), self.lines.to_string()),
+            ("symbols".to_string(), self.symbols.to_string()),
+            ("warnings".to_string(), self.warnings.to_string()),
+            ("errors".to_string(), self.errors.to_string()),
+        ];
+        let millis = |time: Duration| format!("{:.1}ms", time.as_secs_f64() * 1000.0);
+        for (name, time) in &self.phases {
+            rows.push((name.to_string(), millis(*time)));
+        }
+        let total = self.phases.iter().map(|(_, time)| *time).sum();
+        rows.push(("total".to_string(), millis(total)));
+        let width = rows.iter().map(|(_, value)| value.len()).max().unwrap_or(0);
+        let mut out = String::new();
+        for (name, value) in rows {
+            out += format!("{:<10} {:>width$}\n", name, value, width = width).as_str();
+        }
+        out
+    }
+}
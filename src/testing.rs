@@ -0,0 +1,188 @@
+use std::{fs, path::Path, process::Command};
+
+use crate::{
+    compile,
+    lexer::{lex, LexerState, TokenType},
+    lspcom, manifest,
+    package::{self, Features},
+    transpiler::Transpiler,
+    variable::Variables,
+};
+
+const RUNNER: &str = "wyst_tests";
+
+#[derive(Clone, Debug)]
+pub struct TestCase {
+    pub name: String,
+    pub rname: String,
+    // int tests fail by returning something other than 0
+    pub returns_int: bool,
+}
+
+// A ```wyst block in the `//` comments of a file, it runs as a test of its own with `wyst test --doc`
+#[derive(Clone, Debug)]
+pub struct DocExample {
+    // The line of the opening fence
+    pub line: usize,
+    pub code: String,
+}
+
+/*The examples in the top level comments of a file. The comment markers are taken off every
+line, and a block that isn't closed before the comment ends is left out*/
+pub fn doc_examples(code: &str) -> Vec<DocExample> {
+    let Ok(tokens) = lex(code, false, LexerState { line: 1, column: 0 }) else {
+        return vec![];
+    };
+    let mut examples = Vec::new();
+    let mut open: Option<DocExample> = None;
+    for token in tokens {
+        if token.token_type != TokenType::Comment {
+            open = None;
+            continue;
+        }
+        let text = token.value.trim_end().trim_start_matches('/');
+        let text = text.strip_prefix(' ').unwrap_or(text);
+        match open.take() {
+            None => {
+                if text.trim() == "```wyst" {
+                    open = Some(DocExample {
+                        line: token.line,
+                        code: String::new(),
+                    });
+                }
+            }
+            Some(example) if text.trim() == "```" => examples.push(example),
+            Some(mut example) => {
+                example.code += text;
+                example.code += "\n";
+                open = Some(example);
+            }
+        }
+    }
+    examples
+}
+
+// The runner's main runs the single test named by its first argument, report goes after it
+pub fn runner_main(tests: &[TestCase], hooks: &str, report: &str) -> String {
+    let mut out = format!(
+        "\nfn main() {{{}let code = match std::env::args().nth(1).unwrap_or_default().as_str() {{",
+        hooks
+    );
+    for test in tests {
+        if test.returns_int {
+            out += format!("\"{}\" => {}(),", test.name, test.rname).as_str();
+        } else {
+            out += format!("\"{}\" => {}{}(); 0{},", test.name, "{", test.rname, "}").as_str();
+        }
+    }
+    out += "_ => 101};";
+    out += report;
+    out += "std::process::exit(code)}";
+    out
+}
+
+/*Compiles main.wt in the test configuration and runs every @test function in its own process,
+or every example in its comments instead with doc*/
+pub fn run_tests(
+    coverage: bool,
+    doc: bool,
+    deterministic: bool,
+    features: &Features,
+    runtime_dir: Option<String>,
+    error_limit: Option<usize>,
+) -> bool {
+    package::require_lock();
+    let toolchain = compile::load_toolchain();
+    let file_content = fs::read_to_string("main.wt").expect("Error reading file");
+    if Path::new("build").exists() {
+        fs::remove_dir_all("build").expect("err rm build");
+    }
+    fs::create_dir("build").expect("error making build");
+    let mut trsp = Transpiler {
+        test: true,
+        coverage,
+        ..Default::default()
+    };
+    let profile = manifest::load_profile("debug");
+    trsp.writer.configure(features);
+    trsp.writer.runtime_dir = runtime_dir;
+    trsp.writer.trace = profile.trace;
+    trsp.writer.deterministic = deterministic || profile.deterministic;
+    let mut vars = Variables::new();
+    let mut transpiled_code = trsp.transpile(file_content.clone(), 0, &mut vars);
+    if doc {
+        trsp.tests.clear();
+        for example in doc_examples(&file_content) {
+            // The function starts on the fence, so the lines of the example are the ones in main.wt
+            trsp.state = LexerState {
+                line: example.line,
+                column: 0,
+            };
+            let wrapped = format!(
+                "@test void wyst_doc_{}() {{\n{}}}\n",
+                example.line, example.code
+            );
+            transpiled_code += trsp.transpile(wrapped, 0, &mut vars).as_str();
+            if let Some(test) = trsp.tests.last_mut() {
+                test.name = format!("{} - line {}", trsp.file, example.line);
+            }
+        }
+    }
+    let mut report = String::new();
+    let hooks = trsp.panic_hooks(&vars) + trsp.exit_hooks().as_str();
+    if coverage {
+        trsp.writer.use_runtime("wyst_coverage");
+        report = trsp.coverage_report();
+        // Every test process appends its own record
+        if Path::new("coverage.lcov").exists() {
+            fs::remove_file("coverage.lcov").expect("err rm coverage");
+        }
+    }
+    transpiled_code = trsp.writer.runtime_mods() + transpiled_code.as_str();
+    transpiled_code += runner_main(&trsp.tests, &hooks, &report).as_str();
+    for line in lspcom::summarize(&trsp.problems, error_limit) {
+        println!("{}", line)
+    }
+    if !trsp.problems.is_empty() {
+        return false;
+    }
+    trsp.writer.write();
+    compile::write_to_rust_file(&transpiled_code, "build/main.rs")
+        .expect("Error writing to temporary Rust file");
+    std::env::set_current_dir("build").expect("setDir err: ");
+    let mut rustc_args = profile.rustc_args();
+    rustc_args.extend(features.rustc_args());
+    let compiled = compile::compile_to_executable(&toolchain, RUNNER, &rustc_args);
+    std::env::set_current_dir("..").expect("setDir0 err: ");
+    if compiled.is_err() {
+        return false;
+    }
+
+    println!("\nrunning {} tests", trsp.tests.len());
+    let mut failed = Vec::new();
+    for test in &trsp.tests {
+        let output = Command::new(Path::new("build").join(RUNNER))
+            .arg(&test.name)
+            .output()
+            .expect("err_run_test");
+        if output.status.success() {
+            println!("test {} ... ok", test.name);
+        } else {
+            println!("test {} ... FAILED", test.name);
+            failed.push((test.name.clone(), output));
+        }
+    }
+    for (name, output) in &failed {
+        println!("\n---- {} output ----", name);
+        print!("{}", String::from_utf8_lossy(&output.stdout));
+        print!("{}", String::from_utf8_lossy(&output.stderr));
+    }
+    println!(
+        "\ntest result: {}. {} passed; {} failed",
+        if failed.is_empty() { "ok" } else { "FAILED" },
+        trsp.tests.len() - failed.len(),
+        failed.len()
+    );
+    fs::remove_dir_all("build").expect("err rm build");
+    failed.is_empty()
+}
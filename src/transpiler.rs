@@ -1,9 +1,20 @@
 use crate::{
+    analysis::{check_indexes, check_returns, check_unreachable, const_value, split_literal},
+    attribute,
     file_writer::FileWriter,
-    lexer::{lex, LexerState, TokenType},
-    lspcom::{Problem, ProblemType},
+    intrinsic::{
+        arith_op, arity, bit_op, is_integer, method_arity, methods, receiver, split_args,
+        string_literal, vec_element, wyst_type, ARITH_INTRINSICS, BIT_INTRINSICS,
+    },
+    lexer::{
+        advance, inner_state, lex, type_args, LexerState, Token, TokenType, MAP_TYPE, RUST_SUFFIXES,
+    },
+    lspcom::{did_you_mean, Problem, ProblemType},
+    messages::message,
     parser::{is_decl, Ast, AstType, Parser},
-    variable::{VariableType, Variables},
+    pragma,
+    testing::TestCase,
+    variable::{Variable, VariableType, Variables},
 };
 
 use std::{fs, path::Path};
@@ -19,7 +30,23 @@ pub struct Transpiler {
     pub peek: String,
     pub matched_vars: Variables,
     pub problems: Vec<Problem>,
+    // Problems that don't stop the build, like uses of @deprecated functions
+    pub warnings: Vec<Problem>,
     pub writer: FileWriter,
+    pub test: bool,
+    pub tests: Vec<TestCase>,
+    pub file: String,
+    pub coverage: bool,
+    // The wyst line of every coverage probe, indexed by probe number
+    pub probes: Vec<usize>,
+    // The experimental features this file turned on with #pragma feature(...)
+    pub features: Vec<String>,
+    // Set by the playground, the code can't read files or the environment it's compiled in
+    pub sandbox: bool,
+    // Unknown names with the declared name closest to each, the language server's quick fixes
+    pub suggestions: Vec<(String, String)>,
+    // The type of the elements of the vec literal transpile_square is lowering, if it's one
+    pub element: Option<String>,
 }
 
 impl Default for Transpiler {
@@ -35,6 +62,16 @@ impl Default for Transpiler {
             peek: String::new(),
             matched_vars: Variables::new(),
             problems: Vec::new(),
+            warnings: Vec::new(),
+            test: false,
+            tests: Vec::new(),
+            file: String::from("main.wt"),
+            coverage: false,
+            probes: Vec::new(),
+            features: Vec::new(),
+            sandbox: false,
+            suggestions: Vec::new(),
+            element: None,
         };
         transpiler
     }
@@ -53,16 +90,68 @@ impl Transpiler {
         match lexer_out {
             Ok(tokens) => {
                 let mut full_ast = Parser::new(tokens.clone(), variables.clone());
+                full_ast.suffixes = self.writer.suffixes.clone();
                 let mut last_ast = Ast {
                     ast_type: AstType::Other,
                     tokens: vec![],
+                    children: vec![],
                 };
-                let f_ast = full_ast.parse();
+                let f_ast = self.parsed(&mut full_ast);
                 //variables.expand(full_ast.variables.clone());
                 *variables = full_ast.variables.clone();
+                // Includes go first so their names can be used above the #include
+                for ast in &f_ast {
+                    if ast.ast_type == AstType::Include || ast.ast_type == AstType::IncludeLocal {
+                        result += self.transpile_include(ast, variables).as_str();
+                    } else if ast.ast_type == AstType::Pragma {
+                        let pragma_problems = pragma::enable(ast, &mut self.features);
+                        self.problems.extend(pragma_problems);
+                    }
+                }
+                attribute::mark_deprecated(&f_ast, variables);
+                let index_problems = check_indexes(&f_ast, variables);
+                self.problems.extend(index_problems);
                 //let mut variables = full_ast.variables.clone();
+                let mut attributes: Vec<Ast> = Vec::new();
+                let mut stmt_start = true;
+                // The type a `[...]` literal right after `vec<T> name =` has to be built as
+                let mut collection: Option<String> = None;
+                // What an array declared on this line starts as, unless an `=` comes next
+                let mut uninit: Option<String> = None;
                 for ast in f_ast {
                     let mut ast = ast;
+                    if ast.ast_type == AstType::Attribute {
+                        attributes.push(ast);
+                        continue;
+                    }
+                    if ast.ast_type == AstType::Include
+                        || ast.ast_type == AstType::IncludeLocal
+                        || ast.ast_type == AstType::Pragma
+                    {
+                        continue;
+                    }
+                    let mut attrs: Vec<Ast> = Vec::new();
+                    if ast.tokens[0].token_type != TokenType::Comment {
+                        attrs = std::mem::take(&mut attributes);
+                        let attr_problems = attribute::check(&attrs, &ast);
+                        self.problems.extend(attr_problems);
+                    }
+                    if attribute::has(&attrs, "test")
+                        && (ast.ast_type == AstType::FunctionDeceleration
+                            || ast.ast_type == AstType::VoidFunctionDeceleration)
+                    {
+                        // Tests only exist in the test configuration
+                        if !self.test {
+                            continue;
+                        }
+                        let name = ast.tokens[1].value.clone();
+                        let rname = variables.get_var(name.clone(), self);
+                        self.tests.push(TestCase {
+                            name,
+                            rname,
+                            returns_int: ast.ast_type == AstType::FunctionDeceleration,
+                        });
+                    }
                     if ast.ast_type == AstType::Other
                         && ast.tokens[0].token_type == TokenType::Identifier
                         && ast.tokens[0].value.contains(&self.peek)
@@ -80,16 +169,43 @@ impl Transpiler {
                                     var.state,
                                     var.desc.clone(),
                                 );
+                                if let Some(matched) = self.matched_vars.get_mut(name.to_string()) {
+                                    matched.deprecated = var.deprecated.clone();
+                                    matched.signature = var.signature.clone();
+                                    matched.fields = var.fields.clone();
+                                }
                             }
                         }
                         self.peek = String::new();
                         continue;
+                    } else if ast.ast_type == AstType::For {
+                        // The name of a for in loop is declared by transpile_for_in
+                    } else if ast.ast_type == AstType::Impl {
+                        // transpile_impl looks the struct up itself
+                    } else if ast.ast_type == AstType::TypeAlias {
+                        // The alias keeps its name and the parser already made its type rust
+                    } else if ast.ast_type == AstType::ConstDeceleration {
+                        // The value is already a number
+                        ast.tokens[1].value = variables.get_var(ast.tokens[1].value.clone(), self);
                     } else {
-                        for i in (if is_decl(&ast) { 1 } else { 0 })..ast.tokens.len() {
+                        for i in (if is_decl(&ast)
+                            || ast.ast_type == AstType::MethodCall
+                            || ast.ast_type == AstType::Intrinsic
+                        {
+                            1
+                        } else {
+                            0
+                        })..ast.tokens.len()
+                        {
                             if ast.tokens[i].token_type == TokenType::Identifier {
                                 if ast.tokens[i].value.contains(&self.peek) && self.peek != "" {}
+                                if !(is_decl(&ast) && i == 1) {
+                                    self.check_deprecated(&ast.tokens[i], variables);
+                                }
                                 let x = variables.get_var(ast.tokens[i].value.clone(), self);
                                 ast.tokens[i].value = x;
+                            } else if ast.tokens[i].token_type == TokenType::Number {
+                                ast.tokens[i].value = self.expand_literal(&ast.tokens[i]);
                             }
                         }
                     }
@@ -112,49 +228,90 @@ impl Transpiler {
                     last_ast = Ast {
                         ast_type: ast.ast_type.clone(),
                         tokens: ast.tokens.clone(),
+                        children: vec![],
                     };
+                    if self.coverage && indent > 0 {
+                        result += self.probe(&ast, &mut stmt_start).as_str();
+                    }
+                    let literal = collection.take();
+                    if ast.tokens[0].token_type == TokenType::Assign {
+                        collection = literal.clone();
+                    }
+                    if let Some(init) = uninit.take() {
+                        if ast.tokens[0].token_type != TokenType::Assign {
+                            result += init.as_str();
+                        }
+                    }
 
                     if ast.ast_type == AstType::FunctionDeceleration {
                         if self.auto_pub {
                             result += "pub ";
                         }
+                        if let Some(problem) = check_returns(&ast.tokens[1], &ast.tokens[3]) {
+                            self.problems.push(problem);
+                        }
+                        self.warnings.extend(check_unreachable(&ast.tokens[3]));
                         let mut vars: Variables = variables.clone();
-                        let round = self.transpile_round(ast.tokens[2].value.clone(), &mut vars);
+                        let round = self.transpile_round_group(&ast.tokens[2], &mut vars);
                         result += format!(
                             "fn {}({}) -> {} {}",
                             ast.tokens[1].value,
                             round,
                             ast.tokens[0].value,
-                            self.transpile(
-                                ast.tokens[3].value.clone(),
-                                indent + 1,
-                                &mut (vars.clone())
-                            )
+                            self.transpile_group(&ast.tokens[3], indent + 1, &mut (vars.clone()))
                         )
                         .as_str();
                     } else if ast.ast_type == AstType::VoidFunctionDeceleration {
                         if self.auto_pub {
                             result += "pub ";
                         }
+                        self.warnings.extend(check_unreachable(&ast.tokens[3]));
                         let mut vars: Variables = variables.clone();
-                        let round = self.transpile_round(ast.tokens[2].value.clone(), &mut vars);
+                        let round = self.transpile_round_group(&ast.tokens[2], &mut vars);
                         result += format!(
                             "fn {}({}) {}",
                             ast.tokens[1].value,
                             round,
-                            self.transpile(
-                                ast.tokens[3].value.clone(),
-                                indent + 1,
-                                &mut (vars.clone())
-                            )
+                            self.transpile_group(&ast.tokens[3], indent + 1, &mut (vars.clone()))
                         )
                         .as_str();
                     } else if ast.ast_type == AstType::StructDeceleration {
+                        let derives = attribute::derives(&attrs, &mut self.problems);
+                        let mut vars: Variables = variables.clone();
+                        let round = self.transpile_round_group(&ast.tokens[1], &mut vars);
+                        // What the body declared, a field can have the name of a global
+                        let mut fields: Vec<(String, &Variable)> = vars
+                            .vars
+                            .iter()
+                            .filter(|(name, var)| {
+                                variables
+                                    .vars
+                                    .get(*name)
+                                    .is_none_or(|global| global.state != var.state)
+                            })
+                            .map(|(name, var)| (name.clone(), var))
+                            .collect();
+                        fields.sort_by_key(|(_, var)| (var.state.line, var.state.column));
+                        let fields: Vec<(String, String)> = fields
+                            .into_iter()
+                            .map(|(name, var)| (name, var.rname.clone()))
+                            .collect();
+                        if derives.contains(&"json".to_string()) {
+                            self.writer.use_runtime("wyst_json");
+                        }
+                        let (before, after) = attribute::expand_derives(
+                            &ast.tokens[0].value,
+                            &derives,
+                            &fields,
+                            attribute::has(&attrs, "packed"),
+                        );
+                        result += before.as_str();
+                        if let Some(repr) = attribute::layout(&attrs, &mut self.problems) {
+                            result += repr.as_str();
+                        }
                         if self.auto_pub {
                             result += "pub ";
                         }
-                        let mut vars: Variables = variables.clone();
-                        let round = self.transpile_round(ast.tokens[1].value.clone(), &mut vars);
                         result += format!(
                             "struct {} {} {}",
                             ast.tokens[0].value,
@@ -168,6 +325,7 @@ impl Transpiler {
                         )
                         .as_str();
                         result += "\n}\n";
+                        result += after.as_str();
                         let vvars = variables.clone();
                         if let Some(v) = variables.get_mut(ast.tokens[0].value.clone()) {
                             for (name, var) in vars.iter_mut() {
@@ -179,8 +337,47 @@ impl Transpiler {
                                     );
                                 }
                             }
+                            // Generated methods point back at the struct they came from
+                            for derive in &derives {
+                                for method in attribute::derived_methods(derive) {
+                                    v.params.new_func(
+                                        method.to_string(),
+                                        v.state,
+                                        format!("Generated by @derive({})", derive),
+                                    );
+                                }
+                            }
                         }
                     } else if ast.ast_type == AstType::VariableDeceleration {
+                        // The parser leaves buffers whose length it couldn't work out as they were
+                        if let Some(args) = ast.tokens[0].value.strip_prefix("buffer<") {
+                            self.problems.push(Problem {
+                                problem_type: ProblemType::BufferLength,
+                                problem_msg: message(
+                                    "buffer_length",
+                                    &[&args.strip_suffix('>').unwrap_or(args)],
+                                ),
+                                state: LexerState {
+                                    line: ast.tokens[1].line,
+                                    column: ast.tokens[1].column,
+                                },
+                                file: self.file.clone(),
+                            });
+                        }
+                        collection = Some(ast.tokens[0].value.clone());
+                        // A buffer is a rust array, which has to be given its elements
+                        uninit = ast.tokens[0]
+                            .value
+                            .strip_prefix('[')
+                            .and_then(|array| array.strip_suffix(']'))
+                            .and_then(|array| array.rsplit_once("; "))
+                            .map(|(_, size)| format!(" = [Default::default(); {}]", size))
+                            // and a collection starts out empty
+                            .or_else(|| {
+                                receiver(&ast.tokens[0].value)
+                                    .filter(|ty| *ty == "vec" || *ty == "map")
+                                    .map(|_| " = Default::default()".to_string())
+                            });
                         if self.clone().auto_mut {
                             result +=
                                 format!("let mut {}: {}", ast.tokens[1].value, ast.tokens[0].value)
@@ -194,15 +391,25 @@ impl Transpiler {
                         result +=
                             format!("let mut {}: {}", ast.tokens[1].value, ast.tokens[0].value)
                                 .as_str();
+                    } else if ast.ast_type == AstType::ArrayDeceleration {
+                        let size = self.transpile_array_size(&ast.tokens[1], variables);
+                        uninit = Some(format!(" = [Default::default(); {}]", size));
+                        if self.auto_mut {
+                            result += "let mut ";
+                        } else {
+                            result += "let ";
+                        }
+                        result += format!(
+                            "{}: [{}; {}]",
+                            ast.tokens[2].value, ast.tokens[0].value, size
+                        )
+                        .as_str();
                     } else if ast.ast_type == AstType::Other
                         && ast.tokens[0].token_type == TokenType::Round
                     {
                         result += format!(
                             "({})",
-                            self.transpile_round(
-                                ast.tokens[0].value.clone(),
-                                &mut variables.clone()
-                            )
+                            self.transpile_round_group(&ast.tokens[0], &mut variables.clone())
                         )
                         .as_str();
                     } else if ast.ast_type == AstType::Other
@@ -210,24 +417,70 @@ impl Transpiler {
                     {
                         result += ast.tokens[0].value.clone().as_str();
                         result += " ".repeat((indent as usize) * 2).as_str();
+                    } else if ast.ast_type == AstType::StaticExecution {
+                        // A block that folds to a constant is replaced by its value
+                        let square = &ast.tokens[0];
+                        let folded = lex(&square.value, false, inner_state(square))
+                            .ok()
+                            .and_then(|tokens| const_value(&tokens, variables));
+                        match folded {
+                            Some(value) => result += value.to_string().as_str(),
+                            None => {
+                                result += r#"use serde::Deserialize;
+use serde_json::Value;
+#[derive(Deserialize, Debug)]
+struct Request(i32, String, Vec<(bool, Value)>);
+#[derive(Deserialize, Debug)]
+struct Response(i32, Vec<(bool, Value)>);
+"#
+                            }
+                        }
+                    } else if ast.ast_type == AstType::Index {
+                        result += self.transpile_index(&ast.tokens[0], variables).as_str();
                     } else if ast.tokens.len() == 1 && ast.tokens[0].token_type == TokenType::Square
                     {
+                        if let Some(ty) = literal.filter(|ty| receiver(ty) == Some("vec")) {
+                            result += "vec!";
+                            self.element = vec_element(&ty).map(str::to_string);
+                        }
                         result += format!(
                             "[{}]",
                             self.transpile_square(
                                 ast.tokens[0].value.clone(),
                                 LexerState {
                                     line: ast.tokens[0].line,
-                                    column: ast.tokens[0].column
+                                    column: ast.tokens[0].column + 1
                                 },
                                 variables.clone()
                             )
                         )
                         .as_str();
+                    } else if ast.ast_type == AstType::TypeAlias {
+                        result += format!("type {} = {}", ast.tokens[1].value, ast.tokens[2].value)
+                            .as_str();
+                    } else if ast.ast_type == AstType::ConstDeceleration {
+                        result += format!(
+                            "const {}: {} = {}",
+                            ast.tokens[1].value, ast.tokens[0].value, ast.tokens[2].value
+                        )
+                        .as_str();
                     } else if ast.ast_type == AstType::CodeBlock {
                         result += "{";
                         result += ast.tokens[0].value.as_str();
                         result += "}";
+                    } else if ast.ast_type == AstType::ArrayLiteral {
+                        result += format!(
+                            "[{}]",
+                            self.transpile_square(
+                                ast.tokens[0].value.clone(),
+                                LexerState {
+                                    line: ast.tokens[0].line,
+                                    column: ast.tokens[0].column + 1
+                                },
+                                variables.clone()
+                            )
+                        )
+                        .as_str();
                     } else if ast.tokens.len() == 1 && ast.tokens[0].token_type == TokenType::Curly
                     {
                         result += self
@@ -235,7 +488,7 @@ impl Transpiler {
                                 ast.tokens[0].value.as_str().to_string(),
                                 LexerState {
                                     line: ast.tokens[0].line,
-                                    column: ast.tokens[0].column,
+                                    column: ast.tokens[0].column + 1,
                                 },
                                 variables.clone(),
                             )
@@ -259,61 +512,35 @@ impl Transpiler {
                             "}"
                         )
                         .as_str();
-                    } else if ast.ast_type == AstType::Include {
-                        match self
-                            .writer
-                            .add(ast.tokens[0].value.clone(), variables, true)
-                        {
-                            Ok(modname) => {
-                                result += "mod ";
-                                result += modname.as_str();
-                                result += ";\n";
-                                result += "use ";
-                                result += modname.as_str();
-                                result += "::*;\n";
-                            }
-                            Err(ptype) => {
-                                self.problems.push(ptype);
-                            }
-                        }
-                    } else if ast.ast_type == AstType::IncludeLocal {
-                        match self
-                            .writer
-                            .add(ast.tokens[0].value.clone(), variables, false)
-                        {
-                            Ok(modname) => {
-                                result += "mod ";
-                                result += modname.as_str();
-                                result += ";\n";
-                                result += "use ";
-                                result += modname.as_str();
-                                result += "::*;\n";
-                            }
-                            Err(ptype) => {
-                                self.problems.push(ptype);
-                            }
-                        }
-                    } else if ast.ast_type == AstType::State3 {
+                    } else if ast.ast_type == AstType::For && ast.tokens.len() == 4 {
+                        result += self.transpile_for_in(&ast, indent, variables).as_str();
+                    } else if ast.ast_type == AstType::For {
+                        result += self.transpile_for(&ast, indent, variables).as_str();
+                    } else if ast.ast_type == AstType::If
+                        || ast.ast_type == AstType::ElseIf
+                        || ast.ast_type == AstType::While
+                    {
+                        let keyword = match ast.ast_type {
+                            AstType::If => "if",
+                            AstType::ElseIf => "else if",
+                            _ => "while",
+                        };
                         result += format!(
                             "{} {} {}",
-                            ast.tokens[0].value.clone(),
-                            self.transpile_round(
-                                ast.tokens[1].value.clone(),
-                                &mut variables.clone()
-                            ),
-                            self.transpile(
-                                ast.tokens[2].value.clone(),
+                            keyword,
+                            self.transpile_round_group(&ast.tokens[1], &mut variables.clone()),
+                            self.transpile_group(
+                                &ast.tokens[2],
                                 indent + 1,
                                 &mut variables.clone()
                             ),
                         )
                         .as_str();
-                    } else if ast.ast_type == AstType::State2 {
+                    } else if ast.ast_type == AstType::Else {
                         result += format!(
-                            "{} {}",
-                            ast.tokens[0].value.clone(),
-                            self.transpile(
-                                ast.tokens[1].value.clone(),
+                            "else {}",
+                            self.transpile_group(
+                                &ast.tokens[1],
                                 indent + 1,
                                 &mut variables.clone()
                             ),
@@ -324,19 +551,12 @@ impl Transpiler {
                             "mod {} {}{}{}",
                             &ast.tokens[0].value.clone(),
                             "{",
-                            self.transpile(ast.tokens[1].value.clone(), 0, &mut variables.clone()),
+                            self.transpile_group(&ast.tokens[1], 0, &mut variables.clone()),
                             "}"
                         )
                         .as_str();
                     } else if ast.ast_type == AstType::Impl {
-                        result += format!(
-                            "impl {} {}{}{}",
-                            &ast.tokens[0].value.clone(),
-                            "{",
-                            self.transpile(ast.tokens[1].value.clone(), 0, &mut variables.clone()),
-                            "}"
-                        )
-                        .as_str();
+                        result += self.transpile_impl(&ast, variables).as_str();
                     } else if ast.ast_type == AstType::PointerDeceleration {
                         if self.auto_mut {
                             result += format!(
@@ -351,14 +571,12 @@ impl Transpiler {
                             )
                             .as_str();
                         }
-                    } else if ast.ast_type == AstType::StaticExecution {
-                        result += r#"use serde::Deserialize;
-use serde_json::Value;
-#[derive(Deserialize, Debug)]
-struct Request(i32, String, Vec<(bool, Value)>);
-#[derive(Deserialize, Debug)]
-struct Response(i32, Vec<(bool, Value)>);
-"#;
+                    } else if ast.ast_type == AstType::Intrinsic {
+                        result += self
+                            .transpile_intrinsic(&ast, &mut variables.clone())
+                            .as_str();
+                    } else if ast.ast_type == AstType::MethodCall {
+                        result += self.transpile_method(&ast, &mut variables.clone()).as_str();
                     } else if ast.ast_type == AstType::Ref {
                         result += "&mut ";
                         result += ast.tokens[0].value.as_str();
@@ -390,6 +608,12 @@ struct Response(i32, Vec<(bool, Value)>);
                     result += " ".repeat((indent as usize - 1) * 2).as_str();
                     return "{\n".to_owned() + result.as_str() + "}";
                 } else {
+                    // The passes outside the transpiler don't know which file they looked at
+                    for problem in self.problems.iter_mut().chain(self.warnings.iter_mut()) {
+                        if problem.file.is_empty() {
+                            problem.file = self.file.clone();
+                        }
+                    }
                     return result;
                 }
             }
@@ -398,6 +622,47 @@ struct Response(i32, Vec<(bool, Value)>);
             }
         }
     }
+    /*Reports `what` when the playground's sandbox doesn't allow it*/
+    fn sandboxed(&mut self, what: &str, state: LexerState) -> bool {
+        if self.sandbox {
+            self.problems.push(Problem {
+                problem_type: ProblemType::Sandboxed,
+                problem_msg: message("sandboxed", &[&what]),
+                state,
+                file: self.file.clone(),
+            });
+        }
+        self.sandbox
+    }
+
+    fn transpile_include(&mut self, ast: &Ast, variables: &mut Variables) -> String {
+        let state = LexerState {
+            line: ast.tokens[0].line,
+            column: ast.tokens[0].column,
+        };
+        if self.sandboxed("#include", state) {
+            return String::new();
+        }
+        let global = ast.ast_type == AstType::Include;
+        let included = self.writer.add(
+            ast.tokens[0].value.clone(),
+            variables,
+            global,
+            state,
+            &self.file,
+        );
+        // What the included file and the ones it includes reported
+        self.problems.append(&mut self.writer.problems);
+        self.warnings.append(&mut self.writer.warnings);
+        match included {
+            Ok(modname) => format!("mod {};\nuse {}::*;\n", modname, modname),
+            Err(ptype) => {
+                self.problems.push(ptype);
+                String::new()
+            }
+        }
+    }
+
     pub fn transpile_mod(&mut self, ast: Ast, s: &str) -> String {
         let modfile = ast.tokens[0].value.as_str();
         let modname = format!(
@@ -414,18 +679,730 @@ struct Response(i32, Vec<(bool, Value)>);
         .expect("Error writing file");
         modname
     }
+    /*Counts the start of a statement for --coverage, stmt_start tracks whether the previous ast ended one*/
+    pub fn probe(&mut self, ast: &Ast, stmt_start: &mut bool) -> String {
+        let token = &ast.tokens[0];
+        if token.token_type == TokenType::Comment || token.token_type == TokenType::Newline {
+            return String::new();
+        }
+        let at_start = *stmt_start;
+        *stmt_start = token.token_type == TokenType::Semicolon
+            || ast.ast_type == AstType::For
+            || ast.ast_type == AstType::While
+            || ast.ast_type == AstType::If
+            || ast.ast_type == AstType::ElseIf
+            || ast.ast_type == AstType::Else
+            || ast.ast_type == AstType::CodeBlock;
+        // An else belongs to the if before it, nothing can go in between
+        if !at_start
+            || token.token_type == TokenType::Semicolon
+            || ast.ast_type == AstType::ElseIf
+            || ast.ast_type == AstType::Else
+        {
+            return String::new();
+        }
+        self.probes.push(token.line);
+        format!("crate::wyst_coverage::hit({});", self.probes.len() - 1)
+    }
+    // The call that writes the coverage report, it goes right before the program exits
+    pub fn coverage_report(&self) -> String {
+        let lines: Vec<String> = self.probes.iter().map(|line| line.to_string()).collect();
+        format!(
+            "crate::wyst_coverage::write_report(\"{}\", &[{}]);",
+            self.file,
+            lines.join(", ")
+        )
+    }
+    // Registers the report with exit() too, which ends the program before main can write it
+    pub fn exit_hooks(&self) -> String {
+        if !self.coverage
+            || !self
+                .writer
+                .runtime
+                .iter()
+                .any(|name| name == "wyst_process")
+        {
+            return String::new();
+        }
+        format!(
+            "crate::wyst_process::at_exit(|| {{{}}});",
+            self.coverage_report()
+        )
+    }
+    /*The first thing main does, so panics of the generated rust are reported like any other
+    failure. The table lets a backtrace name the wyst functions*/
+    pub fn panic_hooks(&mut self, variables: &Variables) -> String {
+        self.writer.use_runtime("wyst_panic");
+        let mut symbols: Vec<String> = variables
+            .vars
+            .iter()
+            .filter(|(_, var)| var.vtype == VariableType::Func)
+            .map(|(name, var)| {
+                let function = format!(
+                    "{} ({}:{}:{})",
+                    name, self.file, var.state.line, var.state.column
+                );
+                format!("({:?}, {:?})", var.rname, function)
+            })
+            .collect();
+        symbols.sort();
+        format!("crate::wyst_panic::install(&[{}]);", symbols.join(", "))
+    }
+    /*Lowers a call to one of the intrinsics, the Round token holds its arguments*/
+    pub fn transpile_intrinsic(&mut self, ast: &Ast, variables: &mut Variables) -> String {
+        let name = ast.tokens[0].value.as_str();
+        let args = split_args(&ast.tokens[1].value);
+        let location = format!(
+            "{}:{}:{}",
+            self.file, ast.tokens[0].line, ast.tokens[0].column
+        );
+        let state = LexerState {
+            line: ast.tokens[0].line,
+            column: ast.tokens[0].column,
+        };
+        if let Some(feature) = pragma::gate(name) {
+            if !self.features.iter().any(|enabled| enabled == feature) {
+                self.problems.push(Problem {
+                    problem_type: ProblemType::FeatureGate,
+                    problem_msg: message("feature_gated", &[&name, &feature]),
+                    state,
+                    file: self.file.clone(),
+                });
+                return String::new();
+            }
+        }
+        if !arity(name).contains(&args.len()) {
+            let expected = if arity(name).start() == arity(name).end() {
+                arity(name).start().to_string()
+            } else if *arity(name).end() == usize::MAX {
+                message("arity_at_least", &[arity(name).start()])
+            } else {
+                message("arity_range", &[arity(name).start(), arity(name).end()])
+            };
+            self.problems.push(Problem {
+                problem_type: ProblemType::IntrinsicArguments,
+                problem_msg: message("intrinsic_arity", &[&name, &expected, &args.len()]),
+                state,
+                file: self.file.clone(),
+            });
+            return String::new();
+        }
+        if (name == "embed!" || name == "env!") && self.sandboxed(name, state) {
+            return String::new();
+        }
+        if name == "embed!" {
+            return self.transpile_embed(&args[0], state);
+        }
+        if name == "env!" {
+            return self.transpile_env(&args, state);
+        }
+        if name == "spawn" {
+            return self.transpile_spawn(&args[0], state, variables);
+        }
+        // The operands that have to be integers, the count of a rotate can be any integer expression
+        let operands = if BIT_INTRINSICS.contains(&name) {
+            &args[..1]
+        } else if ARITH_INTRINSICS.contains(&name) {
+            &args[..]
+        } else {
+            &[]
+        };
+        for arg in operands {
+            // Names with a known type are checked here, anything else is left to rustc
+            let data_type = variables
+                .get_mut(arg.clone())
+                .and_then(|var| var.data_type.clone());
+            if let Some(data_type) = data_type.filter(|data_type| !is_integer(data_type)) {
+                self.problems.push(Problem {
+                    problem_type: ProblemType::IntrinsicArguments,
+                    problem_msg: message("bit_operand", &[&name, arg, &wyst_type(&data_type)]),
+                    state,
+                    file: self.file.clone(),
+                });
+                return String::new();
+            }
+        }
+        let rargs = self.transpile_args(&ast.tokens[1], &args, variables);
+        match name {
+            "assert" => {
+                self.writer.use_runtime("wyst_panic");
+                format!(
+                    "{}if !({}) {}crate::wyst_panic::fail({:?}, {:?}){}{}",
+                    "{",
+                    rargs[0],
+                    "{",
+                    format!("assertion failed: {}", args[0]),
+                    location,
+                    "}",
+                    "}"
+                )
+            }
+            "assert_eq" => {
+                self.writer.use_runtime("wyst_panic");
+                format!(
+                    "{}let (left, right) = (&({}), &({})); if left != right {}crate::wyst_panic::fail(&format!(\"assertion failed: {{}} == {{}}\\n   left: {{:?}}\\n  right: {{:?}}\", {:?}, {:?}, left, right), {:?}){}{}",
+                    "{", rargs[0], rargs[1], "{", args[0], args[1], location, "}", "}"
+                )
+            }
+            "panic" => {
+                self.writer.use_runtime("wyst_panic");
+                format!(
+                    "crate::wyst_panic::fail(&format!(\"panicked: {{}}\", {}), {:?})",
+                    rargs[0], location
+                )
+            }
+            // A thread that panicked has already printed why, so it stops the program like assert
+            "join" => format!(
+                "{}.join().unwrap_or_else(|_| std::process::exit(101))",
+                rargs[0]
+            ),
+            name if BIT_INTRINSICS.contains(&name) => bit_op(name, &rargs),
+            name if ARITH_INTRINSICS.contains(&name) => {
+                self.writer.use_runtime("wyst_panic");
+                arith_op(name, &rargs, &location)
+            }
+            "trace!" => self.transpile_trace(&args[0], &rargs[1..], &location, state),
+            // Borrowed so the value can still be used after it
+            "debug" => format!(
+                "eprintln!(\"[debug {{}}] {{}} = {{:#?}}\", {:?}, {:?}, &({}))",
+                location,
+                args[0].trim(),
+                rargs[0]
+            ),
+            "time::now" | "time::millis" => {
+                self.writer.use_runtime("wyst_time");
+                format!(
+                    "crate::wyst_time::{}({})",
+                    name.trim_start_matches("time::"),
+                    self.writer.deterministic
+                )
+            }
+            "random::int" => {
+                self.writer.use_runtime("wyst_random");
+                format!(
+                    "crate::wyst_random::int({}, {}, {})",
+                    self.writer.deterministic, rargs[0], rargs[1]
+                )
+            }
+            "random::seed" => {
+                self.writer.use_runtime("wyst_random");
+                format!("crate::wyst_random::seed({})", rargs[0])
+            }
+            // exit runs what main would on its way out, abort doesn't
+            "exit" | "abort" => {
+                self.writer.use_runtime("wyst_process");
+                format!("crate::wyst_process::{}({})", name, rargs.join(", "))
+            }
+            name if name.starts_with("fs::") || name.starts_with("env::") => {
+                let (module, _) = name.split_once("::").unwrap_or_default();
+                self.writer.use_runtime(&format!("wyst_{}", module));
+                format!("crate::wyst_{}({})", name, rargs.join(", "))
+            }
+            _ => String::new(),
+        }
+    }
+
+    /*Lowers `impl Name {...}` and registers every function in it as a method of the struct,
+    next to the ones @derive generates. Only a struct declared in the program can have one*/
+    fn transpile_impl(&mut self, ast: &Ast, variables: &mut Variables) -> String {
+        let name = ast.tokens[0].value.clone();
+        let is_struct = variables
+            .get_mut(name.clone())
+            .is_some_and(|var| var.vtype == VariableType::Struct);
+        if !is_struct {
+            let structs = variables
+                .vars
+                .iter()
+                .filter(|(_, var)| var.vtype == VariableType::Struct)
+                .map(|(name, _)| name.as_str());
+            self.problems.push(Problem {
+                problem_type: ProblemType::ImplTarget,
+                problem_msg: message("impl_target", &[&name, &did_you_mean(&name, structs)]),
+                state: LexerState {
+                    line: ast.tokens[0].line,
+                    column: ast.tokens[0].column,
+                },
+                file: self.file.clone(),
+            });
+            return String::new();
+        }
+        let mut vars = variables.clone();
+        // The receiver of a method keeps its rust name
+        vars.new_var(
+            "self".to_string(),
+            LexerState { line: 0, column: 0 },
+            String::new(),
+        );
+        if let Some(receiver) = vars.get_mut("self".to_string()) {
+            receiver.rname = "self".to_string();
+        }
+        let body = self.transpile_group(&ast.tokens[1], 0, &mut vars);
+        let methods: Vec<(String, Variable)> = vars
+            .vars
+            .into_iter()
+            .filter(|(name, var)| {
+                var.vtype == VariableType::Func && !variables.vars.contains_key(name)
+            })
+            .collect();
+        let target = variables.get_mut(name.clone()).unwrap();
+        for (method, var) in methods {
+            target.params.vars.insert(method, var);
+        }
+        // The struct keeps the name it was written with, see StructDeceleration
+        format!("impl {} {}{}{}", name, "{", body, "}")
+    }
+
+    /*Transpiles the arguments split_args took out of a group. Each one is lexed from where it is
+    in the group, so its problems point at the right line and column*/
+    fn transpile_args(
+        &mut self,
+        group: &Token,
+        args: &[String],
+        variables: &mut Variables,
+    ) -> Vec<String> {
+        let outer = self.state;
+        let mut rest = 0;
+        let mut rargs = Vec::new();
+        for arg in args {
+            let start = rest + group.value[rest..].find(arg.as_str()).unwrap_or(0);
+            rest = start + arg.len();
+            self.state = advance(inner_state(group), &group.value[..start]);
+            rargs.push(self.transpile_round(arg.clone(), &mut variables.clone()));
+        }
+        self.state = outer;
+        rargs
+    }
+
+    /*Lowers `value.method(args)`. The methods on string go to the wyst_string runtime,
+    anything else is called the way it's written*/
+    pub fn transpile_method(&mut self, ast: &Ast, variables: &mut Variables) -> String {
+        let ident = ast.tokens[0].value.clone();
+        let args = split_args(&ast.tokens[1].value);
+        let rargs = self.transpile_args(&ast.tokens[1], &args, variables);
+        let (base, method) = ident.split_once('.').unwrap_or((&ident, ""));
+        let receiver = variables
+            .get_mut(base.to_string())
+            .and_then(|var| var.data_type.as_deref().and_then(receiver));
+        let receiver = match receiver {
+            Some(receiver) => receiver,
+            None => {
+                if let Some(rmethod) = variables.method(base, method) {
+                    let rname = variables.get_var(base.to_string(), self);
+                    return format!("{}.{}({})", rname, rmethod, rargs.join(", "));
+                }
+                self.check_deprecated(&ast.tokens[0], variables);
+                let name = variables.get_var(ident.clone(), self);
+                return format!("{}({})", name, rargs.join(", "));
+            }
+        };
+        let state = LexerState {
+            line: ast.tokens[0].line,
+            column: ast.tokens[0].column,
+        };
+        let expected = match method_arity(receiver, method) {
+            Some(expected) => expected,
+            None => {
+                self.problems.push(Problem {
+                    problem_type: ProblemType::UnknownMethod,
+                    problem_msg: message(
+                        "unknown_method",
+                        &[&receiver, &method, &methods(receiver).join(", ")],
+                    ),
+                    state,
+                    file: self.file.clone(),
+                });
+                return String::new();
+            }
+        };
+        if args.len() != expected {
+            self.problems.push(Problem {
+                problem_type: ProblemType::IntrinsicArguments,
+                problem_msg: message(
+                    "method_arity",
+                    &[&receiver, &method, &expected, &args.len()],
+                ),
+                state,
+                file: self.file.clone(),
+            });
+            return String::new();
+        }
+        let rname = variables.get_var(base.to_string(), self);
+        let (runtime, function, mut call_args) = if receiver == "string" {
+            ("wyst_string", method.to_string(), vec![rname])
+        } else {
+            // Collections are passed by reference so push and insert can change them
+            (
+                "wyst_collections",
+                format!("{}_{}", receiver, method),
+                vec![format!("&mut {}", rname)],
+            )
+        };
+        self.writer.use_runtime(runtime);
+        call_args.extend(rargs);
+        format!("crate::{}::{}({})", runtime, function, call_args.join(", "))
+    }
+
+    /*for (x in xs) walks a vec by value or a map's keys, without moving the collection*/
+    fn transpile_for_in(&mut self, ast: &Ast, indent: u32, variables: &mut Variables) -> String {
+        let name = ast.tokens[1].value.clone();
+        let iterable = ast.tokens[2].value.trim().to_string();
+        let mut vars = variables.clone();
+        let data_type = vars
+            .get_mut(iterable.clone())
+            .and_then(|var| var.data_type.clone());
+        let (iter, item_type) = match data_type.as_deref().and_then(receiver) {
+            Some("vec") => (
+                ".iter().cloned()",
+                type_args(data_type.as_deref().unwrap()).pop(),
+            ),
+            Some("map") => (
+                ".keys().cloned()",
+                type_args(data_type.as_deref().unwrap()).first().cloned(),
+            ),
+            _ => ("", None),
+        };
+        let iterable = self.transpile_round_group(&ast.tokens[2], &mut vars.clone());
+        vars.new_var(
+            name.clone(),
+            LexerState {
+                line: ast.tokens[1].line,
+                column: ast.tokens[1].column,
+            },
+            String::new(),
+        );
+        if let Some(var) = vars.get_mut(name.clone()) {
+            var.data_type = item_type;
+        }
+        let rname = vars.get_var(name, self);
+        format!(
+            "for {} in {}{} {}",
+            rname,
+            iterable,
+            iter,
+            self.transpile_group(&ast.tokens[3], indent + 1, &mut vars)
+        )
+    }
+
+    /*Lowers `for (init; condition; step)` to a loop that runs the step before every check but
+    the first one, so a continue in the body still gets to the step*/
+    fn transpile_for(&mut self, ast: &Ast, indent: u32, variables: &mut Variables) -> String {
+        let mut vars = variables.clone();
+        let mut part = |trsp: &mut Transpiler, token: &Token| {
+            if token.value.trim().is_empty() {
+                String::new()
+            } else {
+                trsp.transpile_group(token, 0, &mut vars)
+            }
+        };
+        let init = part(self, &ast.tokens[1]);
+        let step = part(self, &ast.tokens[3]);
+        // Without a condition there's no check, so rustc knows only a break leaves the loop
+        let check = if ast.tokens[2].value.trim().is_empty() {
+            String::new()
+        } else {
+            let condition = self.transpile_round_group(&ast.tokens[2], &mut vars.clone());
+            format!("if !({}) {{break;}} ", condition)
+        };
+        let body = self.transpile_group(&ast.tokens[4], indent + 1, &mut vars.clone());
+        format!(
+            "{{{}; let mut wyst_started = false; loop {{if wyst_started {{{};}} wyst_started = true; {}{}}}}}",
+            init, step, check, body
+        )
+    }
+
+    /*Expands a literal like 4kb with the suffix table at compile time. Rust's own suffixes such
+    as 4u8 are left for rustc*/
+    fn expand_literal(&mut self, token: &Token) -> String {
+        let (number, suffix) = split_literal(&token.value);
+        if suffix.is_empty() || RUST_SUFFIXES.contains(&suffix) {
+            return token.value.clone();
+        }
+        let state = LexerState {
+            line: token.line,
+            column: token.column,
+        };
+        let factor = match self.writer.suffixes.get(suffix) {
+            Some(factor) => *factor,
+            None => {
+                let mut known: Vec<&String> = self.writer.suffixes.keys().collect();
+                known.sort();
+                self.problems.push(Problem {
+                    problem_type: ProblemType::LiteralSuffix,
+                    problem_msg: message(
+                        "unknown_suffix",
+                        &[
+                            &suffix,
+                            &token.value,
+                            &known
+                                .iter()
+                                .map(|name| name.as_str())
+                                .collect::<Vec<&str>>()
+                                .join(", "),
+                        ],
+                    ),
+                    state,
+                    file: self.file.clone(),
+                });
+                return number.to_string();
+            }
+        };
+        match number
+            .parse::<i64>()
+            .ok()
+            .and_then(|number| number.checked_mul(factor))
+            .filter(|value| *value <= i32::MAX as i64)
+        {
+            Some(value) => value.to_string(),
+            None => {
+                self.problems.push(Problem {
+                    problem_type: ProblemType::LiteralSuffix,
+                    problem_msg: message("suffix_overflow", &[&token.value]),
+                    state,
+                    file: self.file.clone(),
+                });
+                number.to_string()
+            }
+        }
+    }
+
+    // Warns when the identifier names something marked @deprecated
+    fn check_deprecated(&mut self, token: &Token, variables: &mut Variables) {
+        let note = match variables.get_mut(token.value.clone()) {
+            Some(var) => match &var.deprecated {
+                Some(note) => note.clone(),
+                None => return,
+            },
+            None => return,
+        };
+        self.warnings.push(Problem {
+            problem_type: ProblemType::Deprecated,
+            problem_msg: message("deprecated", &[&token.value, &note]),
+            state: LexerState {
+                line: token.line,
+                column: token.column,
+            },
+            file: self.file.clone(),
+        });
+    }
+
+    /*spawn(worker) runs an int function on a new thread. Functions can't capture anything, so
+    only what is Send in rust can cross over and rustc checks the rest*/
+    fn transpile_spawn(
+        &mut self,
+        arg: &str,
+        state: LexerState,
+        variables: &mut Variables,
+    ) -> String {
+        let is_worker = variables.get_mut(arg.to_string()).is_some_and(|var| {
+            var.vtype == VariableType::Func
+                && var.data_type.as_deref() == Some("i32")
+                && var.signature.is_empty()
+        });
+        if !is_worker {
+            self.problems.push(Problem {
+                problem_type: ProblemType::SpawnTarget,
+                problem_msg: message("spawn_target", &[&arg]),
+                state,
+                file: self.file.clone(),
+            });
+            return String::new();
+        }
+        self.check_deprecated(
+            &Token {
+                token_type: TokenType::Identifier,
+                value: arg.to_string(),
+                line: state.line,
+                column: state.column,
+            },
+            variables,
+        );
+        format!(
+            "::std::thread::spawn({})",
+            variables.get_var(arg.to_string(), self)
+        )
+    }
+
+    /*embed!("file") becomes the file's bytes, the path is relative to the file being transpiled*/
+    fn transpile_embed(&mut self, arg: &str, state: LexerState) -> String {
+        let path = match string_literal(arg) {
+            Some(path) => path,
+            None => {
+                self.problems.push(Problem {
+                    problem_type: ProblemType::IntrinsicArguments,
+                    problem_msg: message("embed_literal", &[]),
+                    state,
+                    file: self.file.clone(),
+                });
+                return String::new();
+            }
+        };
+        let dir = Path::new(&self.file).parent().unwrap_or(Path::new(""));
+        match fs::read(dir.join(&path)) {
+            Ok(bytes) => format!("include_bytes!({:?})", self.writer.embed(bytes)),
+            Err(err) => {
+                self.problems.push(Problem {
+                    problem_type: ProblemType::FileNotFound,
+                    problem_msg: message("embed_failed", &[&dir.join(&path).display(), &err]),
+                    state,
+                    file: self.file.clone(),
+                });
+                String::new()
+            }
+        }
+    }
+
+    /*Lowers trace! to an eprintln! with the location in front. When the profile turns tracing off
+    nothing is left, so the arguments aren't evaluated either. They're still transpiled first, so
+    a release build reports the same errors*/
+    fn transpile_trace(
+        &mut self,
+        format: &str,
+        rargs: &[String],
+        location: &str,
+        state: LexerState,
+    ) -> String {
+        let Some(format) = string_literal(format) else {
+            self.problems.push(Problem {
+                problem_type: ProblemType::IntrinsicArguments,
+                problem_msg: message("trace_literal", &[]),
+                state,
+                file: self.file.clone(),
+            });
+            return String::new();
+        };
+        if !self.writer.trace {
+            return String::new();
+        }
+        let mut out = format!("eprintln!({:?}", format!("[trace {}] {}", location, format));
+        for arg in rargs {
+            out += format!(", {}", arg).as_str();
+        }
+        out + ")"
+    }
+
+    /*env!("NAME") and env!("NAME", "default") become the variable's value when wyst runs*/
+    fn transpile_env(&mut self, args: &[String], state: LexerState) -> String {
+        let literals: Vec<Option<String>> = args.iter().map(|arg| string_literal(arg)).collect();
+        if literals.iter().any(|literal| literal.is_none()) {
+            self.problems.push(Problem {
+                problem_type: ProblemType::IntrinsicArguments,
+                problem_msg: message("env_literal", &[]),
+                state,
+                file: self.file.clone(),
+            });
+            return String::new();
+        }
+        let name = literals[0].clone().unwrap_or_default();
+        match (std::env::var(&name), literals.get(1)) {
+            (Ok(value), _) => format!("{:?}", value),
+            (Err(_), Some(Some(default))) => format!("{:?}", default),
+            (Err(_), _) => {
+                self.problems.push(Problem {
+                    problem_type: ProblemType::IntrinsicArguments,
+                    problem_msg: message("env_unset", &[&name]),
+                    state,
+                    file: self.file.clone(),
+                });
+                String::new()
+            }
+        }
+    }
+
+    /*The nodes the parser made, or none when some didn't parse, those are reported instead. A
+    Return is handed back as the keyword followed by the nodes of its expression*/
+    fn parsed(&mut self, parser: &mut Parser) -> Vec<Ast> {
+        match parser.parse() {
+            Ok(asts) => asts
+                .into_iter()
+                .flat_map(|ast| {
+                    if ast.ast_type != AstType::Return {
+                        return vec![ast];
+                    }
+                    let keyword = Ast {
+                        tokens: vec![ast.tokens[0].clone()],
+                        ast_type: AstType::Other,
+                        children: vec![],
+                    };
+                    std::iter::once(keyword).chain(ast.children).collect()
+                })
+                .collect(),
+            Err(errors) => {
+                for error in errors {
+                    self.problems.push(Problem {
+                        problem_type: ProblemType::SyntaxError,
+                        problem_msg: error.message,
+                        state: error.state,
+                        file: self.file.clone(),
+                    });
+                }
+                vec![]
+            }
+        }
+    }
+
+    // The length of an array, a usize for rust, so the consts in it are folded into a number
+    fn transpile_array_size(&mut self, square: &Token, variables: &Variables) -> String {
+        let folded = lex(&square.value, false, inner_state(square))
+            .ok()
+            .and_then(|tokens| const_value(&tokens, &mut variables.clone()));
+        match folded {
+            Some(size) => size.to_string(),
+            None => {
+                self.transpile_square(square.value.clone(), inner_state(square), variables.clone())
+            }
+        }
+    }
+
+    // Rust indexes arrays with a usize, wyst with any int
+    fn transpile_index(&mut self, square: &Token, variables: &Variables) -> String {
+        let index = self.transpile_square(
+            square.value.clone(),
+            LexerState {
+                line: square.line,
+                column: square.column + 1,
+            },
+            variables.clone(),
+        );
+        format!("[({}) as usize]", index)
+    }
+
+    /*Transpiles the text inside a group token with the lexer starting where the text does, so the
+    tokens in it keep their place in the file instead of counting from the bracket*/
+    pub fn transpile_group(
+        &mut self,
+        group: &Token,
+        indent: u32,
+        variables: &mut Variables,
+    ) -> String {
+        let outer = std::mem::replace(&mut self.state, inner_state(group));
+        let result = self.transpile(group.value.clone(), indent, variables);
+        self.state = outer;
+        result
+    }
+
+    pub fn transpile_round_group(&mut self, group: &Token, variables: &mut Variables) -> String {
+        let outer = std::mem::replace(&mut self.state, inner_state(group));
+        let result = self.transpile_round(group.value.clone(), variables);
+        self.state = outer;
+        result
+    }
+
     pub fn transpile_round(&mut self, input: String, variables: &mut Variables) -> String {
         let mut result = String::new();
         let lexer_out = lex(input.as_str(), false, self.state);
         match lexer_out {
             Ok(tokens) => {
                 let mut full_ast = Parser::new(tokens.clone(), variables.clone());
+                full_ast.suffixes = self.writer.suffixes.clone();
                 let mut last_ast = Ast {
                     ast_type: AstType::Other,
                     tokens: vec![],
+                    children: vec![],
                 };
-                let fast = full_ast.parse();
+                let fast = self.parsed(&mut full_ast);
                 *variables = full_ast.variables.clone();
+                let index_problems = check_indexes(&fast, variables);
+                self.problems.extend(index_problems);
                 for ast in fast {
                     let mut ast = ast;
                     if ast.ast_type == AstType::Other
@@ -445,16 +1422,33 @@ struct Response(i32, Vec<(bool, Value)>);
                                     var.state,
                                     var.desc.clone(),
                                 );
+                                if let Some(matched) = self.matched_vars.get_mut(name.to_string()) {
+                                    matched.deprecated = var.deprecated.clone();
+                                    matched.signature = var.signature.clone();
+                                }
                             }
                         }
                         self.peek = String::new();
                         continue;
                     } else {
-                        for i in (if is_decl(&ast) { 1 } else { 0 })..ast.tokens.len() {
+                        for i in (if is_decl(&ast)
+                            || ast.ast_type == AstType::MethodCall
+                            || ast.ast_type == AstType::Intrinsic
+                        {
+                            1
+                        } else {
+                            0
+                        })..ast.tokens.len()
+                        {
                             if ast.tokens[i].token_type == TokenType::Identifier {
                                 if ast.tokens[i].value.contains(&self.peek) && self.peek != "" {}
+                                if !(is_decl(&ast) && i == 1) {
+                                    self.check_deprecated(&ast.tokens[i], variables);
+                                }
                                 let x = variables.get_var(ast.tokens[i].value.clone(), self);
                                 ast.tokens[i].value = x;
+                            } else if ast.tokens[i].token_type == TokenType::Number {
+                                ast.tokens[i].value = self.expand_literal(&ast.tokens[i]);
                             }
                         }
                     }
@@ -473,6 +1467,10 @@ struct Response(i32, Vec<(bool, Value)>);
                                     var.state,
                                     var.desc.clone(),
                                 );
+                                if let Some(matched) = self.matched_vars.get_mut(name.to_string()) {
+                                    matched.deprecated = var.deprecated.clone();
+                                    matched.signature = var.signature.clone();
+                                }
                             }
                         }
                         self.peek = String::new();
@@ -497,6 +1495,7 @@ struct Response(i32, Vec<(bool, Value)>);
                     last_ast = Ast {
                         ast_type: ast.ast_type.clone(),
                         tokens: ast.tokens.clone(),
+                        children: vec![],
                     };
                     if ast.ast_type == AstType::VariableDeceleration {
                         result +=
@@ -507,16 +1506,22 @@ struct Response(i32, Vec<(bool, Value)>);
                     } else if ast.ast_type == AstType::PointerDeceleration {
                         result += format!("{}: &mut {}", ast.tokens[1].value, ast.tokens[0].value)
                             .as_str();
+                    } else if ast.ast_type == AstType::ArrayDeceleration {
+                        let size = self.transpile_array_size(&ast.tokens[1], variables);
+                        result += format!(
+                            "{}: [{}; {}]",
+                            ast.tokens[2].value, ast.tokens[0].value, size
+                        )
+                        .as_str();
                     } else if ast.tokens.len() == 1 && ast.tokens[0].token_type == TokenType::Round
                     {
                         result += format!(
                             "({})",
-                            self.transpile_round(
-                                ast.tokens[0].value.clone(),
-                                &mut variables.clone()
-                            )
+                            self.transpile_round_group(&ast.tokens[0], &mut variables.clone())
                         )
                         .as_str();
+                    } else if ast.ast_type == AstType::Index {
+                        result += self.transpile_index(&ast.tokens[0], variables).as_str();
                     } else if ast.tokens.len() == 1 && ast.tokens[0].token_type == TokenType::Square
                     {
                         result += format!(
@@ -525,7 +1530,7 @@ struct Response(i32, Vec<(bool, Value)>);
                                 ast.tokens[0].value.clone(),
                                 LexerState {
                                     line: ast.tokens[0].line,
-                                    column: ast.tokens[0].column
+                                    column: ast.tokens[0].column + 1
                                 },
                                 variables.clone()
                             )
@@ -540,7 +1545,7 @@ struct Response(i32, Vec<(bool, Value)>);
                                 ast.tokens[0].value.as_str().to_string(),
                                 LexerState {
                                     line: ast.tokens[0].line,
-                                    column: ast.tokens[0].column,
+                                    column: ast.tokens[0].column + 1,
                                 },
                                 variables.clone(),
                             )
@@ -550,6 +1555,12 @@ struct Response(i32, Vec<(bool, Value)>);
                         result += " {";
                         result += ast.tokens[1].value.as_str();
                         result += "}";
+                    } else if ast.ast_type == AstType::Intrinsic {
+                        result += self
+                            .transpile_intrinsic(&ast, &mut variables.clone())
+                            .as_str();
+                    } else if ast.ast_type == AstType::MethodCall {
+                        result += self.transpile_method(&ast, &mut variables.clone()).as_str();
                     } else if ast.ast_type == AstType::Ref {
                         result += "&mut ";
                         result += ast.tokens[0].value.as_str();
@@ -576,16 +1587,22 @@ struct Response(i32, Vec<(bool, Value)>);
         variables: Variables,
     ) -> String {
         let mut result = String::new();
+        // Only the literals right inside this one are elements
+        let element = self.element.take();
         let lexer_out = lex(input.as_str(), false, state);
         match lexer_out {
             Ok(tokens) => {
                 let mut full_ast = Parser::new(tokens.clone(), variables.clone());
+                full_ast.suffixes = self.writer.suffixes.clone();
                 let mut last_ast = Ast {
                     ast_type: AstType::Other,
                     tokens: vec![],
+                    children: vec![],
                 };
-                let fast = full_ast.parse();
+                let fast = self.parsed(&mut full_ast);
                 let mut variables = full_ast.variables.clone();
+                let index_problems = check_indexes(&fast, &mut variables);
+                self.problems.extend(index_problems);
                 for ast in fast {
                     let mut ast = ast;
                     if ast.ast_type == AstType::Other
@@ -605,16 +1622,33 @@ struct Response(i32, Vec<(bool, Value)>);
                                     var.state,
                                     var.desc.clone(),
                                 );
+                                if let Some(matched) = self.matched_vars.get_mut(name.to_string()) {
+                                    matched.deprecated = var.deprecated.clone();
+                                    matched.signature = var.signature.clone();
+                                }
                             }
                         }
                         self.peek = String::new();
                         continue;
                     } else {
-                        for i in (if is_decl(&ast) { 1 } else { 0 })..ast.tokens.len() {
+                        for i in (if is_decl(&ast)
+                            || ast.ast_type == AstType::MethodCall
+                            || ast.ast_type == AstType::Intrinsic
+                        {
+                            1
+                        } else {
+                            0
+                        })..ast.tokens.len()
+                        {
                             if ast.tokens[i].token_type == TokenType::Identifier {
                                 if ast.tokens[i].value.contains(&self.peek) && self.peek != "" {}
+                                if !(is_decl(&ast) && i == 1) {
+                                    self.check_deprecated(&ast.tokens[i], &mut variables);
+                                }
                                 let x = variables.get_var(ast.tokens[i].value.clone(), self);
                                 ast.tokens[i].value = x;
+                            } else if ast.tokens[i].token_type == TokenType::Number {
+                                ast.tokens[i].value = self.expand_literal(&ast.tokens[i]);
                             }
                         }
                     }
@@ -633,6 +1667,10 @@ struct Response(i32, Vec<(bool, Value)>);
                                     var.state,
                                     var.desc.clone(),
                                 );
+                                if let Some(matched) = self.matched_vars.get_mut(name.to_string()) {
+                                    matched.deprecated = var.deprecated.clone();
+                                    matched.signature = var.signature.clone();
+                                }
                             }
                         }
                         self.peek = String::new();
@@ -657,6 +1695,7 @@ struct Response(i32, Vec<(bool, Value)>);
                     last_ast = Ast {
                         ast_type: ast.ast_type.clone(),
                         tokens: ast.tokens.clone(),
+                        children: vec![],
                     };
 
                     if ast.ast_type == AstType::VariableDeceleration {
@@ -669,21 +1708,26 @@ struct Response(i32, Vec<(bool, Value)>);
                     {
                         result += format!(
                             "({})",
-                            self.transpile_round(
-                                ast.tokens[0].value.clone(),
-                                &mut variables.clone()
-                            )
+                            self.transpile_round_group(&ast.tokens[0], &mut variables.clone())
                         )
                         .as_str();
+                    } else if ast.ast_type == AstType::Index {
+                        result += self.transpile_index(&ast.tokens[0], &variables).as_str();
                     } else if ast.tokens.len() == 1 && ast.tokens[0].token_type == TokenType::Square
                     {
+                        if let Some(ty) =
+                            element.as_deref().filter(|ty| receiver(ty) == Some("vec"))
+                        {
+                            result += "vec!";
+                            self.element = vec_element(ty).map(str::to_string);
+                        }
                         result += format!(
                             "[{}]",
                             self.transpile_square(
                                 ast.tokens[0].value.clone(),
                                 LexerState {
                                     line: ast.tokens[0].line,
-                                    column: ast.tokens[0].column
+                                    column: ast.tokens[0].column + 1
                                 },
                                 variables.clone()
                             )
@@ -698,7 +1742,7 @@ struct Response(i32, Vec<(bool, Value)>);
                                 ast.tokens[0].value.as_str().to_string(),
                                 LexerState {
                                     line: ast.tokens[0].line,
-                                    column: ast.tokens[0].column,
+                                    column: ast.tokens[0].column + 1,
                                 },
                                 variables.clone(),
                             )
@@ -708,6 +1752,12 @@ struct Response(i32, Vec<(bool, Value)>);
                         result += " {";
                         result += ast.tokens[1].value.as_str();
                         result += "}";
+                    } else if ast.ast_type == AstType::Intrinsic {
+                        result += self
+                            .transpile_intrinsic(&ast, &mut variables.clone())
+                            .as_str();
+                    } else if ast.ast_type == AstType::MethodCall {
+                        result += self.transpile_method(&ast, &mut variables.clone()).as_str();
                     } else if ast.ast_type == AstType::Ref {
                         result += "&mut ";
                         result += ast.tokens[0].value.as_str();
@@ -738,13 +1788,15 @@ struct Response(i32, Vec<(bool, Value)>);
         match lexer_out {
             Ok(tokens) => {
                 let mut full_ast = Parser::new(tokens.clone(), variables.clone());
+                full_ast.suffixes = self.writer.suffixes.clone();
                 full_ast.json = true;
-                result += "HashMap::from([";
+                result += format!("{}::from([", MAP_TYPE).as_str();
                 let mut last_ast = Ast {
                     ast_type: AstType::Other,
                     tokens: vec![],
+                    children: vec![],
                 };
-                let fast = full_ast.parse();
+                let fast = self.parsed(&mut full_ast);
                 let mut variables = full_ast.variables.clone();
                 for ast in fast {
                     let mut ast = ast;
@@ -765,16 +1817,33 @@ struct Response(i32, Vec<(bool, Value)>);
                                     var.state,
                                     var.desc.clone(),
                                 );
+                                if let Some(matched) = self.matched_vars.get_mut(name.to_string()) {
+                                    matched.deprecated = var.deprecated.clone();
+                                    matched.signature = var.signature.clone();
+                                }
                             }
                         }
                         self.peek = String::new();
                         continue;
                     } else {
-                        for i in (if is_decl(&ast) { 1 } else { 0 })..ast.tokens.len() {
+                        for i in (if is_decl(&ast)
+                            || ast.ast_type == AstType::MethodCall
+                            || ast.ast_type == AstType::Intrinsic
+                        {
+                            1
+                        } else {
+                            0
+                        })..ast.tokens.len()
+                        {
                             if ast.tokens[i].token_type == TokenType::Identifier {
                                 if ast.tokens[i].value.contains(&self.peek) && self.peek != "" {}
+                                if !(is_decl(&ast) && i == 1) {
+                                    self.check_deprecated(&ast.tokens[i], &mut variables);
+                                }
                                 let x = variables.get_var(ast.tokens[i].value.clone(), self);
                                 ast.tokens[i].value = x;
+                            } else if ast.tokens[i].token_type == TokenType::Number {
+                                ast.tokens[i].value = self.expand_literal(&ast.tokens[i]);
                             }
                         }
                     }
@@ -793,6 +1862,10 @@ struct Response(i32, Vec<(bool, Value)>);
                                     var.state,
                                     var.desc.clone(),
                                 );
+                                if let Some(matched) = self.matched_vars.get_mut(name.to_string()) {
+                                    matched.deprecated = var.deprecated.clone();
+                                    matched.signature = var.signature.clone();
+                                }
                             }
                         }
                         self.peek = String::new();
@@ -817,6 +1890,7 @@ struct Response(i32, Vec<(bool, Value)>);
                     last_ast = Ast {
                         ast_type: ast.ast_type.clone(),
                         tokens: ast.tokens.clone(),
+                        children: vec![],
                     };
 
                     if ast.ast_type == AstType::Json {
@@ -829,12 +1903,11 @@ struct Response(i32, Vec<(bool, Value)>);
                     {
                         result += format!(
                             "({})",
-                            self.transpile_round(
-                                ast.tokens[0].value.clone(),
-                                &mut variables.clone()
-                            )
+                            self.transpile_round_group(&ast.tokens[0], &mut variables.clone())
                         )
                         .as_str();
+                    } else if ast.ast_type == AstType::Index {
+                        result += self.transpile_index(&ast.tokens[0], &variables).as_str();
                     } else if ast.tokens.len() == 1 && ast.tokens[0].token_type == TokenType::Square
                     {
                         result += format!(
@@ -843,7 +1916,7 @@ struct Response(i32, Vec<(bool, Value)>);
                                 ast.tokens[0].value.clone(),
                                 LexerState {
                                     line: ast.tokens[0].line,
-                                    column: ast.tokens[0].column
+                                    column: ast.tokens[0].column + 1
                                 },
                                 variables.clone()
                             )
@@ -856,11 +1929,17 @@ struct Response(i32, Vec<(bool, Value)>);
                                 ast.tokens[0].value.as_str().to_string(),
                                 LexerState {
                                     line: ast.tokens[0].line,
-                                    column: ast.tokens[0].column,
+                                    column: ast.tokens[0].column + 1,
                                 },
                                 variables.clone(),
                             )
                             .as_str();
+                    } else if ast.ast_type == AstType::Intrinsic {
+                        result += self
+                            .transpile_intrinsic(&ast, &mut variables.clone())
+                            .as_str();
+                    } else if ast.ast_type == AstType::MethodCall {
+                        result += self.transpile_method(&ast, &mut variables.clone()).as_str();
                     } else if ast.ast_type == AstType::Ref {
                         result += "&mut ";
                         result += ast.tokens[0].value.as_str();
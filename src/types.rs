@@ -0,0 +1,113 @@
+use crate::{
+    lexer::{MAP_TYPE, STRING_TYPE, THREAD_TYPE, VEC_TYPE},
+    messages::message,
+};
+use std::fmt;
+
+// A type as it's written, `map<string, vec<int>>` is map with the arguments string and vec<int>
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeNode {
+    pub name: String,
+    pub args: Vec<TypeNode>,
+}
+
+// How many arguments the builtin generics take, other names aren't checked
+fn arity(name: &str) -> Option<usize> {
+    match name {
+        "vec" => Some(1),
+        "map" => Some(2),
+        "int" | "float" | "string" | "thread" => Some(0),
+        _ => None,
+    }
+}
+
+impl TypeNode {
+    /*Parses a type like "map<string, vec<int>>" into its tree. The error is the message for a
+    type that isn't well formed or gives a builtin the wrong number of arguments*/
+    pub fn parse(text: &str) -> Result<TypeNode, String> {
+        let mut rest = text;
+        let node = TypeNode::parse_from(text, &mut rest)?;
+        if !rest.trim().is_empty() {
+            return Err(message("type_syntax", &[&text.trim()]));
+        }
+        Ok(node)
+    }
+
+    fn parse_from(text: &str, rest: &mut &str) -> Result<TypeNode, String> {
+        let bad = || message("type_syntax", &[&text.trim()]);
+        *rest = rest.trim_start();
+        let end = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == ':'))
+            .unwrap_or(rest.len());
+        if end == 0 {
+            return Err(bad());
+        }
+        let mut node = TypeNode {
+            name: rest[..end].to_string(),
+            args: Vec::new(),
+        };
+        *rest = rest[end..].trim_start();
+        if let Some(after) = rest.strip_prefix('<') {
+            *rest = after;
+            loop {
+                node.args.push(TypeNode::parse_from(text, rest)?);
+                *rest = rest.trim_start();
+                if let Some(after) = rest.strip_prefix(',') {
+                    *rest = after;
+                } else if let Some(after) = rest.strip_prefix('>') {
+                    *rest = after;
+                    break;
+                } else {
+                    return Err(bad());
+                }
+            }
+        }
+        match arity(&node.name) {
+            Some(expected) if expected != node.args.len() => Err(message(
+                "generic_arity",
+                &[&node.name, &expected, &node.args.len()],
+            )),
+            _ => Ok(node),
+        }
+    }
+
+    // Swaps every name alias knows for the rust type it stands for
+    pub fn resolve(&mut self, alias: &mut dyn FnMut(&str) -> Option<String>) {
+        if let Some(target) = alias(&self.name) {
+            self.name = target;
+        }
+        for arg in &mut self.args {
+            arg.resolve(alias);
+        }
+    }
+
+    // The rust type, like map_type gives for the text
+    pub fn rust(&self) -> String {
+        let name = match self.name.as_str() {
+            "int" => "i32",
+            "float" => "f32",
+            "string" => STRING_TYPE,
+            "vec" => VEC_TYPE,
+            "map" => MAP_TYPE,
+            "thread" => THREAD_TYPE,
+            name => name,
+        };
+        if self.args.is_empty() {
+            return name.to_string();
+        }
+        let args: Vec<String> = self.args.iter().map(TypeNode::rust).collect();
+        format!("{}<{}>", name, args.join(", "))
+    }
+}
+
+// Back in wyst, with one space after each comma
+impl fmt::Display for TypeNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.args.is_empty() {
+            let args: Vec<String> = self.args.iter().map(|arg| arg.to_string()).collect();
+            write!(f, "<{}>", args.join(", "))?;
+        }
+        Ok(())
+    }
+}
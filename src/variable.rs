@@ -4,8 +4,10 @@ use rand::Rng;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    lexer::LexerState,
-    lspcom::{Problem, ProblemType},
+    intrinsic::split_args,
+    lexer::{inner_state, lex, map_type, LexerState, Token, TokenType},
+    lspcom::{closest, Problem, ProblemType},
+    messages::message,
     transpiler::Transpiler,
 };
 
@@ -16,6 +18,7 @@ pub enum VariableType {
     Keyword,
     Struct,
     Namespace,
+    Alias,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -25,6 +28,106 @@ pub struct Variable {
     pub state: LexerState,
     pub params: Variables,
     pub rname: String,
+    #[serde(default)]
+    pub size: Option<usize>,
+    // The declared type of a variable, as rust
+    #[serde(default)]
+    pub data_type: Option<String>,
+    // The since/message part of the warning for a declaration marked @deprecated
+    #[serde(default)]
+    pub deprecated: Option<String>,
+    // The parameters of a function, in order
+    #[serde(default)]
+    pub signature: Vec<Param>,
+    // The fields of a struct, in order
+    #[serde(default)]
+    pub fields: Vec<Field>,
+    // The element type of an array, as rust
+    #[serde(default)]
+    pub element: Option<String>,
+    // The value of a const, worked out while parsing
+    #[serde(default)]
+    pub value: Option<i64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Param {
+    pub name: String,
+    // As written in wyst, map_type gives the rust one
+    pub data_type: String,
+    // The expression after `=`, if the parameter has one
+    pub default: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Field {
+    pub name: String,
+    // As rust, like the data_type of a Variable
+    pub data_type: String,
+    // The comment above it, if any
+    pub desc: String,
+}
+
+/*Reads the fields of a struct from its Curly body, `int x, vec<int> xs` gives x and xs with
+the comment above each one. Anything that isn't a type followed by a name is skipped*/
+pub fn parse_fields(body: &Token) -> Vec<Field> {
+    let Ok(tokens) = lex(body.value.as_str(), false, inner_state(body)) else {
+        return Vec::new();
+    };
+    let mut fields = Vec::new();
+    let mut desc = String::new();
+    let mut decl: Vec<&Token> = Vec::new();
+    // A None at the end closes the last field, it doesn't need a comma after it
+    for token in tokens.iter().map(Some).chain([None]) {
+        match token {
+            Some(token) if token.token_type == TokenType::Comment => desc = token.value.clone(),
+            Some(token) if token.value != "," && token.token_type != TokenType::Semicolon => {
+                decl.push(token)
+            }
+            _ => {
+                if let Some((name, data_type)) = decl.split_last() {
+                    if name.token_type == TokenType::Identifier && !data_type.is_empty() {
+                        fields.push(Field {
+                            name: name.value.clone(),
+                            data_type: map_type(
+                                &data_type
+                                    .iter()
+                                    .map(|token| match token.token_type {
+                                        TokenType::Angle => format!("<{}>", token.value),
+                                        _ => token.value.clone(),
+                                    })
+                                    .collect::<String>(),
+                            ),
+                            desc: desc.clone(),
+                        });
+                    }
+                }
+                decl.clear();
+                desc.clear();
+            }
+        }
+    }
+    fields
+}
+
+/*Reads the parameter list of a function declaration, `int w, vec<int> xs = []` gives w and
+xs. Parameters without a name are skipped*/
+pub fn parse_params(list: &str) -> Vec<Param> {
+    let mut params = Vec::new();
+    for param in split_args(list) {
+        let (decl, default) = match param.split_once('=') {
+            Some((decl, default)) => (decl.trim(), Some(default.trim().to_string())),
+            None => (param.trim(), None),
+        };
+        if let Some((data_type, name)) = decl.rsplit_once(|c: char| c.is_whitespace() || c == '*') {
+            params.push(Param {
+                name: name.to_string(),
+                data_type: data_type.trim().to_string(),
+                default,
+            });
+        }
+    }
+    params
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -45,6 +148,13 @@ impl Variables {
                         vars: HashMap::new(),
                     },
                     rname: "".to_string(),
+                    size: None,
+                    data_type: None,
+                    deprecated: None,
+                    signature: Vec::new(),
+                    fields: Vec::new(),
+                    element: None,
+                    value: None,
                 },
             )]),
         }
@@ -60,6 +170,42 @@ impl Variables {
                     vars: HashMap::new(),
                 },
                 rname: generate_varname(),
+                size: None,
+                data_type: None,
+                deprecated: None,
+                signature: Vec::new(),
+                fields: Vec::new(),
+                element: None,
+                value: None,
+            },
+        );
+    }
+
+    pub fn new_array(
+        &mut self,
+        name: String,
+        state: LexerState,
+        desc: String,
+        element: String,
+        size: Option<usize>,
+    ) {
+        self.vars.insert(
+            name,
+            Variable {
+                vtype: VariableType::Var,
+                desc,
+                state,
+                params: Variables {
+                    vars: HashMap::new(),
+                },
+                rname: generate_varname(),
+                size,
+                data_type: size.map(|size| format!("[{}; {}]", element, size)),
+                deprecated: None,
+                signature: Vec::new(),
+                fields: Vec::new(),
+                element: Some(element),
+                value: None,
             },
         );
     }
@@ -75,10 +221,55 @@ impl Variables {
                     vars: HashMap::new(),
                 },
                 rname: generate_varname(),
+                size: None,
+                data_type: None,
+                deprecated: None,
+                signature: Vec::new(),
+                fields: Vec::new(),
+                element: None,
+                value: None,
+            },
+        );
+    }
+
+    // A type alias, its data_type is the type it stands for as rust
+    pub fn new_alias(&mut self, name: String, state: LexerState, desc: String, target: String) {
+        self.vars.insert(
+            name.clone(),
+            Variable {
+                vtype: VariableType::Alias,
+                desc,
+                state,
+                params: Variables {
+                    vars: HashMap::new(),
+                },
+                rname: name,
+                size: None,
+                data_type: Some(target),
+                deprecated: None,
+                signature: Vec::new(),
+                fields: Vec::new(),
+                element: None,
+                value: None,
             },
         );
     }
 
+    pub fn new_const(
+        &mut self,
+        name: String,
+        state: LexerState,
+        desc: String,
+        data_type: String,
+        value: Option<i64>,
+    ) {
+        self.new_var(name.clone(), state, desc);
+        if let Some(var) = self.get_mut(name) {
+            var.data_type = Some(data_type);
+            var.value = value;
+        }
+    }
+
     pub fn new_struct(&mut self, name: String, state: LexerState, desc: String) {
         self.vars.insert(
             name,
@@ -90,6 +281,13 @@ impl Variables {
                     vars: HashMap::new(),
                 },
                 rname: generate_varname(),
+                size: None,
+                data_type: None,
+                deprecated: None,
+                signature: Vec::new(),
+                fields: Vec::new(),
+                element: None,
+                value: None,
             },
         );
     }
@@ -120,6 +318,13 @@ impl Variables {
                     vars: HashMap::new(),
                 },
                 rname: generate_varname(),
+                size: None,
+                data_type: None,
+                deprecated: None,
+                signature: Vec::new(),
+                fields: Vec::new(),
+                element: None,
+                value: None,
             },
         );
     }
@@ -134,6 +339,13 @@ impl Variables {
                     vars: HashMap::new(),
                 },
                 rname: generate_varname(),
+                size: None,
+                data_type: None,
+                deprecated: None,
+                signature: Vec::new(),
+                fields: Vec::new(),
+                element: None,
+                value: None,
             },
         );
     }
@@ -141,9 +353,18 @@ impl Variables {
         if let Some(x) = self.get_mut(name.clone()) {
             return x.rname.clone();
         } else {
+            let suggestion = match closest(&name, self.vars.keys().map(|key| key.as_str())) {
+                Some(known) => {
+                    root.suggestions.push((name.clone(), known.to_string()));
+                    message("did_you_mean", &[&known])
+                }
+                None => String::new(),
+            };
             root.problems.push(Problem {
                 problem_type: ProblemType::VariableNotFound,
-                problem_msg: format!("Variable '{}' doesn't exist", &name),
+                problem_msg: message("variable_not_found", &[&name, &suggestion]),
+                state: LexerState { line: 0, column: 0 },
+                file: root.file.clone(),
             });
             return name;
         }
@@ -156,6 +377,24 @@ impl Variables {
     pub fn get_mut(&mut self, name: String) -> Option<&mut Variable> {
         self.vars.get_mut(&name)
     }
+    // The type an alias stands for, None when name isn't an alias
+    pub fn alias(&mut self, name: &str) -> Option<String> {
+        self.get_mut(name.to_string())
+            .filter(|var| var.vtype == VariableType::Alias)
+            .and_then(|var| var.data_type.clone())
+    }
+    // The rust name of a method from an impl of the struct a variable holds
+    pub fn method(&mut self, value: &str, method: &str) -> Option<String> {
+        let data_type = self.get_mut(value.to_string())?.data_type.clone()?;
+        let target = self.get_mut(data_type)?;
+        if target.vtype != VariableType::Struct {
+            return None;
+        }
+        target
+            .params
+            .get_mut(method.to_string())
+            .map(|var| var.rname.clone())
+    }
     pub fn expand(&mut self, vars: Variables) {
         for (x, y) in vars.vars {
             self.vars.insert(x, y);